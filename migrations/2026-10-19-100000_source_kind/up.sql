@@ -0,0 +1,3 @@
+/****************************************************************************************************/
+/* Kind of the source file: 'zip' - multi-book archive, 'fb2.zip' - single zipped book, 'fb2' - plain book */
+ALTER TABLE archives ADD COLUMN arch_kind TEXT NOT NULL DEFAULT 'zip';
@@ -0,0 +1,2 @@
+DROP INDEX IF EXISTS books_crc32_idx;
+DROP TABLE IF EXISTS documents;
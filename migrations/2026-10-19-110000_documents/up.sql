@@ -0,0 +1,19 @@
+/****************************************************************************************************/
+/* canon_id is the id of the earliest indexed book considered to be the same book */
+CREATE TABLE documents (
+  id          INTEGER NOT NULL PRIMARY KEY,
+  book_id     INTEGER NOT NULL UNIQUE ON CONFLICT IGNORE REFERENCES books(id),
+  canon_id    INTEGER NOT NULL REFERENCES books(id),
+  doc_id      TEXT NOT NULL,
+  doc_version TEXT NOT NULL,
+  doc_date    TEXT NOT NULL,
+  match_key   TEXT NOT NULL
+);
+CREATE INDEX documents_canon_id_idx  ON documents (canon_id);
+CREATE INDEX documents_doc_id_idx    ON documents (doc_id);
+CREATE INDEX documents_match_key_idx ON documents (match_key);
+CREATE INDEX books_crc32_idx         ON books (book_crc32, book_size);
+
+/* Books loaded before this migration have no document info, use 'fb2loader dedup' to match them */
+INSERT INTO documents (book_id, canon_id, doc_id, doc_version, doc_date, match_key)
+SELECT id, id, '', '', '', '' FROM books;
@@ -0,0 +1,19 @@
+DROP TABLE IF EXISTS isbns;
+DROP TABLE IF EXISTS publications;
+
+CREATE TABLE documents_backup AS SELECT id, book_id, canon_id, doc_id, doc_version, doc_date, match_key FROM documents;
+DROP TABLE documents;
+CREATE TABLE documents (
+  id          INTEGER NOT NULL PRIMARY KEY,
+  book_id     INTEGER NOT NULL UNIQUE ON CONFLICT IGNORE REFERENCES books(id),
+  canon_id    INTEGER NOT NULL REFERENCES books(id),
+  doc_id      TEXT NOT NULL,
+  doc_version TEXT NOT NULL,
+  doc_date    TEXT NOT NULL,
+  match_key   TEXT NOT NULL
+);
+INSERT INTO documents SELECT * FROM documents_backup;
+DROP TABLE documents_backup;
+CREATE INDEX documents_canon_id_idx  ON documents (canon_id);
+CREATE INDEX documents_doc_id_idx    ON documents (doc_id);
+CREATE INDEX documents_match_key_idx ON documents (match_key);
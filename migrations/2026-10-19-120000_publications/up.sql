@@ -0,0 +1,24 @@
+/****************************************************************************************************/
+ALTER TABLE documents ADD COLUMN program_used TEXT NOT NULL DEFAULT '';
+ALTER TABLE documents ADD COLUMN src_url      TEXT NOT NULL DEFAULT '';
+ALTER TABLE documents ADD COLUMN creator      TEXT NOT NULL DEFAULT '';
+
+/****************************************************************************************************/
+CREATE TABLE publications (
+  id          INTEGER NOT NULL PRIMARY KEY,
+  book_id     INTEGER NOT NULL UNIQUE ON CONFLICT IGNORE REFERENCES books(id),
+  book_name   TEXT NOT NULL,
+  publisher   TEXT NOT NULL,
+  city        TEXT NOT NULL,
+  year        TEXT NOT NULL
+);
+
+/* isbn_key is the ISBN without dashes and spaces used for lookup */
+CREATE TABLE isbns (
+  id          INTEGER NOT NULL PRIMARY KEY,
+  book_id     INTEGER NOT NULL REFERENCES books(id),
+  isbn        TEXT NOT NULL,
+  isbn_key    TEXT NOT NULL,
+  CONSTRAINT u_isbns UNIQUE(book_id, isbn_key) ON CONFLICT IGNORE
+);
+CREATE INDEX isbn_key_idx ON isbns (isbn_key);
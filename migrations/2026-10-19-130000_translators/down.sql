@@ -0,0 +1,61 @@
+DROP TABLE IF EXISTS originals;
+
+DROP VIEW IF EXISTS full_view;
+DROP VIEW IF EXISTS author_title_view;
+DROP VIEW IF EXISTS translators_view;
+DROP VIEW IF EXISTS authors_view;
+
+CREATE TABLE author_links_backup AS SELECT id, book_id, author_id FROM author_links WHERE role = 'author';
+DROP TABLE author_links;
+CREATE TABLE author_links (
+  id          INTEGER NOT NULL PRIMARY KEY,
+  book_id     INTEGER NOT NULL REFERENCES books(id),
+  author_id   INTEGER NOT NULL REFERENCES authors(id),
+  CONSTRAINT u_authors UNIQUE(book_id, author_id) ON CONFLICT IGNORE
+);
+INSERT INTO author_links (id, book_id, author_id) SELECT id, book_id, author_id FROM author_links_backup;
+DROP TABLE author_links_backup;
+
+CREATE VIEW authors_view AS
+SELECT
+	book_id,
+	first_name,
+	middle_name,
+	last_name,
+	nickname,
+	uuid
+FROM author_links LEFT JOIN authors ON (author_id = authors.id);
+
+CREATE VIEW author_title_view AS
+SELECT
+	first_name,
+	middle_name,
+	last_name,
+	book_title
+FROM author_links
+LEFT JOIN title_links ON (author_links.book_id = title_links.book_id)
+LEFT JOIN authors ON (author_links.author_id = authors.id)
+LEFT JOIN titles ON (title_links.title_id = titles.id);
+
+CREATE VIEW full_view AS
+SELECT
+	arch_id,
+	arch_name,
+	arch_home,
+	arch_size,
+	arch_uuid,
+	books_view.book_id,
+	book_file,
+	book_zip_size,
+	book_size,
+	book_crc32,
+	book_offset,
+	book_title,
+	first_name,
+	middle_name,
+	last_name,
+	genre_name
+FROM books_view
+LEFT JOIN titles_view  ON books_view.book_id = titles_view.book_id
+LEFT JOIN authors_view ON books_view.book_id = authors_view.book_id
+LEFT JOIN genres_view  ON books_view.book_id = genres_view.book_id;
@@ -0,0 +1,13 @@
+/****************************************************************************************************/
+/* The name variants merged into the author, the loader links books with such names to author_id */
+CREATE TABLE author_aliases (
+  id          INTEGER NOT NULL PRIMARY KEY,
+  first_name  TEXT NOT NULL,
+  middle_name TEXT NOT NULL,
+  last_name   TEXT NOT NULL,
+  nickname    TEXT NOT NULL,
+  uuid        TEXT NOT NULL,
+  author_id   INTEGER NOT NULL REFERENCES authors(id),
+  CONSTRAINT u_author_aliases UNIQUE(first_name, middle_name, last_name, nickname, uuid) ON CONFLICT REPLACE
+);
+CREATE INDEX author_aliases_author_idx ON author_aliases (author_id);
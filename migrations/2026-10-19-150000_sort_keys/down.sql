@@ -0,0 +1,26 @@
+CREATE TABLE authors_backup AS SELECT id, first_name, middle_name, last_name, nickname, uuid FROM authors;
+DROP TABLE authors;
+CREATE TABLE authors (
+  id          INTEGER NOT NULL PRIMARY KEY,
+  first_name  TEXT NOT NULL,
+  middle_name TEXT NOT NULL,
+  last_name   TEXT NOT NULL,
+  nickname    TEXT NOT NULL,
+  uuid        TEXT NOT NULL,
+  CONSTRAINT u_authors UNIQUE(first_name, middle_name, last_name, nickname, uuid) ON CONFLICT IGNORE
+);
+INSERT INTO authors SELECT * FROM authors_backup;
+DROP TABLE authors_backup;
+CREATE INDEX first_name_idx   ON authors (first_name  COLLATE NOCASE);
+CREATE INDEX middle_name_idx  ON authors (middle_name COLLATE NOCASE);
+CREATE INDEX last_name_idx    ON authors (last_name   COLLATE NOCASE);
+CREATE INDEX nickname_idx     ON authors (nickname    COLLATE NOCASE);
+
+CREATE TABLE titles_backup AS SELECT id, book_title FROM titles;
+DROP TABLE titles;
+CREATE TABLE titles (
+  id          INTEGER NOT NULL PRIMARY KEY,
+  book_title  TEXT NOT NULL
+);
+INSERT INTO titles SELECT * FROM titles_backup;
+DROP TABLE titles_backup;
@@ -0,0 +1,15 @@
+/****************************************************************************************************/
+/* Folded (lowercased, 'ё' replaced by 'е') values used by navigation, filled by the loader */
+ALTER TABLE authors ADD COLUMN first_name_key  TEXT NOT NULL DEFAULT '';
+ALTER TABLE authors ADD COLUMN middle_name_key TEXT NOT NULL DEFAULT '';
+ALTER TABLE authors ADD COLUMN last_name_key   TEXT NOT NULL DEFAULT '';
+CREATE INDEX first_name_key_idx  ON authors (first_name_key);
+CREATE INDEX middle_name_key_idx ON authors (middle_name_key);
+CREATE INDEX last_name_key_idx   ON authors (last_name_key);
+
+ALTER TABLE titles ADD COLUMN book_title_key TEXT NOT NULL DEFAULT '';
+CREATE INDEX book_title_key_idx ON titles (book_title_key);
+
+/* Good enough for ASCII names until 'fb2loader reindex' is run */
+UPDATE authors SET first_name_key = lower(first_name), middle_name_key = lower(middle_name), last_name_key = lower(last_name);
+UPDATE titles SET book_title_key = lower(book_title);
@@ -0,0 +1,2 @@
+DROP TABLE series_links;
+DROP TABLE series;
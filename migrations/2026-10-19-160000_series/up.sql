@@ -0,0 +1,20 @@
+/****************************************************************************************************/
+CREATE TABLE series (
+  id              INTEGER NOT NULL PRIMARY KEY,
+  series_name     TEXT NOT NULL,
+  series_name_key TEXT NOT NULL DEFAULT '',
+  CONSTRAINT u_series UNIQUE(series_name) ON CONFLICT IGNORE
+);
+CREATE INDEX series_name_key_idx ON series (series_name_key);
+
+CREATE TABLE series_links (
+  id          INTEGER NOT NULL PRIMARY KEY,
+  book_id     INTEGER NOT NULL,
+  series_id   INTEGER NOT NULL,
+  serno       INTEGER NOT NULL DEFAULT 0,
+  FOREIGN KEY(book_id) REFERENCES books(id),
+  FOREIGN KEY(series_id) REFERENCES series(id),
+  CONSTRAINT u_series_links UNIQUE(book_id, series_id) ON CONFLICT IGNORE
+);
+CREATE INDEX series_links_book_idx ON series_links (book_id);
+CREATE INDEX series_links_series_idx ON series_links (series_id);
@@ -0,0 +1,29 @@
+CREATE TABLE archives_backup AS SELECT id, arch_name, arch_home, arch_size, arch_uuid, arch_done, arch_kind FROM archives;
+DROP TABLE archives;
+CREATE TABLE archives (
+  id         INTEGER NOT NULL PRIMARY KEY,
+  arch_name  TEXT NOT NULL,
+  arch_home  TEXT NOT NULL,
+  arch_size  BIGINT NOT NULL,
+  arch_uuid  TEXT NOT NULL UNIQUE ON CONFLICT IGNORE,
+  arch_done  BOOLEAN NOT NULL DEFAULT 0,
+  arch_kind  TEXT NOT NULL DEFAULT 'zip'
+);
+INSERT INTO archives SELECT * FROM archives_backup;
+DROP TABLE archives_backup;
+
+CREATE TABLE books_backup AS SELECT id, arch_id, book_file, book_zip_size, book_size, book_crc32, book_offset FROM books;
+DROP TABLE books;
+CREATE TABLE books (
+  id              INTEGER NOT NULL PRIMARY KEY,
+  arch_id         INTEGER NOT NULL REFERENCES archives(id),
+  book_file       TEXT NOT NULL,
+  book_zip_size   BIGINT NOT NULL,
+  book_size       BIGINT NOT NULL,
+  book_crc32      BIGINT NOT NULL,
+  book_offset     BIGINT NOT NULL,
+  CONSTRAINT u_books UNIQUE(arch_id, book_file, book_crc32) ON CONFLICT IGNORE
+);
+INSERT INTO books SELECT * FROM books_backup;
+DROP TABLE books_backup;
+CREATE INDEX books_crc32_idx ON books (book_crc32, book_size);
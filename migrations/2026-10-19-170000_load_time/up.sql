@@ -0,0 +1,5 @@
+/****************************************************************************************************/
+/* UTC load time as 'YYYY-MM-DD HH:MM:SS', comparable with datetime(), empty for the earlier loads */
+ALTER TABLE archives ADD COLUMN arch_loaded TEXT NOT NULL DEFAULT '';
+ALTER TABLE books ADD COLUMN book_loaded TEXT NOT NULL DEFAULT '';
+CREATE INDEX books_loaded_idx ON books (book_loaded);
@@ -0,0 +1,3 @@
+DROP TABLE shelves;
+DROP TABLE sessions;
+DROP TABLE users;
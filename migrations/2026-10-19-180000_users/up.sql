@@ -0,0 +1,31 @@
+/****************************************************************************************************/
+CREATE TABLE users (
+  id              INTEGER NOT NULL PRIMARY KEY,
+  user_name       TEXT NOT NULL,
+  password_hash   TEXT NOT NULL,
+  user_created    TEXT NOT NULL DEFAULT '',
+  CONSTRAINT u_users UNIQUE(user_name)
+);
+
+CREATE TABLE sessions (
+  id              INTEGER NOT NULL PRIMARY KEY,
+  user_id         INTEGER NOT NULL,
+  token           TEXT NOT NULL,
+  session_created TEXT NOT NULL DEFAULT '',
+  FOREIGN KEY(user_id) REFERENCES users(id),
+  CONSTRAINT u_sessions UNIQUE(token)
+);
+CREATE INDEX sessions_user_idx ON sessions (user_id);
+
+/* book_id is the canonical book of the versions, the shelf is 'want', 'reading' or 'read' */
+CREATE TABLE shelves (
+  id              INTEGER NOT NULL PRIMARY KEY,
+  user_id         INTEGER NOT NULL,
+  book_id         INTEGER NOT NULL,
+  shelf           TEXT NOT NULL,
+  shelf_added     TEXT NOT NULL DEFAULT '',
+  FOREIGN KEY(user_id) REFERENCES users(id),
+  FOREIGN KEY(book_id) REFERENCES books(id),
+  CONSTRAINT u_shelves UNIQUE(user_id, book_id) ON CONFLICT REPLACE
+);
+CREATE INDEX shelves_user_idx ON shelves (user_id, shelf);
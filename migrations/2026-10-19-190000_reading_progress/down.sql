@@ -0,0 +1,14 @@
+DROP TABLE reading_progress;
+DROP TABLE book_digests;
+
+CREATE TABLE users_backup AS SELECT id, user_name, password_hash, user_created FROM users;
+DROP TABLE users;
+CREATE TABLE users (
+  id              INTEGER NOT NULL PRIMARY KEY,
+  user_name       TEXT NOT NULL,
+  password_hash   TEXT NOT NULL,
+  user_created    TEXT NOT NULL DEFAULT '',
+  CONSTRAINT u_users UNIQUE(user_name)
+);
+INSERT INTO users SELECT * FROM users_backup;
+DROP TABLE users_backup;
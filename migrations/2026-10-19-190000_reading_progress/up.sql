@@ -0,0 +1,27 @@
+/****************************************************************************************************/
+/* PBKDF2 hash of the MD5 of the password, KOReader sends the MD5 as the key */
+ALTER TABLE users ADD COLUMN sync_key TEXT NOT NULL DEFAULT '';
+
+/* KOReader partial MD5 of the files served by the download links */
+CREATE TABLE book_digests (
+  id              INTEGER NOT NULL PRIMARY KEY,
+  book_id         INTEGER NOT NULL,
+  document        TEXT NOT NULL,
+  FOREIGN KEY(book_id) REFERENCES books(id),
+  CONSTRAINT u_book_digests UNIQUE(document) ON CONFLICT IGNORE
+);
+CREATE INDEX book_digests_book_idx ON book_digests (book_id);
+
+/* The last reading position of the document reported by the device of the user */
+CREATE TABLE reading_progress (
+  id              INTEGER NOT NULL PRIMARY KEY,
+  user_id         INTEGER NOT NULL,
+  document        TEXT NOT NULL,
+  progress        TEXT NOT NULL,
+  percentage      REAL NOT NULL DEFAULT 0,
+  device          TEXT NOT NULL DEFAULT '',
+  device_id       TEXT NOT NULL DEFAULT '',
+  progress_time   BIGINT NOT NULL DEFAULT 0,
+  FOREIGN KEY(user_id) REFERENCES users(id),
+  CONSTRAINT u_reading_progress UNIQUE(user_id, document) ON CONFLICT REPLACE
+);
@@ -0,0 +1,3 @@
+DROP TABLE tag_links;
+DROP TABLE tags;
+DROP TABLE reviews;
@@ -0,0 +1,34 @@
+/****************************************************************************************************/
+/* The annotations of the users, book_id is the canonical book of the versions */
+CREATE TABLE reviews (
+  id              INTEGER NOT NULL PRIMARY KEY,
+  user_id         INTEGER NOT NULL,
+  book_id         INTEGER NOT NULL,
+  rating          INTEGER NOT NULL DEFAULT 0, /* 1..5 stars, 0 is not rated */
+  review          TEXT NOT NULL DEFAULT '',
+  review_added    TEXT NOT NULL DEFAULT '',
+  FOREIGN KEY(user_id) REFERENCES users(id),
+  FOREIGN KEY(book_id) REFERENCES books(id),
+  CONSTRAINT u_reviews UNIQUE(user_id, book_id) ON CONFLICT REPLACE
+);
+CREATE INDEX reviews_book_idx ON reviews (book_id);
+
+CREATE TABLE tags (
+  id              INTEGER NOT NULL PRIMARY KEY,
+  tag_name        TEXT NOT NULL,
+  tag_name_key    TEXT NOT NULL DEFAULT '',
+  CONSTRAINT u_tags UNIQUE(tag_name) ON CONFLICT IGNORE
+);
+
+CREATE TABLE tag_links (
+  id              INTEGER NOT NULL PRIMARY KEY,
+  user_id         INTEGER NOT NULL,
+  book_id         INTEGER NOT NULL,
+  tag_id          INTEGER NOT NULL,
+  FOREIGN KEY(user_id) REFERENCES users(id),
+  FOREIGN KEY(book_id) REFERENCES books(id),
+  FOREIGN KEY(tag_id) REFERENCES tags(id),
+  CONSTRAINT u_tag_links UNIQUE(user_id, book_id, tag_id) ON CONFLICT IGNORE
+);
+CREATE INDEX tag_links_book_idx ON tag_links (book_id);
+CREATE INDEX tag_links_tag_idx ON tag_links (tag_id);
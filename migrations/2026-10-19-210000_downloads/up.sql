@@ -0,0 +1,13 @@
+/****************************************************************************************************/
+/* The files served by the download links, the user is empty for the anonymous downloads */
+CREATE TABLE downloads (
+  id              INTEGER NOT NULL PRIMARY KEY,
+  book_id         INTEGER NOT NULL,
+  user_id         INTEGER,
+  download_format TEXT NOT NULL,
+  download_time   TEXT NOT NULL,
+  FOREIGN KEY(book_id) REFERENCES books(id),
+  FOREIGN KEY(user_id) REFERENCES users(id)
+);
+CREATE INDEX downloads_book_idx ON downloads (book_id);
+CREATE INDEX downloads_time_idx ON downloads (download_time);
@@ -0,0 +1,17 @@
+CREATE TABLE archives_backup AS
+    SELECT id, arch_name, arch_home, arch_size, CASE WHEN arch_head = '' THEN arch_uuid ELSE arch_head END AS arch_uuid,
+        arch_done, arch_kind, arch_loaded
+    FROM archives;
+DROP TABLE archives;
+CREATE TABLE archives (
+  id         INTEGER NOT NULL PRIMARY KEY,
+  arch_name  TEXT NOT NULL,
+  arch_home  TEXT NOT NULL,
+  arch_size  BIGINT NOT NULL,
+  arch_uuid  TEXT NOT NULL UNIQUE ON CONFLICT IGNORE,
+  arch_done  BOOLEAN NOT NULL DEFAULT 0,
+  arch_kind  TEXT NOT NULL DEFAULT 'zip',
+  arch_loaded TEXT NOT NULL DEFAULT ''
+);
+INSERT INTO archives SELECT * FROM archives_backup;
+DROP TABLE archives_backup;
@@ -0,0 +1,5 @@
+/****************************************************************************************************/
+/* The MD5 of the first megabyte for the quick change detection, arch_uuid becomes the MD5 of the whole file */
+ALTER TABLE archives ADD COLUMN arch_head TEXT NOT NULL DEFAULT '';
+/* The existing hashes cover only the first megabyte, fb2loader rehash replaces them with the full ones */
+UPDATE archives SET arch_head = arch_uuid, arch_uuid = 'PREFIX:' || arch_uuid WHERE arch_uuid NOT LIKE '%:%';
@@ -0,0 +1,19 @@
+/****************************************************************************************************/
+/* The named directories of the library, arch_home of the archives inside them is relative to the root */
+CREATE TABLE roots (
+  id         INTEGER NOT NULL PRIMARY KEY,
+  root_name  TEXT NOT NULL UNIQUE,
+  root_path  TEXT NOT NULL
+);
+/* 0 for the archives outside the roots, their arch_home is absolute */
+ALTER TABLE archives ADD COLUMN root_id INTEGER NOT NULL DEFAULT 0;
+
+/* The absolute directory of the archive */
+CREATE VIEW archive_homes AS
+    SELECT archives.id AS arch_id,
+           CASE WHEN roots.id IS NULL THEN arch_home
+                WHEN arch_home = '' THEN root_path
+                ELSE root_path || '/' || arch_home
+           END AS arch_home
+    FROM archives
+    LEFT JOIN roots ON (roots.id = archives.root_id);
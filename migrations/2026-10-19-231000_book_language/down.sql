@@ -0,0 +1,18 @@
+CREATE TABLE books_backup AS
+    SELECT id, arch_id, book_file, book_zip_size, book_size, book_crc32, book_offset, book_loaded FROM books;
+DROP TABLE books;
+CREATE TABLE books (
+  id              INTEGER NOT NULL PRIMARY KEY,
+  arch_id         INTEGER NOT NULL REFERENCES archives(id),
+  book_file       TEXT NOT NULL,
+  book_zip_size   BIGINT NOT NULL,
+  book_size       BIGINT NOT NULL,
+  book_crc32      BIGINT NOT NULL,
+  book_offset     BIGINT NOT NULL,
+  book_loaded     TEXT NOT NULL DEFAULT '',
+  CONSTRAINT u_books UNIQUE(arch_id, book_file, book_crc32) ON CONFLICT IGNORE
+);
+INSERT INTO books SELECT * FROM books_backup;
+DROP TABLE books_backup;
+CREATE INDEX books_crc32_idx ON books (book_crc32, book_size);
+CREATE INDEX books_loaded_idx ON books (book_loaded);
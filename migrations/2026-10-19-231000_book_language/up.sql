@@ -0,0 +1,5 @@
+/****************************************************************************************************/
+/* The language of the book, empty for the earlier loads which accepted only the russian books */
+ALTER TABLE books ADD COLUMN book_lang TEXT NOT NULL DEFAULT '';
+/* The book marked deleted by the later INPX catalog, it is kept for the shelves and the annotations */
+ALTER TABLE books ADD COLUMN book_deleted BOOLEAN NOT NULL DEFAULT 0;
@@ -0,0 +1,2 @@
+/* The reset keys are recalculated by the next start of the service or the loader */
+SELECT 1;
@@ -0,0 +1,7 @@
+/****************************************************************************************************/
+/* The navigation keys are calculated by the loader in the alphabet order of normalize::sort_key,     */
+/* the keys filled by lower() and by the earlier folded format are stale. The empty keys of the named */
+/* rows are recalculated by the next start of the service or the loader.                              */
+UPDATE authors SET first_name_key = '', middle_name_key = '', last_name_key = '';
+UPDATE titles SET book_title_key = '';
+UPDATE series SET series_name_key = '';
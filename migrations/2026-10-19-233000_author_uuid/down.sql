@@ -0,0 +1 @@
+DROP INDEX author_uuid_idx;
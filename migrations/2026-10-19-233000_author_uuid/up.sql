@@ -0,0 +1,3 @@
+/****************************************************************************************************/
+/* The loader looks up the author by the FB2 author id before adding the new one */
+CREATE INDEX author_uuid_idx ON authors (uuid);
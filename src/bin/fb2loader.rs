@@ -1,110 +1,351 @@
-extern crate fb2parser;
 extern crate clap;
-extern crate zip;
+extern crate env_logger;
 
-use clap::{Arg, App, AppSettings};
-use fb2parser::FictionBook;
-use std::{fs, path};
-use std::convert::TryFrom;
-use std::collections::HashSet;
+use clap::{Arg, App, AppSettings, SubCommand};
+use std::io::Write;
+use std::path;
 use lib::database;
-use lib::models::Archive;
-use lib::parser;
+use lib::inpx;
+use lib::loader::Loader;
+use lib::models::{self, RootRecord};
+use lib::verifier::Verifier;
 
 
 fn main() {
+    // The progress and the problems of the load are printed as they are, RUST_LOG=warn keeps only the problems
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info"))
+        .format(|buf, record| writeln!(buf, "{}", record.args()))
+        .init();
     let selfname: String = std::env::args().nth(0).unwrap_or_default();
 
     let app = App::new(selfname)
         .version("1.0.0")
         .author("seb <seb@ukr.net>")
         .about("FictionBook Library database loader")
-        .arg(Arg::with_name("ARCHIVE.ZIP")
-            .help("Sets the input file to use")
+        .arg(Arg::with_name("SOURCE")
+            .help("Sets the input archive (.zip), book (.fb2, .fb2.zip) or directory with them to use")
             .required(true)
             .index(1)
         )
+        .subcommand(SubCommand::with_name("import-inpx")
+            .about("Imports the INPX catalog without decompressing the books")
+            .arg(Arg::with_name("INPX")
+                .help("Sets the INPX catalog to import")
+                .required(true)
+                .index(1)
+            )
+            .arg(Arg::with_name("archives")
+                .long("archives")
+                .value_name("DIR")
+                .help("Sets the directory with archives of the catalog (default: directory of the INPX)")
+                .takes_value(true)
+            )
+        )
+        .subcommand(SubCommand::with_name("export-inpx")
+            .about("Exports the indexed library into the INPX catalog")
+            .arg(Arg::with_name("INPX")
+                .help("Sets the INPX catalog to create")
+                .required(true)
+                .index(1)
+            )
+            .arg(Arg::with_name("name")
+                .long("name")
+                .value_name("NAME")
+                .help("Sets the collection name stored in collection.info")
+                .default_value("fb2c")
+                .takes_value(true)
+            )
+        )
+        .subcommand(SubCommand::with_name("remove")
+            .about("Removes the archive with its books and the authors, the titles, the genres and the series left without books")
+            .arg(Arg::with_name("ARCHIVE")
+                .help("Sets the id, the path or the file name of the loaded archive")
+                .required(true)
+                .index(1)
+            )
+        )
+        .subcommand(SubCommand::with_name("replace")
+            .about("Loads the new version of the archive keeping the unchanged books and removes the old one")
+            .arg(Arg::with_name("OLD")
+                .help("Sets the id, the path or the file name of the loaded archive")
+                .required(true)
+                .index(1)
+            )
+            .arg(Arg::with_name("NEW")
+                .help("Sets the new archive (.zip) or book (.fb2, .fb2.zip)")
+                .required(true)
+                .index(2)
+            )
+        )
+        .subcommand(SubCommand::with_name("verify")
+            .about("Checks the loaded archives and books against the files, exits with code 2 if problems remain")
+            .arg(Arg::with_name("full")
+                .long("full")
+                .help("Checks the MD5 of the whole archives and the CRC of every book")
+            )
+            .arg(Arg::with_name("search")
+                .long("search")
+                .value_name("DIR")
+                .help("Sets the directory to search for the moved archives besides the library roots, may be repeated")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+            )
+            .arg(Arg::with_name("fix")
+                .long("fix")
+                .help("Updates the paths of the moved archives and the offsets of the shifted books")
+            )
+        )
+        .subcommand(SubCommand::with_name("rehash")
+            .about("Replaces the first megabyte MD5 of the archives loaded by the earlier versions with the full one")
+            .arg(Arg::with_name("jobs")
+                .long("jobs")
+                .value_name("N")
+                .help("Sets the number of the archives read at the same time")
+                .default_value("1")
+                .takes_value(true)
+            )
+        )
+        .subcommand(SubCommand::with_name("roots")
+            .about("Lists the library roots")
+        )
+        .subcommand(SubCommand::with_name("rebase-root")
+            .about("Sets the directory of the library root, the archives inside it are stored relative to the root")
+            .arg(Arg::with_name("NAME")
+                .help("Sets the name of the root, e.g. librusec")
+                .required(true)
+                .index(1)
+            )
+            .arg(Arg::with_name("DIR")
+                .help("Sets the current directory of the root")
+                .required(true)
+                .index(2)
+            )
+        )
+        .subcommand(SubCommand::with_name("dedup")
+            .about("Recalculates the canonical books of the indexed library")
+        )
+        .subcommand(SubCommand::with_name("reindex")
+            .about("Recalculates the sort keys used by the navigation")
+        )
+        .subcommand(SubCommand::with_name("author-duplicates")
+            .about("Lists the authors which look like the same person")
+        )
+        .subcommand(SubCommand::with_name("merge-authors")
+            .about("Merges the name variants into the author")
+            .arg(Arg::with_name("all")
+                .long("all")
+                .help("Merges every group listed by author-duplicates into its first author")
+            )
+            .arg(Arg::with_name("AUTHOR")
+                .help("Sets the id of the author to keep")
+                .required_unless("all")
+                .index(1)
+            )
+            .arg(Arg::with_name("ALIAS")
+                .help("Sets the ids of the authors to merge")
+                .required_unless("all")
+                .multiple(true)
+                .index(2)
+            )
+        )
+        .subcommand(SubCommand::with_name("add-user")
+            .about("Adds the user of the service or changes the password and the progress sync key of the existing one")
+            .arg(Arg::with_name("USER")
+                .help("Sets the user name")
+                .required(true)
+                .index(1)
+            )
+            .arg(Arg::with_name("PASSWORD")
+                .help("Sets the password")
+                .required(true)
+                .index(2)
+            )
+        )
+        .subcommand(SubCommand::with_name("remove-user")
+            .about("Removes the user of the service with the sessions, the shelves, the reading progress and the annotations")
+            .arg(Arg::with_name("USER")
+                .help("Sets the user name")
+                .required(true)
+                .index(1)
+            )
+        )
+        .setting(AppSettings::SubcommandsNegateReqs)
         .setting(AppSettings::ArgRequiredElseHelp);
 
     let matches = app.get_matches();
-    let filename = matches.value_of("ARCHIVE.ZIP").unwrap();
-    println!("Using input file: {}", filename);
-    let path = path::Path::new(filename);
-    let file = fs::File::open(&path).unwrap();
-    let mut archive = zip::ZipArchive::new(file).unwrap();
-    let mut error_counter = 0;
-    let mut skip_counter = 0;
-    let mut manager = database::Manager::new();
-    let arch_id = match manager.save_archive(Archive::new(&path, md5sum(&path, false))) {
-        database::SaveResult::CacheHit(id) => {
-            println!("Archive already loaded into DB record id is {}", id);
-            id
+    let mut loader = Loader::new(database::Manager::new());
+    match matches.subcommand() {
+        ("import-inpx", Some(args)) => {
+            let filename = args.value_of("INPX").unwrap();
+            println!("Using catalog: {}", filename);
+            let inpx = path::Path::new(filename);
+            let home = args.value_of("archives")
+                .map(|dir| path::Path::new(dir))
+                .unwrap_or(inpx.parent().unwrap_or(path::Path::new(".")));
+            loader.import_inpx(inpx, home);
+            println!("Books not matched with archives: {} ", loader.mismatch_counter);
         },
-        database::SaveResult::Quered(id) => {
-            println!("Archive already loaded into DB record id is {}", id);
-            id
+        ("export-inpx", Some(args)) => {
+            let filename = args.value_of("INPX").unwrap();
+            println!("Exporting catalog: {}", filename);
+            let catalog = inpx::export_inpx(loader.manager.get_connection(), path::Path::new(filename), args.value_of("name").unwrap())
+                .expect(&format!("Can't write INPX catalog '{}'", filename));
+            let books: usize = catalog.iter().map(|inp| inp.records.len()).sum();
+            println!("Exported archives: {} ", catalog.len());
+            println!("Exported books: {} ", books);
+            return;
         },
-        database::SaveResult::Added(id) => {
-            id
-        }
-    };
-
-    let russian: HashSet<String> = vec!["ru", "rus", "russian", "ru-ru"]
-                .into_iter()
-                .map(|s| String::from(s))
-                .collect();
-
-    for i in 0..archive.len() {
-        let mut zip_file = archive.by_index(i).unwrap();
-        if let Some(_) = manager.find_book(arch_id, zip_file.name(), zip_file.crc32() as i64) {
-            continue
-        }
-        if let Some(header) = parser::load_header(&mut zip_file)
-        {
-            match FictionBook::try_from(header.as_bytes()) {
-                Ok(fb) => {
-                    let lang = if let Some(ref el) = fb.description.title_info.lang {
-                        &el.text
-                    } else {
-                        "ru"
-                    }.to_lowercase();
-
-                    if russian.contains(&lang) {
-                        let book_id = manager.save_book(arch_id, &zip_file).get_id();
-                        manager.save_content(book_id, &fb);
-                    } else {
-                        skip_counter += 1;
-                    }
+        ("remove", Some(args)) => {
+            let archive = find_archive(&loader, args.value_of("ARCHIVE").unwrap());
+            let removed = loader.manager.remove_archive(archive)
+                .expect(&format!("Failed to remove archive {}", archive));
+            println!("Archive {} removed with books: {} ", archive, removed);
+            return;
+        },
+        ("replace", Some(args)) => {
+            let archive = find_archive(&loader, args.value_of("OLD").unwrap());
+            let filename = args.value_of("NEW").unwrap();
+            println!("Replacing archive {} with: {}", archive, filename);
+            match loader.replace(archive, path::Path::new(filename)) {
+                Ok((moved, removed)) => {
+                    println!("Unchanged books kept: {} ", moved);
+                    println!("Old books removed: {} ", removed);
                 },
-                Err(err) =>  {
-                    println!("{} : {:?} '{}'", zip_file.name(), err, header);
-                    error_counter += 1;
+                Err(e) => {
+                    eprintln!("Failed to replace archive {}: {}", archive, e);
+                    std::process::exit(1);
                 }
             }
+        },
+        ("verify", Some(args)) => {
+            let search = args.values_of("search")
+                .map(|dirs| dirs.map(path::PathBuf::from).collect())
+                .unwrap_or_default();
+            let mut verifier = Verifier::new(search, args.is_present("full"), args.is_present("fix"));
+            let report = verifier.verify(loader.manager.get_connection()).expect("Failed to verify library");
+            for problem in report.problems.iter() {
+                println!("{}", problem);
+            }
+            println!("Archives checked: {} ", report.archives);
+            println!("Books checked: {} ", report.books);
+            println!("Problems found: {} ", report.problems.len());
+            println!("Problems fixed: {} ", report.fixed);
+            if report.problems.len() > report.fixed {
+                std::process::exit(2);
+            }
+            return;
+        },
+        ("rehash", Some(args)) => {
+            let jobs = args.value_of("jobs").unwrap().parse().expect("N must be a number");
+            let updated = loader.rehash(jobs).expect("Failed to rehash archives");
+            println!("Archives rehashed: {} ", updated);
+            println!("Archives failed: {} ", loader.error_counter);
+            return;
+        },
+        ("roots", Some(_)) => {
+            let roots = RootRecord::load_all(loader.manager.get_connection()).expect("Failed to load library roots");
+            for root in roots.iter() {
+                println!("{:>4} {} = {}", root.id, root.root_name, root.root_path);
+            }
+            println!("Library roots: {} ", roots.len());
+            return;
+        },
+        ("rebase-root", Some(args)) => {
+            let name = args.value_of("NAME").unwrap();
+            match loader.manager.rebase_root(name, path::Path::new(args.value_of("DIR").unwrap())) {
+                Ok((root, adopted)) => {
+                    println!("Root '{}' saved with id {}", name, root);
+                    println!("Archives moved into the root: {} ", adopted);
+                },
+                Err(e) => {
+                    eprintln!("{}", e);
+                    std::process::exit(1);
+                }
+            }
+            return;
+        },
+        ("dedup", Some(_)) => {
+            let duplicates = loader.manager.rebuild_documents().expect("Failed to rebuild documents");
+            println!("Duplicated books found: {} ", duplicates);
+            return;
+        },
+        ("reindex", Some(_)) => {
+            let (authors, titles, series) = loader.manager.reindex().expect("Failed to reindex navigation keys");
+            println!("Reindexed authors: {} ", authors);
+            println!("Reindexed titles: {} ", titles);
+            println!("Reindexed series: {} ", series);
+            return;
+        },
+        ("author-duplicates", Some(_)) => {
+            let groups = loader.manager.find_author_duplicates().expect("Failed to find duplicated authors");
+            for group in groups.iter() {
+                for (i, author) in group.iter().enumerate() {
+                    println!("{} {:>8} '{}' '{}' '{}' '{}' {}",
+                        if 0 == i { "*" } else { " " },
+                        author.id, author.last_name, author.first_name, author.middle_name, author.nickname, author.uuid);
+                }
+                println!();
+            }
+            println!("Groups of duplicated authors: {} ", groups.len());
+            return;
+        },
+        ("merge-authors", Some(args)) => {
+            let mut merges: Vec<(lib::models::Id, lib::models::Id)> = Vec::new();
+            if args.is_present("all") {
+                for group in loader.manager.find_author_duplicates().expect("Failed to find duplicated authors") {
+                    for alias in group.iter().skip(1) {
+                        merges.push((group[0].id, alias.id));
+                    }
+                }
+            } else {
+                let author = args.value_of("AUTHOR").unwrap().parse().expect("AUTHOR must be an author id");
+                for alias in args.values_of("ALIAS").unwrap() {
+                    merges.push((author, alias.parse().expect("ALIAS must be an author id")));
+                }
+            }
+            for (author, alias) in merges.iter() {
+                loader.manager.merge_authors(*author, *alias)
+                    .expect(&format!("Failed to merge author {} into {}", alias, author));
+            }
+            println!("Merged authors: {} ", merges.len());
+            return;
+        },
+        ("add-user", Some(args)) => {
+            let name = args.value_of("USER").unwrap();
+            let id = loader.manager.save_user(name, args.value_of("PASSWORD").unwrap())
+                .expect(&format!("Failed to save user '{}'", name));
+            println!("User '{}' saved with id {}", name, id);
+            return;
+        },
+        ("remove-user", Some(args)) => {
+            let name = args.value_of("USER").unwrap();
+            loader.manager.remove_user(name).expect(&format!("Failed to remove user '{}'", name));
+            println!("User '{}' removed", name);
+            return;
+        },
+        _ => {
+            let filename = matches.value_of("SOURCE").unwrap();
+            println!("Using input: {}", filename);
+            loader.load(path::Path::new(filename));
         }
     }
-    println!("Total books in archive: {} ", archive.len());
-    println!("Broken books found: {} ", error_counter);
-    println!("Skipped by language filter: {} ", skip_counter);
+    println!("Total books found: {} ", loader.total_counter);
+    println!("Broken books found: {} ", loader.error_counter);
+    println!("Skipped by language filter, deleted or already loaded: {} ", loader.skip_counter);
+    match models::reindex_stale_keys(loader.manager.get_connection()) {
+        Ok(ref reindexed) if reindexed.is_empty() => {},
+        Ok(reindexed) => println!("Reindexed navigation keys of {}", reindexed.join(", ")),
+        Err(e) => println!("Failed to reindex navigation keys, run 'fb2loader reindex': {}", e),
+    }
 }
 
-fn md5sum(path: &path::Path, complete: bool) -> String {
-    use std::io::prelude::*;
-    use std::fs::File;
-
-    let mut file = File::open(path).expect(&format!("Can't open file '{}'", path.to_string_lossy()));
-    let mut buffer = [0; 1024*1024];
-    let mut ctx = md5::Context::new();
-    while let Some(readed) = file.read(&mut buffer).ok() {
-        if readed > 0 {
-            ctx.write(&buffer[0..readed]).expect("Failed to calculate md5");
-            if !complete {
-                break;
-            }
-        } else {
-            break;
+fn find_archive(loader: &Loader, value: &str) -> lib::models::Id {
+    match loader.manager.find_archive_by(value) {
+        Ok(id) => id,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
         }
     }
-    format!("{:X}", ctx.compute())
 }
-
@@ -1,170 +1,1349 @@
 extern crate env_logger;
 #[macro_use]
 extern crate serde_json;
-use actix_files::NamedFile;
+use actix_files::{Files, NamedFile};
 
-use std::env;
+use clap::{App as Cli, Arg, ArgMatches};
 use lib::actions;
-use actix_web::{get, middleware, web, App, Error, HttpResponse, HttpServer};
-use handlebars::Handlebars;
+use lib::models::{self, ArchiveRecord, DownloadFormat, Id, Root, RootRecord, UserRecord};
+use lib::service_error::{self, ErrorBody, OrNotFound, ServiceError};
+use lib::config::{Config, Features};
+use lib::worker::{LibraryJob, LibraryWorker, LoadStatus, SharedStatus};
+use actix::Addr;
+use actix_web::{get, post, put, middleware, web, App, HttpMessage, HttpRequest, HttpResponse, HttpServer};
+use actix_web::body::{Body, ResponseBody};
+use actix_web::dev::ServiceResponse;
+use actix_web::http::StatusCode;
+use actix_web::middleware::errhandlers::{ErrorHandlerResponse, ErrorHandlers};
+use actix_web::http::{header, Cookie};
+use actix_web::cookie::SameSite;
+use handlebars::{Handlebars, Helper, HelperResult, Output, RenderContext};
+
+include!(concat!(env!("OUT_DIR"), "/version.rs"));
 
 struct Context<'a> {
     pub pool: actions::ConnectionPool,
     pub handlebars: Handlebars<'a>,
+    pub workdir: String,
+    pub database: String,
+    pub admins: Vec<String>,
+    pub library: String,
+    pub status: SharedStatus,
+    pub worker: Option<Addr<LibraryWorker>>,
 }
 impl<'a> Context<'a> {
-    pub fn new(pool: actions::ConnectionPool, handlebars: Handlebars<'a>) -> Self {
+    pub fn new(pool: actions::ConnectionPool, handlebars: Handlebars<'a>, config: &Config) -> Self {
         Self {
             pool: pool,
-            handlebars: handlebars
+            handlebars: handlebars,
+            workdir: config.workdir.clone(),
+            database: config.database.clone(),
+            admins: config.admins.clone(),
+            library: config.library.clone(),
+            status: SharedStatus::default(),
+            worker: None,
         }
     }
 }
 
 type WebCtx<'a> = web::Data<Context<'a>>;
-type WebResult = Result<HttpResponse, Error>;
-type FileResult = Result<NamedFile, Error>;
+type WebResult = Result<HttpResponse, ServiceError>;
+type FileResult = Result<NamedFile, ServiceError>;
+
+const OPDS_CONTENT_TYPE: &str = "application/atom+xml;profile=opds-catalog;charset=utf-8";
+const ATOM_CONTENT_TYPE: &str = "application/atom+xml;charset=utf-8";
+const SESSION_COOKIE: &str = "session";
+const BASIC_REALM: &str = "Basic realm=\"fb2c\"";
+
+/// The session cookie of the browser or the basic authorization of the OPDS client
+fn get_credentials(req: &HttpRequest) -> actions::Credentials {
+    if let Some(cookie) = req.cookie(SESSION_COOKIE) {
+        return actions::Credentials::Session(String::from(cookie.value()));
+    }
+    req.headers().get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| lib::auth::parse_basic_auth(value))
+        .map(|(name, password)| actions::Credentials::Basic(name, password))
+        .unwrap_or(actions::Credentials::Anonymous)
+}
+
+/// KOReader sends the user name and the MD5 of the password with every sync request
+fn get_sync_credentials(req: &HttpRequest) -> (String, String) {
+    let get = |name: &str| req.headers().get(name)
+        .and_then(|value| value.to_str().ok())
+        .map(String::from)
+        .unwrap_or_default();
+    (get("x-auth-user"), get("x-auth-key"))
+}
+
+fn sync_error(code: i32) -> HttpResponse {
+    let error = actions::SyncError::new(code);
+    match code {
+        actions::sync_context::ERROR_UNAUTHORIZED => HttpResponse::Unauthorized().json(&error),
+        _ => HttpResponse::Forbidden().json(&error),
+    }
+}
+
+fn redirect(location: &str) -> HttpResponse {
+    HttpResponse::SeeOther().header(header::LOCATION, location).finish()
+}
+
+/// Moves the legacy and the undecorated URLs to the canonical one keeping the query
+fn redirect_permanent(req: &HttpRequest, location: &str) -> HttpResponse {
+    let location = match req.query_string() {
+        "" => String::from(location),
+        query if location.contains('?') => format!("{}&{}", location, query),
+        query => format!("{}?{}", location, query),
+    };
+    HttpResponse::MovedPermanently().header(header::LOCATION, location).finish()
+}
+
+fn redirect_to_login(next: &str) -> HttpResponse {
+    redirect(&format!("/login?next={}", next))
+}
+
+/// Replaces the JSON body of the error with the rendered error page, the API keeps the JSON
+fn render_error<B>(mut res: ServiceResponse<B>) -> actix_web::Result<ErrorHandlerResponse<B>> {
+    let status = res.status();
+    let error = res.response().error()
+        .and_then(|error| error.as_error::<ServiceError>())
+        .map(|error| error.get_body());
+    let is_api = service_error::is_api_path(res.request().path());
+    if is_api && error.is_some() {
+        return Ok(ErrorHandlerResponse::Response(res));
+    }
+    let body = error.unwrap_or_else(|| ErrorBody::from_status(status));
+    let (content, content_type) = if is_api {
+        (json!(&body).to_string(), "application/json")
+    } else {
+        match res.request().app_data::<WebCtx<'static>>().map(|ctx| ctx.handlebars.render("error", &json!(&body))) {
+            Some(Ok(page)) => (page, "text/html; charset=utf-8"),
+            Some(Err(e)) => {
+                log::error!("{}", e);
+                return Ok(ErrorHandlerResponse::Response(res));
+            },
+            None => return Ok(ErrorHandlerResponse::Response(res)),
+        }
+    };
+    res.headers_mut().insert(header::CONTENT_TYPE, header::HeaderValue::from_static(content_type));
+    Ok(ErrorHandlerResponse::Response(res.map_body(|_, _| ResponseBody::Other(Body::from(content)))))
+}
+
+fn error_handlers<B: 'static>() -> ErrorHandlers<B> {
+    ErrorHandlers::new()
+        .handler(StatusCode::BAD_REQUEST, render_error)
+        .handler(StatusCode::FORBIDDEN, render_error)
+        .handler(StatusCode::NOT_FOUND, render_error)
+        .handler(StatusCode::INTERNAL_SERVER_ERROR, render_error)
+}
+
+/// The malformed query, form, path or JSON of the request
+fn bad_request<E: std::fmt::Display>(error: E, _: &HttpRequest) -> actix_web::Error {
+    ServiceError::BadRequest(error.to_string()).into()
+}
 
 #[get("/")]
 async fn root<'a>(ctx: WebCtx<'a>) -> WebResult {
-    let conn = ctx.pool.get().expect("couldn't get db connection from pool");
+    let conn = ctx.pool.get()?;
     let page = web::block(move|| actions::get_root_page(&conn))
-        .await
-        .map_err(|e| {
-            eprintln!("{}", e);
-            HttpResponse::InternalServerError().finish()})?;
+        .await?;
 
-    let body = ctx.handlebars.render("root", &json!(&page))
-                             .expect("couldn't render template");
+    let body = ctx.handlebars.render("root", &json!(&page))?;
 
     Ok(HttpResponse::Ok().body(body))
 }
 
 
+#[get("/search")]
+async fn search(query: web::Query<actions::SearchQuery>) -> HttpResponse {
+    redirect(&query.get_url())
+}
+
+#[get("/authors/")]
+async fn authors<'a>(ctx: WebCtx<'a>, mask: web::Query<actions::AuthorMask>, query: web::Query<actions::PageQuery>) -> WebResult {
+    let pattern = mask.into_inner();
+    let paging = actions::Paging::new(&query);
+    let conn = ctx.pool.get()?;
+    let page = web::block(move|| actions::get_authors_page(&conn, &pattern, paging))
+        .await?;
+
+    let body = ctx.handlebars.render("authors", &json!(&page))?;
+
+    Ok(HttpResponse::Ok().body(body))
+}
+
+/// The names of the search were the path with the '-' for the empty name
 #[get("/authors/{fname}/{mname}/{lname}/")]
-async fn authors<'a>(ctx: WebCtx<'a>, args: web::Path<(String, String, String)>) -> WebResult {
+async fn legacy_authors(req: HttpRequest, args: web::Path<(String, String, String)>) -> HttpResponse {
     let (first_name, middle_name, last_name) = args.into_inner();
-    let pattern = actions::AuthorMask::new(first_name, middle_name, last_name);
-    let conn = ctx.pool.get().expect("couldn't get db connection from pool");
-    let page = web::block(move|| actions::get_authors_page(&conn, "authors", &pattern))
-        .await
-        .map_err(|e| {
-            eprintln!("{}", e);
-            HttpResponse::InternalServerError().finish()})?;
+    let mask = actions::AuthorMask::new(first_name, middle_name, last_name);
+    redirect_permanent(&req, &mask.get_link("").get_url())
+}
 
-    let body = ctx.handlebars.render("authors", &json!(&page))
-                             .expect("couldn't render template");
+#[get("/author/{id:\\d+}/{slug}/")]
+async fn author<'a>(ctx: WebCtx<'a>, req: HttpRequest, args: web::Path<(Id, String)>, query: web::Query<actions::PageQuery>) -> WebResult {
+    let (id, _) = args.into_inner();
+    let mut paging = actions::Paging::new(&query);
+    let translated = paging.with_second(query.translated, "translated");
+    let conn = ctx.pool.get()?;
+    let page = web::block(move|| actions::load_author_ctx(&conn, id, paging, translated))
+        .await.or_not_found("Автор не найден")?;
+    if req.path() != page.get_url() {
+        return Ok(redirect_permanent(&req, &page.get_url()));
+    }
+
+    let body = ctx.handlebars.render("author", &json!(&page))?;
 
     Ok(HttpResponse::Ok().body(body))
 }
 
+#[get("/author/{id:\\d+}/")]
+async fn author_by_id<'a>(ctx: WebCtx<'a>, req: HttpRequest, args: web::Path<Id>) -> WebResult {
+    let id = args.into_inner();
+    let conn = ctx.pool.get()?;
+    let url = web::block(move|| actions::AuthorEntry::load(&conn, id).map(|entry| entry.get_url()))
+        .await.or_not_found("Автор не найден")?;
+    Ok(redirect_permanent(&req, &url))
+}
+
 #[get("/author/{fname}/{mname}/{lname}/")]
-async fn author<'a>(ctx: WebCtx<'a>, args: web::Path<(String, String, String)>) -> WebResult {
+async fn legacy_author<'a>(ctx: WebCtx<'a>, req: HttpRequest, args: web::Path<(String, String, String)>) -> WebResult {
     let (first_name, middle_name, last_name) = args.into_inner();
-    let author = actions::AuthorMask::new(first_name, middle_name, last_name);
-    let conn = ctx.pool.get().expect("couldn't get db connection from pool");
-    let page = web::block(move|| actions::get_author_ctx(&conn, "author", &author))
-        .await
-        .map_err(|e| {
-            eprintln!("{}", e);
-            HttpResponse::InternalServerError().finish()})?;
+    let mask = actions::AuthorMask::new(first_name, middle_name, last_name);
+    let conn = ctx.pool.get()?;
+    let url = web::block(move|| actions::find_author_url(&conn, &mask))
+        .await.or_not_found("Автор не найден")?;
+    Ok(redirect_permanent(&req, &url))
+}
 
-    let body = ctx.handlebars.render("author", &json!(&page))
-                             .expect("couldn't render template");
+#[get("/book/{id:\\d+}/{slug}/")]
+async fn book_page<'a>(ctx: WebCtx<'a>, req: HttpRequest, args: web::Path<(Id, String)>) -> WebResult {
+    let (id, _) = args.into_inner();
+    let credentials = get_credentials(&req);
+    let conn = ctx.pool.get()?;
+    let page = web::block(move|| actions::load_book_ctx(&conn, id, &credentials))
+        .await.or_not_found("Книга не найдена")?;
+    if page.versions.is_empty() {
+        return Err(ServiceError::NotFound(String::from("Книга не найдена")));
+    }
+    if req.path() != page.get_url() {
+        return Ok(redirect_permanent(&req, &page.get_url()));
+    }
+
+    let body = ctx.handlebars.render("title", &json!(&page))?;
 
     Ok(HttpResponse::Ok().body(body))
 }
 
-#[get("/title/{fname}/{mname}/{lname}/{title}/")]
-async fn title<'a>(ctx: WebCtx<'a>, args: web::Path<(String, String, String, String)>) -> WebResult {
+#[get("/book/{id:\\d+}/")]
+async fn book_by_id<'a>(ctx: WebCtx<'a>, req: HttpRequest, args: web::Path<Id>) -> WebResult {
+    let id = args.into_inner();
+    let conn = ctx.pool.get()?;
+    let url = web::block(move|| actions::TitleEntry::load_by_book(&conn, id).map(|entry| entry.get_url()))
+        .await.or_not_found("Книга не найдена")?;
+    Ok(redirect_permanent(&req, &url))
+}
 
+#[get("/title/{fname}/{mname}/{lname}/{title}/")]
+async fn legacy_title<'a>(ctx: WebCtx<'a>, req: HttpRequest, args: web::Path<(String, String, String, String)>) -> WebResult {
     let (first_name, middle_name, last_name, title) = args.into_inner();
-    let au = actions::AuthorMask::new(first_name, middle_name, last_name);
-    let conn = ctx.pool.get().expect("couldn't get db connection from pool");
-    let page = web::block(move|| actions::load_author_title_ctx(&conn, &au, &title))
-        .await
-        .map_err(|e| {
-            eprintln!("{}", e);
-            HttpResponse::InternalServerError().finish()})?;
+    let mask = actions::AuthorMask::new(first_name, middle_name, last_name);
+    let conn = ctx.pool.get()?;
+    let url = web::block(move|| actions::find_book_url(&conn, &mask, &title))
+        .await.or_not_found("Книга не найдена")?;
+    Ok(redirect_permanent(&req, &url))
+}
 
+#[get("/titles/{title}/")]
+async fn titles<'a>(ctx: WebCtx<'a>, args: web::Path<String>, query: web::Query<actions::PageQuery>) -> WebResult {
+    let book_title = args.into_inner();
+    let pattern = actions::TitleMask::new(book_title);
+    let paging = actions::Paging::new(&query);
+    let conn = ctx.pool.get()?;
+    let page = web::block(move|| actions::load_titles_page(&conn, "titles", &pattern, paging))
+        .await?;
 
-    let body = ctx.handlebars.render("title", &json!(&page))
-                             .expect("couldn't render template");
+    let body = ctx.handlebars.render("titles", &json!(&page))?;
 
     Ok(HttpResponse::Ok().body(body))
 }
 
-#[get("/titles/{title}/")]
-async fn titles<'a>(ctx: WebCtx<'a>, args: web::Path<String>) -> WebResult {
-    let book_title = args.into_inner();
-    let pattern = actions::TitleMask::new(book_title);
-    let conn = ctx.pool.get().expect("couldn't get db connection from pool");
-    let page = web::block(move|| actions::load_titles_page(&conn, "titles", &pattern))
-        .await
-        .map_err(|e| {
-            eprintln!("{}", e);
-            HttpResponse::InternalServerError().finish()})?;
+/// The Link header of the API listings: </api/authors?page=2&...>; rel="next"
+fn with_paging_links(mut builder: actix_web::dev::HttpResponseBuilder, path: &str, paging: &actions::Paging) -> actix_web::dev::HttpResponseBuilder {
+    let links: Vec<String> = vec![("prev", &paging.previous), ("next", &paging.next)].into_iter()
+        .filter_map(|(rel, query)| query.as_ref().map(|query| format!("<{}?{}>; rel=\"{}\"", path, query, rel)))
+        .collect();
+    if !links.is_empty() {
+        builder.header(header::LINK, links.join(", "));
+    }
+    builder
+}
+
+#[get("/api/authors")]
+async fn api_authors<'a>(ctx: WebCtx<'a>, mask: web::Query<actions::AuthorMask>, query: web::Query<actions::PageQuery>) -> WebResult {
+    let pattern = mask.into_inner();
+    let paging = actions::Paging::new(&query);
+    let conn = ctx.pool.get()?;
+    let page = web::block(move|| actions::load_authors_list(&conn, &pattern, paging))
+        .await?;
+
+    Ok(with_paging_links(HttpResponse::Ok(), "/api/authors", &page.paging).json(&page))
+}
+
+#[get("/opds/authors")]
+async fn opds_authors<'a>(ctx: WebCtx<'a>, mask: web::Query<actions::AuthorMask>, query: web::Query<actions::PageQuery>) -> WebResult {
+    let pattern = mask.into_inner();
+    let paging = actions::Paging::new(&query);
+    let conn = ctx.pool.get()?;
+    let page = web::block(move|| actions::load_authors_list(&conn, &pattern, paging))
+        .await?;
+
+    let body = ctx.handlebars.render("opds_authors", &json!(&page))?;
+
+    Ok(HttpResponse::Ok().content_type(OPDS_CONTENT_TYPE).body(body))
+}
+
+#[get("/api/titles")]
+async fn api_titles<'a>(ctx: WebCtx<'a>, mask: web::Query<actions::TitleMask>, query: web::Query<actions::PageQuery>) -> WebResult {
+    let pattern = mask.into_inner();
+    let paging = actions::Paging::new(&query);
+    let conn = ctx.pool.get()?;
+    let page = web::block(move|| actions::load_titles_list(&conn, &pattern, paging))
+        .await?;
+
+    Ok(with_paging_links(HttpResponse::Ok(), "/api/titles", &page.paging).json(&page))
+}
+
+#[get("/opds/titles")]
+async fn opds_titles<'a>(ctx: WebCtx<'a>, mask: web::Query<actions::TitleMask>, query: web::Query<actions::PageQuery>) -> WebResult {
+    let pattern = mask.into_inner();
+    let paging = actions::Paging::new(&query);
+    let conn = ctx.pool.get()?;
+    let page = web::block(move|| actions::load_titles_list(&conn, &pattern, paging))
+        .await?;
+
+    let body = ctx.handlebars.render("opds_titles", &json!(&page))?;
+
+    Ok(HttpResponse::Ok().content_type(OPDS_CONTENT_TYPE).body(body))
+}
+
+#[get("/isbn/{isbn}")]
+async fn isbn<'a>(ctx: WebCtx<'a>, args: web::Path<String>) -> WebResult {
+    let isbn = args.into_inner();
+    let conn = ctx.pool.get()?;
+    let page = web::block(move|| actions::load_isbn_ctx(&conn, &isbn))
+        .await?;
+
+    let body = ctx.handlebars.render("isbn", &json!(&page))?;
+
+    Ok(HttpResponse::Ok().body(body))
+}
+
+#[get("/new/")]
+async fn recent<'a>(ctx: WebCtx<'a>, query: web::Query<actions::RecentQuery>, paging: web::Query<actions::PageQuery>) -> WebResult {
+    let paging = actions::Paging::with_order(&paging, actions::SortOrder::Added);
+    let conn = ctx.pool.get()?;
+    let page = web::block(move|| actions::load_recent_ctx(&conn, &query, paging))
+        .await?;
 
-    let body = ctx.handlebars.render("titles", &json!(&page))
-                             .expect("couldn't render template");
+    let body = ctx.handlebars.render("new", &json!(&page))?;
 
     Ok(HttpResponse::Ok().body(body))
 }
 
-#[get("/download/{archive}/{book}")]
-async fn download<'a>(ctx: WebCtx<'a>, args: web::Path<(String, String)>) -> FileResult {
+#[get("/api/new")]
+async fn api_recent<'a>(ctx: WebCtx<'a>, query: web::Query<actions::RecentQuery>, paging: web::Query<actions::PageQuery>) -> WebResult {
+    let paging = actions::Paging::with_order(&paging, actions::SortOrder::Added);
+    let conn = ctx.pool.get()?;
+    let page = web::block(move|| actions::load_recent_ctx(&conn, &query, paging))
+        .await?;
+
+    Ok(HttpResponse::Ok().json(&page))
+}
+
+#[get("/opds/new")]
+async fn opds_recent<'a>(ctx: WebCtx<'a>, query: web::Query<actions::RecentQuery>, paging: web::Query<actions::PageQuery>) -> WebResult {
+    let paging = actions::Paging::with_order(&paging, actions::SortOrder::Added);
+    let conn = ctx.pool.get()?;
+    let page = web::block(move|| actions::load_recent_ctx(&conn, &query, paging))
+        .await?;
+
+    let body = ctx.handlebars.render("opds_new", &json!(&page))?;
+
+    Ok(HttpResponse::Ok().content_type(OPDS_CONTENT_TYPE).body(body))
+}
+
+#[get("/popular/")]
+async fn popular<'a>(ctx: WebCtx<'a>, query: web::Query<actions::RecentQuery>, paging: web::Query<actions::PageQuery>) -> WebResult {
+    let paging = actions::Paging::with_order(&paging, actions::SortOrder::Added);
+    let conn = ctx.pool.get()?;
+    let page = web::block(move|| actions::load_popular_ctx(&conn, &query, paging))
+        .await?;
+
+    let body = ctx.handlebars.render("popular", &json!(&page))?;
+
+    Ok(HttpResponse::Ok().body(body))
+}
+
+#[get("/api/popular")]
+async fn api_popular<'a>(ctx: WebCtx<'a>, query: web::Query<actions::RecentQuery>, paging: web::Query<actions::PageQuery>) -> WebResult {
+    let paging = actions::Paging::with_order(&paging, actions::SortOrder::Added);
+    let conn = ctx.pool.get()?;
+    let page = web::block(move|| actions::load_popular_ctx(&conn, &query, paging))
+        .await?;
+
+    Ok(HttpResponse::Ok().json(&page))
+}
+
+#[get("/opds/popular")]
+async fn opds_popular<'a>(ctx: WebCtx<'a>, query: web::Query<actions::RecentQuery>, paging: web::Query<actions::PageQuery>) -> WebResult {
+    let paging = actions::Paging::with_order(&paging, actions::SortOrder::Added);
+    let conn = ctx.pool.get()?;
+    let page = web::block(move|| actions::load_popular_ctx(&conn, &query, paging))
+        .await?;
+
+    let body = ctx.handlebars.render("opds_popular", &json!(&page))?;
+
+    Ok(HttpResponse::Ok().content_type(OPDS_CONTENT_TYPE).body(body))
+}
+
+#[get("/atom/author/{id:\\d+}/")]
+async fn atom_author<'a>(ctx: WebCtx<'a>, args: web::Path<Id>) -> WebResult {
+    let id = args.into_inner();
+    let conn = ctx.pool.get()?;
+    let feed = web::block(move|| actions::load_author_feed(&conn, id))
+        .await.or_not_found("Автор не найден")?;
+
+    let body = ctx.handlebars.render("atom", &json!(&feed))?;
+
+    Ok(HttpResponse::Ok().content_type(ATOM_CONTENT_TYPE).body(body))
+}
+
+#[get("/atom/author/{fname}/{mname}/{lname}/")]
+async fn legacy_atom_author<'a>(ctx: WebCtx<'a>, req: HttpRequest, args: web::Path<(String, String, String)>) -> WebResult {
+    let (first_name, middle_name, last_name) = args.into_inner();
+    let mask = actions::AuthorMask::new(first_name, middle_name, last_name);
+    let conn = ctx.pool.get()?;
+    let id = web::block(move|| actions::AuthorEntry::find(&conn, &mask).map(|entry| entry.author_id))
+        .await.or_not_found("Автор не найден")?;
+    Ok(redirect_permanent(&req, &format!("/atom/author/{}/", id)))
+}
+
+#[get("/atom/series/{id:\\d+}/")]
+async fn atom_series<'a>(ctx: WebCtx<'a>, args: web::Path<Id>) -> WebResult {
+    let id = args.into_inner();
+    let conn = ctx.pool.get()?;
+    let feed = web::block(move|| actions::load_series_feed(&conn, id))
+        .await.or_not_found("Серия не найдена")?;
+
+    let body = ctx.handlebars.render("atom", &json!(&feed))?;
+
+    Ok(HttpResponse::Ok().content_type(ATOM_CONTENT_TYPE).body(body))
+}
+
+#[get("/atom/series/{series}/")]
+async fn legacy_atom_series<'a>(ctx: WebCtx<'a>, req: HttpRequest, args: web::Path<String>) -> WebResult {
+    let series = args.into_inner();
+    let conn = ctx.pool.get()?;
+    let id = web::block(move|| actions::find_series_id(&conn, &series))
+        .await.or_not_found("Серия не найдена")?;
+    Ok(redirect_permanent(&req, &format!("/atom/series/{}/", id)))
+}
+
+#[get("/login")]
+async fn login_form<'a>(ctx: WebCtx<'a>, query: web::Query<actions::LoginQuery>) -> WebResult {
+    let page = actions::LoginContext::new("", &query.next, false);
+    let body = ctx.handlebars.render("login", &json!(&page))?;
+
+    Ok(HttpResponse::Ok().body(body))
+}
+
+#[post("/login")]
+async fn login<'a>(ctx: WebCtx<'a>, form: web::Form<actions::LoginForm>) -> WebResult {
+    let form = form.into_inner();
+    let (name, password) = (form.user_name.clone(), form.password.clone());
+    let conn = ctx.pool.get()?;
+    let token = web::block(move|| actions::login_context::login(&conn, &name, &password))
+        .await?;
+
+    let page = actions::LoginContext::new(&form.user_name, &form.next, token.is_none());
+    match token {
+        Some(token) => {
+            let cookie = Cookie::build(SESSION_COOKIE, token)
+                .path("/")
+                .http_only(true)
+                .same_site(SameSite::Lax)
+                .finish();
+            Ok(HttpResponse::SeeOther().header(header::LOCATION, page.next).cookie(cookie).finish())
+        },
+        None => {
+            let body = ctx.handlebars.render("login", &json!(&page))?;
+            Ok(HttpResponse::Unauthorized().body(body))
+        }
+    }
+}
+
+#[post("/logout")]
+async fn logout<'a>(ctx: WebCtx<'a>, req: HttpRequest) -> WebResult {
+    if let Some(cookie) = req.cookie(SESSION_COOKIE) {
+        let token = String::from(cookie.value());
+        let conn = ctx.pool.get()?;
+        web::block(move|| actions::login_context::logout(&conn, &token))
+            .await?;
+
+        let expired = Cookie::build(SESSION_COOKIE, "").path("/").finish();
+        return Ok(HttpResponse::SeeOther().header(header::LOCATION, "/").del_cookie(&expired).finish());
+    }
+    Ok(redirect("/"))
+}
+
+#[get("/shelf/")]
+async fn shelf<'a>(ctx: WebCtx<'a>, req: HttpRequest, query: web::Query<actions::ShelfQuery>) -> WebResult {
+    let credentials = get_credentials(&req);
+    let conn = ctx.pool.get()?;
+    let page = web::block(move|| actions::load_shelf_ctx(&conn, &credentials, &query))
+        .await?;
+
+    match page {
+        Some(page) => {
+            let body = ctx.handlebars.render("shelf", &json!(&page))?;
+            Ok(HttpResponse::Ok().body(body))
+        },
+        None => Ok(redirect_to_login("/shelf/")),
+    }
+}
+
+#[post("/shelf/{book}")]
+async fn update_shelf<'a>(ctx: WebCtx<'a>, req: HttpRequest, args: web::Path<i32>, form: web::Form<actions::ShelfForm>) -> WebResult {
+    let book = args.into_inner();
+    let credentials = get_credentials(&req);
+    let conn = ctx.pool.get()?;
+    let updated = web::block(move|| actions::update_shelf(&conn, &credentials, book, &form))
+        .await?;
+
+    // The book is put from the title page, so the user returns to it
+    if updated {
+        Ok(redirect_back(&req, "/shelf/"))
+    } else {
+        Ok(redirect_to_login("/shelf/"))
+    }
+}
+
+#[get("/opds/shelf")]
+async fn opds_shelf<'a>(ctx: WebCtx<'a>, req: HttpRequest, query: web::Query<actions::ShelfQuery>) -> WebResult {
+    let credentials = get_credentials(&req);
+    let conn = ctx.pool.get()?;
+    let page = web::block(move|| actions::load_shelf_ctx(&conn, &credentials, &query))
+        .await?;
+
+    match page {
+        Some(page) => {
+            let body = ctx.handlebars.render("opds_shelf", &json!(&page))?;
+            Ok(HttpResponse::Ok().content_type(OPDS_CONTENT_TYPE).body(body))
+        },
+        None => Ok(HttpResponse::Unauthorized().header(header::WWW_AUTHENTICATE, BASIC_REALM).finish()),
+    }
+}
+
+/// Returns the user to the page of the form
+fn redirect_back(req: &HttpRequest, default: &str) -> HttpResponse {
+    let back = req.headers().get(header::REFERER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.find("://").map(|i| &value[i + 3..]))
+        .and_then(|value| value.find('/').map(|i| &value[i..]))
+        .map(|path| actions::login_context::get_local_path(path))
+        .unwrap_or(String::from(default));
+    redirect(&back)
+}
+
+#[post("/annotate/{book}/review")]
+async fn annotate_review<'a>(ctx: WebCtx<'a>, req: HttpRequest, args: web::Path<i32>, form: web::Form<actions::ReviewForm>) -> WebResult {
+    let book = args.into_inner();
+    let credentials = get_credentials(&req);
+    let conn = ctx.pool.get()?;
+    let updated = web::block(move|| actions::update_review(&conn, &credentials, book, &form))
+        .await?;
+
+    if updated {
+        Ok(redirect_back(&req, "/"))
+    } else {
+        Ok(redirect_to_login("/"))
+    }
+}
+
+#[post("/annotate/{book}/tag")]
+async fn annotate_tag<'a>(ctx: WebCtx<'a>, req: HttpRequest, args: web::Path<i32>, form: web::Form<actions::TagForm>) -> WebResult {
+    let book = args.into_inner();
+    let credentials = get_credentials(&req);
+    let conn = ctx.pool.get()?;
+    let updated = web::block(move|| actions::update_tags(&conn, &credentials, book, &form))
+        .await?;
+
+    if updated {
+        Ok(redirect_back(&req, "/"))
+    } else {
+        Ok(redirect_to_login("/"))
+    }
+}
+
+#[get("/tags/")]
+async fn tags<'a>(ctx: WebCtx<'a>) -> WebResult {
+    let conn = ctx.pool.get()?;
+    let page = web::block(move|| actions::load_tag_ctx(&conn, "", actions::Paging::default()))
+        .await?;
+
+    let body = ctx.handlebars.render("tags", &json!(&page))?;
+
+    Ok(HttpResponse::Ok().body(body))
+}
+
+#[get("/tag/{tag}/")]
+async fn tag<'a>(ctx: WebCtx<'a>, args: web::Path<String>, query: web::Query<actions::PageQuery>) -> WebResult {
+    let tag = args.into_inner();
+    let paging = actions::Paging::with_order(&query, actions::SortOrder::Added);
+    let conn = ctx.pool.get()?;
+    let page = web::block(move|| actions::load_tag_ctx(&conn, &tag, paging))
+        .await?;
+
+    let body = ctx.handlebars.render("tags", &json!(&page))?;
+
+    Ok(HttpResponse::Ok().body(body))
+}
+
+#[get("/api/book/{book}/annotations")]
+async fn api_annotations<'a>(ctx: WebCtx<'a>, args: web::Path<i32>) -> WebResult {
+    let book = args.into_inner();
+    let conn = ctx.pool.get()?;
+    let annotations = web::block(move|| actions::load_annotations(&conn, book))
+        .await.or_not_found("Книга не найдена")?;
+
+    Ok(HttpResponse::Ok().json(&annotations))
+}
+
+#[post("/users/create")]
+async fn sync_create_user() -> WebResult {
+    // The users are added by fb2loader add-user
+    Ok(sync_error(actions::sync_context::ERROR_REGISTRATION_DISABLED))
+}
+
+#[get("/users/auth")]
+async fn sync_auth_user<'a>(ctx: WebCtx<'a>, req: HttpRequest) -> WebResult {
+    let (name, key) = get_sync_credentials(&req);
+    let conn = ctx.pool.get()?;
+    let user = web::block(move|| actions::sync_context::authenticate(&conn, &name, &key).ok_or(()))
+        .await;
+
+    match user {
+        Ok(_) => Ok(HttpResponse::Ok().json(json!({"authorized": "OK"}))),
+        Err(_) => Ok(sync_error(actions::sync_context::ERROR_UNAUTHORIZED)),
+    }
+}
+
+#[put("/syncs/progress")]
+async fn sync_update_progress<'a>(ctx: WebCtx<'a>, req: HttpRequest, form: web::Json<actions::ProgressForm>) -> WebResult {
+    if form.document.is_empty() {
+        return Ok(sync_error(actions::sync_context::ERROR_DOCUMENT_MISSING));
+    }
+    let (name, key) = get_sync_credentials(&req);
+    let conn = ctx.pool.get()?;
+    let form = form.into_inner();
+    let document = form.document.clone();
+    let timestamp = web::block(move|| match actions::sync_context::authenticate(&conn, &name, &key) {
+            Some(user) => actions::sync_context::save_progress(&conn, user.id, &form).map(Some),
+            None => Ok(None),
+        })
+        .await?;
+
+    match timestamp {
+        Some(timestamp) => Ok(HttpResponse::Ok().json(json!({"document": document, "timestamp": timestamp}))),
+        None => Ok(sync_error(actions::sync_context::ERROR_UNAUTHORIZED)),
+    }
+}
+
+#[get("/syncs/progress/{document}")]
+async fn sync_get_progress<'a>(ctx: WebCtx<'a>, req: HttpRequest, args: web::Path<String>) -> WebResult {
+    let document = args.into_inner();
+    let (name, key) = get_sync_credentials(&req);
+    let conn = ctx.pool.get()?;
+    let progress = web::block(move|| match actions::sync_context::authenticate(&conn, &name, &key) {
+            Some(user) => actions::sync_context::load_progress(&conn, user.id, &document).map(Some),
+            None => Ok(None),
+        })
+        .await?;
+
+    match progress {
+        Some(Some(progress)) => Ok(HttpResponse::Ok().json(&progress)),
+        Some(None) => Ok(HttpResponse::Ok().json(json!({}))),
+        None => Ok(sync_error(actions::sync_context::ERROR_UNAUTHORIZED)),
+    }
+}
+
+/// Logs the served file and remembers its KOReader hash for the progress sync,
+/// the file is served anyway if it fails
+async fn record_download<'a>(ctx: &WebCtx<'a>, req: &HttpRequest, page: &actions::DownloadContext, format: DownloadFormat) {
+    let (book, document) = (page.book.book_id, page.document.clone());
+    let credentials = get_credentials(req);
+    let conn = match ctx.pool.get() {
+        Ok(conn) => conn,
+        Err(e) => {
+            log::warn!("Failed to record download of book {}: {}", book, e);
+            return;
+        }
+    };
+    if let Err(e) = web::block(move|| actions::record_download(&conn, &credentials, book, document, format)).await {
+        log::warn!("Failed to record download of book {}: {}", book, e);
+    }
+}
+
+#[get("/download/{book:\\d+}")]
+async fn download<'a>(ctx: WebCtx<'a>, req: HttpRequest, args: web::Path<Id>) -> FileResult {
+    let book = args.into_inner();
+    let workdir = ctx.workdir.clone();
+    let conn = ctx.pool.get()?;
+    let mut page = web::block(move|| actions::load_download_ctx(&conn, workdir, book))
+        .await.or_not_found("Книга не найдена")?;
+    let stream = page.get_unzipped_stream()?;
+    record_download(&ctx, &req, &page, DownloadFormat::Fb2).await;
+    Ok(stream)
+}
+
+#[get("/download_zip/{book:\\d+}")]
+async fn download_zip<'a>(ctx: WebCtx<'a>, req: HttpRequest, args: web::Path<Id>) -> FileResult {
+    let book = args.into_inner();
+    let workdir = ctx.workdir.clone();
+    let conn = ctx.pool.get()?;
+    let mut page = web::block(move|| actions::load_download_ctx(&conn, workdir, book))
+        .await.or_not_found("Книга не найдена")?;
+    let stream = page.get_zipped_stream()?;
+    record_download(&ctx, &req, &page, DownloadFormat::Zip).await;
+    Ok(stream)
+}
+
+/// The book file may be in a subdirectory of the archive
+#[get("/download/{archive}/{book:.+}")]
+async fn legacy_download<'a>(ctx: WebCtx<'a>, req: HttpRequest, args: web::Path<(String, String)>) -> WebResult {
     let (archive, book) = args.into_inner();
-    let conn = ctx.pool.get().expect("couldn't get db connection from pool");
-    let mut page = web::block(move|| actions::load_download_ctx(&conn, String::from("/tmp"), &archive, &book))
-        .await
-        .map_err(|e| {
-            eprintln!("{}", e);
-            HttpResponse::InternalServerError().finish()})?;
-    Ok(page.get_unzipped_stream()?)
+    let conn = ctx.pool.get()?;
+    let id = web::block(move|| actions::find_download_id(&conn, &archive, &book))
+        .await.or_not_found("Книга не найдена")?;
+    Ok(redirect_permanent(&req, &format!("/download/{}", id)))
 }
 
-#[get("/download_zip/{archive}/{book}")]
-async fn download_zip<'a>(ctx: WebCtx<'a>, args: web::Path<(String, String)>) -> FileResult {
+#[get("/download_zip/{archive}/{book:.+}")]
+async fn legacy_download_zip<'a>(ctx: WebCtx<'a>, req: HttpRequest, args: web::Path<(String, String)>) -> WebResult {
     let (archive, book) = args.into_inner();
-    let conn = ctx.pool.get().expect("couldn't get db connection from pool");
-    let mut page = web::block(move|| actions::load_download_ctx(&conn, String::from("/tmp"), &archive, &book))
-        .await
-        .map_err(|e| {
-            eprintln!("{}", e);
-            HttpResponse::InternalServerError().finish()})?;
-    Ok(page.get_zipped_stream()?)
+    let conn = ctx.pool.get()?;
+    let id = web::block(move|| actions::find_download_id(&conn, &archive, &book))
+        .await.or_not_found("Книга не найдена")?;
+    Ok(redirect_permanent(&req, &format!("/download_zip/{}", id)))
 }
 
 
-#[actix_web::main]
-async fn main() -> std::io::Result<()> {
-    std::env::set_var("RUST_LOG", "actix_web=info");
-    env_logger::init();
-    dotenv::dotenv().ok();
+fn configure_catalog(cfg: &mut web::ServiceConfig) {
+    cfg.service(root)
+        .service(search)
+        .service(authors)
+        .service(legacy_authors)
+        .service(author)
+        .service(author_by_id)
+        .service(legacy_author)
+        .service(titles)
+        .service(book_page)
+        .service(book_by_id)
+        .service(legacy_title)
+        .service(isbn)
+        .service(recent)
+        .service(popular)
+        .service(tags)
+        .service(tag)
+        .service(download)
+        .service(download_zip)
+        .service(legacy_download)
+        .service(legacy_download_zip);
+}
+
+fn configure_api(cfg: &mut web::ServiceConfig) {
+    cfg.service(api_authors)
+        .service(api_titles)
+        .service(api_recent)
+        .service(api_popular)
+        .service(api_annotations);
+}
+
+fn configure_opds(cfg: &mut web::ServiceConfig) {
+    cfg.service(opds_authors)
+        .service(opds_titles)
+        .service(opds_recent)
+        .service(opds_popular)
+        .service(atom_author)
+        .service(legacy_atom_author)
+        .service(atom_series)
+        .service(legacy_atom_series);
+}
+
+fn configure_accounts(cfg: &mut web::ServiceConfig) {
+    cfg.service(login_form)
+        .service(login)
+        .service(logout)
+        .service(shelf)
+        .service(update_shelf)
+        .service(annotate_review)
+        .service(annotate_tag);
+}
+
+fn configure_kosync(cfg: &mut web::ServiceConfig) {
+    cfg.service(sync_create_user)
+        .service(sync_auth_user)
+        .service(sync_update_progress)
+        .service(sync_get_progress);
+}
+
+/// The name of the admin sending the request, None for the anonymous user
+async fn authorize_admin<'a>(ctx: &WebCtx<'a>, req: &HttpRequest) -> Result<Option<String>, ServiceError> {
+    let credentials = get_credentials(req);
+    let admins = ctx.admins.clone();
+    let conn = ctx.pool.get()?;
+    let access = web::block(move|| actions::check_admin(&conn, &credentials, &admins))
+        .await?;
+
+    match access {
+        actions::AdminAccess::Granted(user_name) => Ok(Some(user_name)),
+        actions::AdminAccess::Denied => Err(ServiceError::Forbidden(String::from("Страница доступна только администраторам"))),
+        actions::AdminAccess::Anonymous => Ok(None),
+    }
+}
+
+/// The admin forms are only accepted from the pages of the service, the browser sends
+/// the session cookie and the cached basic authorization with the forms of the other sites too
+fn check_same_origin(req: &HttpRequest) -> Result<(), ServiceError> {
+    let source = req.headers().get(header::ORIGIN)
+        .or_else(|| req.headers().get(header::REFERER))
+        .and_then(|value| value.to_str().ok());
+    match source {
+        Some(source) if lib::auth::is_same_origin(source, req.connection_info().host()) => Ok(()),
+        _ => Err(ServiceError::Forbidden(String::from("Запрос отправлен не со страницы библиотеки"))),
+    }
+}
+
+fn get_load_status<'a>(ctx: &WebCtx<'a>) -> Result<LoadStatus, ServiceError> {
+    ctx.status.lock()
+        .map(|status| status.clone())
+        .map_err(|e| ServiceError::Internal(e.to_string()))
+}
+
+/// Queues the jobs of the library worker, they run one after another
+fn submit_jobs<'a>(ctx: &WebCtx<'a>, jobs: Vec<LibraryJob>) -> Result<(), ServiceError> {
+    let worker = ctx.worker.as_ref()
+        .ok_or(ServiceError::Internal(String::from("the library worker is not started")))?;
+    let mut status = ctx.status.lock().map_err(|e| ServiceError::Internal(e.to_string()))?;
+    for job in jobs {
+        status.enqueue(&job);
+        worker.do_send(job);
+    }
+    Ok(())
+}
+
+#[get("/admin/")]
+async fn admin<'a>(ctx: WebCtx<'a>, req: HttpRequest) -> WebResult {
+    let user_name = match authorize_admin(&ctx, &req).await? {
+        Some(user_name) => user_name,
+        None => return Ok(redirect_to_login("/admin/")),
+    };
+    let status = get_load_status(&ctx)?;
+    let (library, database) = (ctx.library.clone(), ctx.database.clone());
+    let conn = ctx.pool.get()?;
+    let page = web::block(move|| actions::load_admin_ctx(&conn, &user_name, &library, &database, status))
+        .await?;
+
+    let body = ctx.handlebars.render("admin", &json!(&page))?;
+
+    Ok(HttpResponse::Ok().body(body))
+}
+
+#[get("/api/admin/status")]
+async fn api_admin_status<'a>(ctx: WebCtx<'a>, req: HttpRequest) -> WebResult {
+    match authorize_admin(&ctx, &req).await? {
+        Some(_) => Ok(HttpResponse::Ok().json(&get_load_status(&ctx)?)),
+        None => Ok(HttpResponse::Unauthorized().header(header::WWW_AUTHENTICATE, BASIC_REALM).finish()),
+    }
+}
+
+#[post("/admin/load")]
+async fn admin_load<'a>(ctx: WebCtx<'a>, req: HttpRequest, form: web::Form<actions::LoadForm>) -> WebResult {
+    check_same_origin(&req)?;
+    if authorize_admin(&ctx, &req).await?.is_none() {
+        return Ok(redirect_to_login("/admin/"));
+    }
+    let library = ctx.library.clone();
+    let conn = ctx.pool.get()?;
+    let jobs = web::block(move|| actions::find_load_jobs(&conn, &library, &form))
+        .await?;
+    if jobs.is_empty() {
+        return Err(ServiceError::NotFound(String::from("Новые архивы не найдены")));
+    }
+    submit_jobs(&ctx, jobs)?;
+
+    Ok(redirect("/admin/"))
+}
 
-    let templates = "./templates";
+#[post("/admin/archive/{archive}/remove")]
+async fn admin_remove<'a>(ctx: WebCtx<'a>, req: HttpRequest, args: web::Path<Id>) -> WebResult {
+    check_same_origin(&req)?;
+    if authorize_admin(&ctx, &req).await?.is_none() {
+        return Ok(redirect_to_login("/admin/"));
+    }
+    let archive = args.into_inner();
+    let conn = ctx.pool.get()?;
+    let job = web::block(move|| actions::find_remove_job(&conn, archive))
+        .await.or_not_found("Архив не найден")?;
+    submit_jobs(&ctx, vec![job])?;
+
+    Ok(redirect("/admin/"))
+}
+
+fn configure_admin(cfg: &mut web::ServiceConfig) {
+    cfg.service(admin)
+        .service(api_admin_status)
+        .service(admin_load)
+        .service(admin_remove);
+}
+
+fn get_args<'a>() -> ArgMatches<'a> {
+    let selfname: String = std::env::args().nth(0).unwrap_or_default();
+    Cli::new(selfname)
+        .version(env!("CARGO_PKG_VERSION"))
+        .author("seb <seb@ukr.net>")
+        .about("FictionBook Library web service")
+        .arg(Arg::with_name("config")
+            .short("c")
+            .long("config")
+            .value_name("FILE")
+            .help("Sets the TOML config file, the options below override it")
+            .takes_value(true))
+        .arg(Arg::with_name("bind")
+            .short("b")
+            .long("bind")
+            .value_name("HOST:PORT")
+            .help("Sets the address to listen, may be repeated (default: $INTERFACE)")
+            .takes_value(true)
+            .multiple(true)
+            .number_of_values(1))
+        .arg(Arg::with_name("database")
+            .short("d")
+            .long("database")
+            .value_name("FILE")
+            .help("Sets the SQLite database (default: $DATABASE_URL)")
+            .takes_value(true))
+        .arg(Arg::with_name("templates")
+            .long("templates")
+            .value_name("DIR")
+            .help("Sets the directory of the Handlebars templates (default: ./templates)")
+            .takes_value(true))
+        .arg(Arg::with_name("static")
+            .long("static")
+            .value_name("DIR")
+            .help("Sets the directory of the stylesheet and the scripts served under /static/, empty to serve nothing (default: ./static)")
+            .takes_value(true))
+        .arg(Arg::with_name("workdir")
+            .long("workdir")
+            .value_name("DIR")
+            .help("Sets the directory of the temporary files made by the downloads (default: /tmp)")
+            .takes_value(true))
+        .arg(Arg::with_name("pool-size")
+            .long("pool-size")
+            .value_name("SIZE")
+            .help("Sets the maximum number of the database connections (default: 10)")
+            .takes_value(true))
+        .arg(Arg::with_name("log-level")
+            .long("log-level")
+            .value_name("LEVEL")
+            .help("Sets the level of the request log (default: info)")
+            .possible_values(Config::LOG_LEVELS)
+            .takes_value(true))
+        .arg(Arg::with_name("base-url")
+            .long("base-url")
+            .value_name("URL")
+            .help("Sets the scheme and the host of the absolute OPDS links, e.g. https://books.example.org")
+            .takes_value(true))
+        .arg(Arg::with_name("admin")
+            .long("admin")
+            .value_name("USER")
+            .help("Allows the user to the /admin pages, may be repeated")
+            .takes_value(true)
+            .multiple(true)
+            .number_of_values(1))
+        .arg(Arg::with_name("library")
+            .long("library")
+            .value_name("DIR")
+            .help("Sets the directory scanned by the /admin pages for the new archives")
+            .takes_value(true))
+        .arg(Arg::with_name("root")
+            .long("root")
+            .value_name("NAME=DIR")
+            .help("Sets the directory of the library root, may be repeated")
+            .takes_value(true)
+            .multiple(true)
+            .number_of_values(1))
+        .arg(Arg::with_name("disable")
+            .long("disable")
+            .value_name("FEATURE")
+            .help("Switches off the part of the service, may be repeated")
+            .possible_values(Features::NAMES)
+            .takes_value(true)
+            .multiple(true)
+            .number_of_values(1))
+        .get_matches()
+}
+
+/// The defaults, then the config file or the environment, then the command line
+fn load_config(args: &ArgMatches) -> Result<Config, String> {
+    let mut config = match args.value_of("config") {
+        Some(path) => Config::load(path)?,
+        None => Config::default(),
+    };
+    config.apply_env();
+    if let Some(bind) = args.values_of("bind") {
+        config.bind = bind.map(String::from).collect();
+    }
+    let set = |value: &mut String, name: &str| if let Some(arg) = args.value_of(name) {
+        *value = String::from(arg);
+    };
+    set(&mut config.database, "database");
+    set(&mut config.templates, "templates");
+    set(&mut config.static_dir, "static");
+    set(&mut config.workdir, "workdir");
+    set(&mut config.log_level, "log-level");
+    set(&mut config.base_url, "base-url");
+    set(&mut config.library, "library");
+    if let Some(admins) = args.values_of("admin") {
+        config.admins = admins.map(String::from).collect();
+    }
+    for value in args.values_of("root").into_iter().flatten() {
+        config.add_root(value)?;
+    }
+    if let Some(size) = args.value_of("pool-size") {
+        config.pool_size = size.parse().map_err(|_| format!("Invalid pool size '{}'", size))?;
+    }
+    for feature in args.values_of("disable").into_iter().flatten() {
+        config.features.disable(feature)?;
+    }
+    config.validate()?;
+    Ok(config)
+}
+
+/// {{url "author" first_name middle_name last_name}} or {{url path}}: the percent-encoded absolute path,
+/// {{url "opds" "new" days=days genre=genre}} adds the percent-encoded query of the non-empty values
+fn url_helper(h: &Helper, _: &Handlebars, _: &handlebars::Context, _: &mut RenderContext, out: &mut dyn Output) -> HelperResult {
+    let to_string = |value: &serde_json::Value| match value {
+        serde_json::Value::String(value) => value.clone(),
+        serde_json::Value::Null => String::new(),
+        value => value.to_string(),
+    };
+    let mut parts = Vec::new();
+    for param in h.params() {
+        match param.value() {
+            serde_json::Value::Array(values) => parts.extend(values.iter().map(to_string)),
+            value => parts.push(to_string(value)),
+        }
+    }
+    let params: Vec<(&str, String)> = h.hash().iter()
+        .map(|(name, param)| (*name, to_string(param.value())))
+        .collect();
+    out.write(&actions::Link::make_path(&parts))?;
+    let query = actions::Link::make_query(&params);
+    if !query.is_empty() {
+        out.write("?")?;
+        out.write(&query.replace('&', "&amp;"))?;
+    }
+    Ok(())
+}
+
+/// The archives of the roots are found at the paths of this host
+fn rebase_roots(pool: &actions::ConnectionPool, config: &Config) -> Result<(), String> {
+    let conn = pool.get().map_err(|e| format!("Can't connect to {}: {}", config.database, e))?;
+    for (name, path) in config.roots.iter() {
+        let path = std::path::Path::new(path).canonicalize()
+            .map_err(|e| format!("Can't find root directory {}: {}", path, e))?;
+        RootRecord::rebase(&conn, &Root::new(name, &path.to_string_lossy()))
+            .map_err(|e| format!("Failed to rebase root {}: {}", name, e))?;
+    }
+    Ok(())
+}
+
+/// The keys left by the earlier versions are recalculated, the navigation still works
+/// with the stale ones in the wrong order if it fails
+fn check_sort_keys(pool: &actions::ConnectionPool, config: &Config) -> Result<(), String> {
+    let conn = pool.get().map_err(|e| format!("Can't connect to {}: {}", config.database, e))?;
+    match models::reindex_stale_keys(&conn) {
+        Ok(ref reindexed) if reindexed.is_empty() => {},
+        Ok(reindexed) => log::info!("Navigation keys of {} are recalculated", reindexed.join(", ")),
+        Err(e) => log::warn!("Failed to recalculate navigation keys, run 'fb2loader reindex': {}", e),
+    }
+    Ok(())
+}
+
+/// Only warns, the users without the key still may use the rest of the service
+fn check_sync_keys(pool: &actions::ConnectionPool, config: &Config) -> Result<(), String> {
+    let conn = pool.get().map_err(|e| format!("Can't connect to {}: {}", config.database, e))?;
+    let names = UserRecord::load_without_sync_key(&conn).map_err(|e| format!("Failed to check sync keys: {}", e))?;
+    if !names.is_empty() {
+        log::warn!("Users {} have no progress sync key, run 'fb2loader add-user' with their password to set it", names.join(", "));
+    }
+    Ok(())
+}
+
+/// The archives hashed by the first megabyte are still found, but the changed ones may be taken for them
+fn check_archive_hashes(pool: &actions::ConnectionPool, config: &Config) -> Result<(), String> {
+    let conn = pool.get().map_err(|e| format!("Can't connect to {}: {}", config.database, e))?;
+    let count = ArchiveRecord::count_prefixed(&conn).map_err(|e| format!("Failed to check archive hashes: {}", e))?;
+    if count > 0 {
+        log::warn!("{} archives are hashed by the first megabyte only, run 'fb2loader rehash' to hash them fully", count);
+    }
+    Ok(())
+}
+
+fn create_context(config: &Config) -> Result<Context<'static>, String> {
     let mut handlebars = Handlebars::new();
-    handlebars.register_templates_directory(".hbs", templates)
-        .expect(&format!("Can't register template directory {}", templates));
+    handlebars.register_templates_directory(".hbs", &config.templates)
+        .map_err(|e| format!("Can't register template directory {}: {}", config.templates, e))?;
+    let base_url = config.get_base_url();
+    handlebars.register_helper("base_url", Box::new(
+        move |_: &Helper, _: &Handlebars, _: &handlebars::Context, _: &mut RenderContext, out: &mut dyn Output| -> HelperResult {
+            out.write(&base_url)?;
+            Ok(())
+        }));
+    handlebars.register_helper("url", Box::new(url_helper));
+    let tag_prefix = config.get_tag_prefix();
+    handlebars.register_helper("tag", Box::new(
+        move |h: &Helper, _: &Handlebars, _: &handlebars::Context, _: &mut RenderContext, out: &mut dyn Output| -> HelperResult {
+            let specific: Vec<String> = h.params().iter()
+                .map(|param| match param.value() {
+                    serde_json::Value::String(value) => value.clone(),
+                    value => value.to_string(),
+                })
+                .collect();
+            out.write(&tag_prefix)?;
+            out.write(actions::Link::make_path(&specific).trim_start_matches('/'))?;
+            Ok(())
+        }));
+    handlebars.register_helper("version", Box::new(
+        |_: &Helper, _: &Handlebars, _: &handlebars::Context, _: &mut RenderContext, out: &mut dyn Output| -> HelperResult {
+            out.write(get_version())?;
+            Ok(())
+        }));
 
-    let ctx = web::Data::new(Context::new(actions::get_connection_pool(), handlebars));
+    let pool = actions::create_connection_pool(&config.database, config.pool_size)?;
+    rebase_roots(&pool, config)?;
+    check_sort_keys(&pool, config)?;
+    check_sync_keys(&pool, config)?;
+    check_archive_hashes(&pool, config)?;
+    let mut ctx = Context::new(pool, handlebars, config);
+    if config.has_admin() {
+        ctx.worker = Some(LibraryWorker::start(&config.database, ctx.status.clone()));
+    }
+    Ok(ctx)
+}
 
-    let bind = env::var("INTERFACE").expect("INTERFACE must be set, e.g.: 192.168.0.1:8080");
-    //let bind = "home:8080";
-    println!("Starting server at: {}", &bind);
-    HttpServer::new(move || {
+#[actix_web::main]
+async fn main() -> std::io::Result<()> {
+    dotenv::dotenv().ok();
+    let args = get_args();
+    let config = load_config(&args).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    });
+    // The checks of the database in create_context report through the log
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or(format!("actix_web={0},fb2service={0},lib={0}", config.log_level))).init();
+    let ctx = create_context(&config).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    });
+
+    let ctx = web::Data::new(ctx);
+    let features = config.features.clone();
+    let has_admin = config.has_admin();
+    let static_dir = config.static_dir.clone();
+    let mut server = HttpServer::new(move || {
+        let features = features.clone();
+        let static_dir = static_dir.clone();
         App::new()
             .app_data(ctx.clone())
+            .app_data(web::QueryConfig::default().error_handler(bad_request))
+            .app_data(web::FormConfig::default().error_handler(bad_request))
+            .app_data(web::PathConfig::default().error_handler(bad_request))
+            .app_data(web::JsonConfig::default().error_handler(bad_request))
+            .wrap(error_handlers())
             .wrap(middleware::Logger::default())
+            .configure(move |cfg| {
+                if !static_dir.is_empty() {
+                    cfg.service(Files::new("/static", &static_dir));
+                }
+                configure_catalog(cfg);
+                if features.api {
+                    configure_api(cfg);
+                }
+                if features.opds {
+                    configure_opds(cfg);
+                }
+                if features.accounts {
+                    configure_accounts(cfg);
+                    if features.opds {
+                        cfg.service(opds_shelf);
+                    }
+                }
+                if features.kosync {
+                    configure_kosync(cfg);
+                }
+                if has_admin {
+                    configure_admin(cfg);
+                }
+            })
+        });
+    for bind in config.bind.iter() {
+        println!("Starting server at: {}", bind);
+        server = server.bind(bind)?;
+    }
+    server.run().await
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::time::Duration;
+    use actix_web::test;
+    use diesel::r2d2::{self, ConnectionManager};
+
+    /// The pool of the single connection, the request waits for it only a moment
+    fn create_test_context() -> Context<'static> {
+        let pool = r2d2::Pool::builder()
+            .max_size(1)
+            .connection_timeout(Duration::from_millis(100))
+            .build(ConnectionManager::new(":memory:"))
+            .unwrap();
+        let mut handlebars = Handlebars::new();
+        handlebars.register_template_string("error", "<h1>{{status}} {{error}}</h1><p>{{message}}</p>").unwrap();
+        Context::new(pool, handlebars, &Config::default())
+    }
+
+    async fn missing_author() -> WebResult {
+        Err(diesel::result::Error::NotFound).or_not_found("Автор не найден")
+    }
+
+    async fn call(ctx: Context<'static>, path: &str) -> (StatusCode, String, String) {
+        let mut app = test::init_service(App::new()
+            .app_data(web::Data::new(ctx))
+            .app_data(web::QueryConfig::default().error_handler(bad_request))
+            .app_data(web::PathConfig::default().error_handler(bad_request))
+            .wrap(error_handlers())
             .service(root)
             .service(authors)
-            .service(author)
-            .service(titles)
-            .service(title)
-            .service(download)
-            .service(download_zip)
-        })
-    .bind(&bind)?
-    .run()
-    .await
-}
\ No newline at end of file
+            .service(author_by_id)
+            .service(login_form)
+            .service(api_recent)
+            .route("/test/author/", web::get().to(missing_author))
+            .route("/api/test/author", web::get().to(missing_author))).await;
+        let res = test::call_service(&mut app, test::TestRequest::get().uri(path).to_request()).await;
+        let status = res.status();
+        let content_type = res.headers().get(header::CONTENT_TYPE).and_then(|value| value.to_str().ok()).unwrap_or("").to_string();
+        let body = String::from_utf8(test::read_body(res).await.to_vec()).unwrap();
+        (status, content_type, body)
+    }
+
+    async fn get(path: &str) -> (StatusCode, String, String) {
+        call(create_test_context(), path).await
+    }
+
+    /// Requests the page while the only connection of the pool is taken
+    async fn get_without_connection(path: &str) -> (StatusCode, String, String) {
+        let ctx = create_test_context();
+        let _conn = ctx.pool.get().unwrap();
+        call(ctx, path).await
+    }
+
+    fn get_message(body: &str) -> serde_json::Value {
+        serde_json::from_str::<serde_json::Value>(body).unwrap()["message"].clone()
+    }
+
+    #[test]
+    fn test_url_helper() {
+        let mut handlebars = Handlebars::new();
+        handlebars.register_helper("url", Box::new(url_helper));
+        let render = |template: &str, data: serde_json::Value| handlebars.render_template(template, &data).unwrap();
+        assert_eq!(render(r#"{{url "book" id slug}}/"#, json!({"id": 1, "slug": "a b"})), "/book/1/a%20b/");
+        assert_eq!(render("{{url path}}/", json!({"path": ["authors", ""]})), "/authors/-/");
+        assert_eq!(render(r#"{{url "opds" "new" days=days genre=genre}}"#, json!({"days": 7, "genre": "sf & <b>"})),
+            "/opds/new?days=7&amp;genre=sf%20%26%20%3Cb%3E");
+        assert_eq!(render(r#"{{url "opds" "new" days=days genre=genre}}"#, json!({"days": 7, "genre": ""})), "/opds/new?days=7");
+    }
+
+    #[test]
+    fn test_render_not_found() {
+        actix_web::rt::System::new("test").block_on(async {
+            let (status, content_type, body) = get("/test/author/").await;
+            assert_eq!(status, StatusCode::NOT_FOUND);
+            assert_eq!(content_type, "text/html; charset=utf-8");
+            assert_eq!(body, "<h1>404 Not Found</h1><p>Автор не найден</p>");
+
+            let (status, content_type, body) = get("/missing/").await;
+            assert_eq!(status, StatusCode::NOT_FOUND);
+            assert_eq!(content_type, "text/html; charset=utf-8");
+            assert_eq!(body, "<h1>404 Not Found</h1><p>Страница не найдена</p>");
+        });
+    }
+
+    #[test]
+    fn test_render_bad_request() {
+        actix_web::rt::System::new("test").block_on(async {
+            let (status, content_type, body) = get("/authors/?page=first").await;
+            assert_eq!(status, StatusCode::BAD_REQUEST);
+            assert_eq!(content_type, "text/html; charset=utf-8");
+            assert!(body.starts_with("<h1>400 Bad Request</h1><p>"));
+
+            let (status, _, body) = get("/author/99999999999/").await;
+            assert_eq!(status, StatusCode::BAD_REQUEST);
+            assert!(body.starts_with("<h1>400 Bad Request</h1><p>"));
+        });
+    }
+
+    #[test]
+    fn test_render_internal_error() {
+        actix_web::rt::System::new("test").block_on(async {
+            let (status, content_type, body) = get_without_connection("/").await;
+            assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+            assert_eq!(content_type, "text/html; charset=utf-8");
+            assert_eq!(body, "<h1>500 Internal Server Error</h1><p>Внутренняя ошибка сервера</p>");
+
+            // The login page has no template in the test context
+            let (status, _, body) = get("/login").await;
+            assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+            assert_eq!(body, "<h1>500 Internal Server Error</h1><p>Внутренняя ошибка сервера</p>");
+        });
+    }
+
+    #[test]
+    fn test_api_errors() {
+        actix_web::rt::System::new("test").block_on(async {
+            let (status, content_type, body) = get("/api/test/author").await;
+            assert_eq!(status, StatusCode::NOT_FOUND);
+            assert_eq!(content_type, "application/json");
+            assert_eq!(serde_json::from_str::<serde_json::Value>(&body).unwrap(),
+                json!({"status": 404, "error": "Not Found", "message": "Автор не найден"}));
+
+            let (status, content_type, body) = get("/api/missing").await;
+            assert_eq!(status, StatusCode::NOT_FOUND);
+            assert_eq!(content_type, "application/json");
+            assert_eq!(get_message(&body), "Страница не найдена");
+
+            let (status, content_type, body) = get("/api/new?page=first").await;
+            assert_eq!(status, StatusCode::BAD_REQUEST);
+            assert_eq!(content_type, "application/json");
+            assert!(get_message(&body).as_str().is_some_and(|message| !message.is_empty()));
+
+            let (status, content_type, body) = get_without_connection("/api/new").await;
+            assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+            assert_eq!(content_type, "application/json");
+            assert_eq!(serde_json::from_str::<serde_json::Value>(&body).unwrap(),
+                json!({"status": 500, "error": "Internal Server Error", "message": "Внутренняя ошибка сервера"}));
+        });
+    }
+}
@@ -0,0 +1,200 @@
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use diesel::sql_query;
+use diesel::sql_types::{BigInt, Bool, Integer, Text};
+use diesel::RunQueryDsl;
+use num_format::{Locale, ToFormattedString};
+use serde::{Deserialize, Serialize};
+
+use super::QueryResult;
+use super::SqliteConnection;
+use crate::models::{Id, SourceKind};
+use crate::worker::{LibraryJob, LoadStatus};
+
+/// The access of the user to the admin pages
+#[derive(Debug, Clone, PartialEq)]
+pub enum AdminAccess {
+    Anonymous,
+    Denied,
+    Granted(String),
+}
+
+/// The form of the admin page: the path of the new archive relative to the library, empty for the all new ones
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct LoadForm {
+    pub path: String,
+}
+
+#[derive(QueryableByName, Debug, Clone)]
+struct ArchiveRow {
+    #[sql_type = "Integer"] id: Id,
+    #[sql_type = "Text"] arch_name: String,
+    #[sql_type = "Text"] arch_home: String,
+    #[sql_type = "BigInt"] arch_size: i64,
+    #[sql_type = "Bool"] arch_done: bool,
+    #[sql_type = "Text"] arch_kind: String,
+    #[sql_type = "Text"] arch_loaded: String,
+    #[sql_type = "BigInt"] books: i64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ArchiveView {
+    pub id: Id,
+    pub arch_name: String,
+    pub arch_home: String,
+    pub arch_size: String,
+    pub arch_done: bool,
+    pub arch_kind: String,
+    pub arch_loaded: String,
+    pub books: i64,
+}
+impl From<ArchiveRow> for ArchiveView {
+    fn from(row: ArchiveRow) -> Self {
+        Self {
+            id: row.id,
+            arch_name: row.arch_name,
+            arch_home: row.arch_home,
+            arch_size: row.arch_size.to_formatted_string(&Locale::fr),
+            arch_done: row.arch_done,
+            arch_kind: row.arch_kind,
+            arch_loaded: row.arch_loaded,
+            books: row.books,
+        }
+    }
+}
+impl ArchiveView {
+    pub fn load_all(conn: &SqliteConnection) -> QueryResult<Vec<Self>> {
+        let rows: Vec<ArchiveRow> = sql_query(
+            r#"
+            SELECT archives.id, arch_name, archive_homes.arch_home, arch_size, arch_done, arch_kind, arch_loaded, count(books.id) AS books
+            FROM archives
+            JOIN archive_homes ON (archive_homes.arch_id = archives.id)
+            LEFT JOIN books ON (books.arch_id = archives.id)
+            GROUP BY archives.id
+            ORDER BY arch_name, archives.id
+            "#
+        ).load(conn)?;
+        Ok(rows.into_iter().map(ArchiveView::from).collect())
+    }
+
+    pub fn get_path(&self) -> PathBuf {
+        Path::new(&self.arch_home).join(&self.arch_name)
+    }
+}
+
+#[derive(QueryableByName, Debug, Clone, Serialize)]
+pub struct LibraryStats {
+    #[sql_type = "BigInt"] pub archives: i64,
+    #[sql_type = "BigInt"] pub books: i64,
+    #[sql_type = "BigInt"] pub authors: i64,
+    #[sql_type = "BigInt"] pub titles: i64,
+    #[sql_type = "BigInt"] pub series: i64,
+    #[sql_type = "BigInt"] pub genres: i64,
+    #[sql_type = "BigInt"] pub users: i64,
+    #[sql_type = "BigInt"] pub downloads: i64,
+}
+impl LibraryStats {
+    pub fn load(conn: &SqliteConnection) -> QueryResult<Self> {
+        sql_query(
+            r#"
+            SELECT
+                (SELECT count(*) FROM archives) AS archives,
+                (SELECT count(*) FROM books) AS books,
+                (SELECT count(*) FROM authors) AS authors,
+                (SELECT count(*) FROM titles) AS titles,
+                (SELECT count(*) FROM series) AS series,
+                (SELECT count(*) FROM genres) AS genres,
+                (SELECT count(*) FROM users) AS users,
+                (SELECT count(*) FROM downloads) AS downloads
+            "#
+        ).get_result(conn)
+    }
+}
+
+/// The archive found in the library directory but not loaded yet
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct NewArchive {
+    pub path: String,
+    pub size: String,
+    pub queued: bool,
+}
+
+/// The archives, the books and the directories of the library, sorted
+fn scan_library(dir: &Path, sources: &mut Vec<PathBuf>) {
+    let mut entries: Vec<PathBuf> = match fs::read_dir(dir) {
+        Ok(entries) => entries.filter_map(|entry| entry.ok()).map(|entry| entry.path()).collect(),
+        Err(_) => return,
+    };
+    entries.sort();
+    for entry in entries {
+        if entry.is_dir() {
+            scan_library(&entry, sources);
+        } else if SourceKind::detect(&entry).is_some() {
+            sources.push(entry);
+        }
+    }
+}
+
+/// The sources of the library not found among the loaded archives
+pub fn find_new_sources(archives: &[ArchiveView], library: &Path) -> Vec<PathBuf> {
+    let loaded: HashSet<PathBuf> = archives.iter().map(ArchiveView::get_path).collect();
+    let mut sources = Vec::new();
+    if let Ok(library) = library.canonicalize() {
+        scan_library(&library, &mut sources);
+    }
+    sources.into_iter().filter(|source| !loaded.contains(source)).collect()
+}
+
+/// The source of the load form, only the new archives of the library are accepted
+pub fn resolve_source(archives: &[ArchiveView], library: &Path, path: &str) -> Option<PathBuf> {
+    let source = library.join(path).canonicalize().ok()?;
+    find_new_sources(archives, library).into_iter().find(|new| *new == source)
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AdminContext {
+    pub user_name: String,
+    pub library: String,
+    pub database_size: String,
+    pub stats: LibraryStats,
+    pub archives: Vec<ArchiveView>,
+    pub new_archives: Vec<NewArchive>,
+    pub status: LoadStatus,
+    pub busy: bool,
+}
+impl AdminContext {
+    pub fn new(user_name: &str, library: &str, database: &str, stats: LibraryStats, status: LoadStatus) -> Self {
+        let database_size = fs::metadata(database).map(|meta| meta.len()).unwrap_or_default();
+        Self {
+            user_name: String::from(user_name),
+            library: String::from(library),
+            database_size: database_size.to_formatted_string(&Locale::fr),
+            stats: stats,
+            archives: Vec::new(),
+            new_archives: Vec::new(),
+            busy: status.is_busy(),
+            status: status,
+        }
+    }
+
+    pub fn load_archives(&mut self, conn: &SqliteConnection) -> QueryResult<()> {
+        self.archives = ArchiveView::load_all(conn)?;
+        if self.library.is_empty() {
+            return Ok(());
+        }
+        let library = Path::new(&self.library).canonicalize().unwrap_or_default();
+        let queued: HashSet<String> = self.status.queue.iter()
+            .chain(self.status.current.iter().map(|current| &current.name))
+            .cloned()
+            .collect();
+        self.new_archives = find_new_sources(&self.archives, &library).into_iter()
+            .map(|source| NewArchive {
+                path: source.strip_prefix(&library).unwrap_or(&source).to_string_lossy().to_string(),
+                size: fs::metadata(&source).map(|meta| meta.len()).unwrap_or_default().to_formatted_string(&Locale::fr),
+                queued: queued.contains(&LibraryJob::Load(source.clone()).get_name()),
+            })
+            .collect();
+        Ok(())
+    }
+}
@@ -0,0 +1,154 @@
+use diesel::sql_query;
+use diesel::sql_types::{BigInt, Double, Integer, Text};
+use diesel::RunQueryDsl;
+use serde::{Deserialize, Serialize};
+
+use super::QueryResult;
+use super::SqliteConnection;
+use super::paging::Paging;
+use super::recent_context::RecentBook;
+use crate::models::{Id, Review, ReviewRecord, Tag, TagLinkRecord};
+
+/// The form of the title page: rating=1..5 (0 clears the rating) and the review text
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ReviewForm {
+    #[serde(default)]
+    pub rating: i32,
+    #[serde(default)]
+    pub review: String,
+}
+
+/// The form of the title page: action=add|remove and the tag
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct TagForm {
+    pub tag: String,
+    #[serde(default)]
+    pub action: String,
+}
+
+#[derive(QueryableByName, Debug, Clone, Serialize)]
+pub struct ReviewView {
+    #[sql_type = "Integer"] #[serde(skip)] pub user_id: i32,
+    #[sql_type = "Text"] pub user_name: String,
+    #[sql_type = "Integer"] pub rating: i32,
+    #[sql_type = "Text"] pub review: String,
+    #[sql_type = "Text"] pub review_added: String,
+}
+
+#[derive(QueryableByName, Debug, Clone, Serialize)]
+pub struct TagView {
+    #[sql_type = "Text"] pub tag_name: String,
+    #[sql_type = "BigInt"] pub uses: i64,
+    #[sql_type = "Integer"] pub mine: i32,
+}
+
+/// The ratings, the reviews and the tags of the canonical book
+#[derive(Debug, Clone, Serialize)]
+pub struct BookAnnotations {
+    pub book_id: Id,
+    pub rating: f64,
+    pub votes: i64,
+    pub reviews: Vec<ReviewView>,
+    pub tags: Vec<TagView>,
+    pub my_rating: i32,
+    pub my_review: String,
+}
+impl BookAnnotations {
+    /// The user is used to find the own rating, review and tags
+    pub fn load(conn: &SqliteConnection, book: Id, user: Option<Id>) -> QueryResult<Self> {
+        #[derive(QueryableByName, Debug, Clone)]
+        struct Summary {
+            #[sql_type = "Double"] rating: f64,
+            #[sql_type = "BigInt"] votes: i64,
+        }
+        let user = user.unwrap_or(0);
+        let summary: Vec<Summary> = sql_query(format!(
+            r#"
+            SELECT ifnull(round(avg(rating), 1), 0.0) AS rating, count(rating) AS votes
+            FROM reviews
+            WHERE book_id = {book} AND rating > 0
+            "#,
+            book = book
+        )).load(conn)?;
+
+        let reviews: Vec<ReviewView> = sql_query(format!(
+            r#"
+            SELECT user_id, user_name, rating, review, review_added
+            FROM reviews
+            JOIN users ON (users.id = reviews.user_id)
+            WHERE book_id = {book}
+            ORDER BY review_added DESC
+            "#,
+            book = book
+        )).load(conn)?;
+
+        let tags: Vec<TagView> = sql_query(format!(
+            r#"
+            SELECT tag_name, count(tag_links.id) AS uses, max(user_id = {user}) AS mine
+            FROM tag_links
+            JOIN tags ON (tags.id = tag_links.tag_id)
+            WHERE book_id = {book}
+            GROUP BY tag_name
+            ORDER BY min(tag_name_key)
+            "#,
+            book = book,
+            user = user
+        )).load(conn)?;
+
+        let (rating, votes) = summary.first().map(|s| (s.rating, s.votes)).unwrap_or((0.0, 0));
+        let own = reviews.iter().find(|review| review.user_id == user).cloned();
+        Ok(Self {
+            book_id: book,
+            rating: rating,
+            votes: votes,
+            my_rating: own.as_ref().map(|review| review.rating).unwrap_or(0),
+            my_review: own.map(|review| review.review).unwrap_or_default(),
+            reviews: reviews,
+            tags: tags,
+        })
+    }
+}
+
+/// The tags of the all users or the books tagged with the tag
+#[derive(Debug, Clone, Serialize)]
+pub struct TagContext {
+    pub tag: String,
+    pub tags: Vec<TagView>,
+    pub books: Vec<RecentBook>,
+    pub paging: Paging,
+}
+impl TagContext {
+    pub fn new(tag: &str, mut paging: Paging) -> Self {
+        paging.tag = String::from(tag.trim());
+        Self {
+            tag: paging.tag.clone(),
+            tags: Vec::new(),
+            books: Vec::new(),
+            paging: paging,
+        }
+    }
+
+    pub fn load_tags(conn: &SqliteConnection) -> QueryResult<Vec<TagView>> {
+        sql_query(
+            r#"
+            SELECT tag_name, count(DISTINCT book_id) AS uses, 0 AS mine
+            FROM tag_links
+            JOIN tags ON (tags.id = tag_links.tag_id)
+            GROUP BY tag_name
+            ORDER BY min(tag_name_key)
+            "#
+        ).load(conn)
+    }
+}
+
+pub fn update_review(conn: &SqliteConnection, user: Id, book: Id, form: &ReviewForm) -> QueryResult<usize> {
+    ReviewRecord::put(conn, &Review::new(user, book, form.rating, &form.review))
+}
+
+pub fn update_tags(conn: &SqliteConnection, user: Id, book: Id, form: &TagForm) -> QueryResult<usize> {
+    let tag = Tag::new(&form.tag);
+    match form.action.as_str() {
+        "remove" => TagLinkRecord::remove(conn, user, book, &tag),
+        _ => TagLinkRecord::put(conn, user, book, &tag),
+    }
+}
@@ -1,87 +1,190 @@
 use serde::Serialize;
+use diesel::sql_query;
+use diesel::sql_types::{Integer, Text};
+use diesel::RunQueryDsl;
+use crate::normalize::slugify;
 use super::author_mask::AuthorMask;
+use super::link::Link;
+use super::paging::Paging;
+use super::{QueryResult, SqliteConnection};
+use crate::models::{AuthorRecord, Id, Load};
 
+/// The author found by the names, the authors of the same names share the first id
+#[derive(QueryableByName, Debug, Clone, Serialize)]
+pub struct AuthorEntry {
+    #[sql_type = "Integer"] pub author_id: i32,
+    #[sql_type = "Text"] pub first_name: String,
+    #[sql_type = "Text"] pub middle_name: String,
+    #[sql_type = "Text"] pub last_name: String,
+}
+impl AuthorEntry {
+    /// The first of the authors of the same names
+    pub fn find(conn: &SqliteConnection, mask: &AuthorMask) -> QueryResult<Self> {
+        sql_query(r#"
+            SELECT id AS author_id, first_name, middle_name, last_name
+            FROM authors
+            WHERE first_name = ? AND middle_name = ? AND last_name = ?
+            ORDER BY id
+            LIMIT 1"#)
+            .bind::<Text, _>(&mask.first_name)
+            .bind::<Text, _>(&mask.middle_name)
+            .bind::<Text, _>(&mask.last_name)
+            .get_result(conn)
+    }
+
+    pub fn load(conn: &SqliteConnection, id: Id) -> QueryResult<Self> {
+        let record = AuthorRecord::load(conn, id)?;
+        Self::find(conn, &AuthorMask {
+            first_name: record.first_name,
+            middle_name: record.middle_name,
+            last_name: record.last_name,
+        })
+    }
+
+    pub fn get_mask(&self) -> AuthorMask {
+        AuthorMask {
+            first_name: self.first_name.clone(),
+            middle_name: self.middle_name.clone(),
+            last_name: self.last_name.clone(),
+        }
+    }
+
+    pub fn get_full_name(&self) -> String {
+        format!("{} {} {}", self.last_name, self.first_name, self.middle_name)
+    }
+
+    pub fn get_link(&self) -> Link {
+        let name = self.get_full_name();
+        Link::new(&name, &["author", &self.author_id.to_string(), &slugify(&name)])
+    }
+
+    pub fn get_url(&self) -> String {
+        Link::make_path(&self.get_link().path) + "/"
+    }
+}
+
+/// The author search page, the links of the names keep the rest of the mask
 #[derive(Debug, Clone, Serialize)]
 pub struct FindAuthorContext {
     pub first_name: String,
-    pub first_name_previous: String,
     pub middle_name: String,
-    pub middle_name_previous: String,
     pub last_name: String,
-    pub last_name_previous: String,
-    pub root_url: String,
-    pub authors: Vec<String>,
-    pub first_name_nvc: Vec<String>,
-    pub middle_name_nvc: Vec<String>,
-    pub last_name_nvc: Vec<String>,
+    pub query: String,
+    pub first_name_reset: Link,
+    pub first_name_previous: Link,
+    pub middle_name_reset: Link,
+    pub middle_name_previous: Link,
+    pub last_name_reset: Link,
+    pub last_name_previous: Link,
+    pub authors: Vec<Link>,
+    pub first_name_nvc: Vec<Link>,
+    pub middle_name_nvc: Vec<Link>,
+    pub last_name_nvc: Vec<Link>,
+    pub paging: Paging,
+    #[serde(skip)]
+    pub mask: AuthorMask,
 }
 impl FindAuthorContext {
 
-    pub fn new(url: &str, mask: &AuthorMask) -> Self {
+    pub fn new(mask: &AuthorMask) -> Self {
+        let reset = |name: &str| mask.with_name(name, "").get_link("[Сброс]");
+        let previous = |name: &str| {
+            let mut value = String::from(mask.get_by_name(name));
+            value.pop();
+            mask.with_name(name, &value).get_link(&format!("[{}]", value))
+        };
         Self {
-            first_name: mask.get_encoded_by_name("first_name"),
-            first_name_previous: mask.get_encoded_by_name_previous("first_name"),
-            middle_name: mask.get_encoded_by_name("middle_name"),
-            middle_name_previous: mask.get_encoded_by_name_previous("middle_name"),
-            last_name: mask.get_encoded_by_name("last_name"),
-            last_name_previous: mask.get_encoded_by_name_previous("last_name"),
-            root_url: String::from(url),
+            first_name: mask.first_name.clone(),
+            middle_name: mask.middle_name.clone(),
+            last_name: mask.last_name.clone(),
+            query: mask.get_link("").query,
+            first_name_reset: reset("first_name"),
+            first_name_previous: previous("first_name"),
+            middle_name_reset: reset("middle_name"),
+            middle_name_previous: previous("middle_name"),
+            last_name_reset: reset("last_name"),
+            last_name_previous: previous("last_name"),
             authors: Vec::new(),
             first_name_nvc: Vec::new(),
             middle_name_nvc: Vec::new(),
             last_name_nvc: Vec::new(),
+            paging: Paging::default(),
+            mask: mask.clone(),
         }
     }
 
+    fn load_nvc(&self, name: &str, nvc: Vec<String>) -> Vec<Link> {
+        nvc.iter().map(|value| self.mask.with_name(name, value).get_link(value)).collect()
+    }
+
     pub fn load_first_name_nvc(&mut self, nvc: Vec<String>) {
-        self.first_name_nvc = nvc.iter().map(|first|
-            format!("<a href='/{}/{}/{}/{}/'>{}</a>",
-                self.root_url,
-                AuthorMask::encode(first.clone()),
-                AuthorMask::encode(self.middle_name.clone()),
-                AuthorMask::encode(self.last_name.clone()),
-                first)).collect();
+        self.first_name_nvc = self.load_nvc("first_name", nvc);
     }
 
     pub fn load_middle_name_nvc(&mut self, nvc: Vec<String>) {
-        self.middle_name_nvc = nvc.iter().map(|middle|
-            format!("<a href='/{}/{}/{}/{}/'>{}</a>",
-                self.root_url,
-                AuthorMask::encode(self.first_name.clone()),
-                AuthorMask::encode(middle.clone()),
-                AuthorMask::encode(self.last_name.clone()),
-                middle)).collect();
+        self.middle_name_nvc = self.load_nvc("middle_name", nvc);
     }
 
     pub fn load_last_name_nvc(&mut self, nvc: Vec<String>) {
-        self.last_name_nvc = nvc.iter().map(|last|
-            format!("<a href='/{}/{}/{}/{}/'>{}</a>",
-                self.root_url,
-                AuthorMask::encode(self.first_name.clone()),
-                AuthorMask::encode(self.middle_name.clone()),
-                AuthorMask::encode(last.clone()),
-                last)).collect();
+        self.last_name_nvc = self.load_nvc("last_name", nvc);
     }
 
 }
 
+/// The authors of the mask listed by the API and the OPDS catalog
 #[derive(Debug, Clone, Serialize)]
-pub struct AuthorContext {
+pub struct AuthorListContext {
     pub first_name: String,
     pub middle_name: String,
     pub last_name: String,
-    pub root_url: String,
-    pub titles: Vec<String>,
+    pub updated: String,
+    pub authors: Vec<AuthorEntry>,
+    pub paging: Paging,
 }
-impl AuthorContext {
-    pub fn new(url: &str, mask: &AuthorMask) -> Self {
+impl AuthorListContext {
+    pub fn new(mask: &AuthorMask, paging: Paging) -> Self {
         Self {
             first_name: mask.first_name.clone(),
             middle_name: mask.middle_name.clone(),
             last_name: mask.last_name.clone(),
-            root_url: String::from(url),
-            titles: Vec::new()
+            updated: chrono::Utc::now().format("%Y-%m-%dT%H:%M:%SZ").to_string(),
+            authors: Vec::new(),
+            paging: paging,
         }
     }
 }
 
+#[derive(Debug, Clone, Serialize)]
+pub struct AuthorContext {
+    pub id: i32,
+    pub slug: String,
+    pub first_name: String,
+    pub middle_name: String,
+    pub last_name: String,
+    pub titles: Vec<Link>,
+    pub translated: Vec<Link>,
+    pub series: Vec<Link>,
+    pub paging: Paging,
+    pub translated_paging: Paging,
+}
+impl AuthorContext {
+    pub fn new(author: &AuthorEntry) -> Self {
+        Self {
+            id: author.author_id,
+            slug: slugify(&author.get_full_name()),
+            first_name: author.first_name.clone(),
+            middle_name: author.middle_name.clone(),
+            last_name: author.last_name.clone(),
+            titles: Vec::new(),
+            translated: Vec::new(),
+            series: Vec::new(),
+            paging: Paging::default(),
+            translated_paging: Paging::default(),
+        }
+    }
+
+    /// The canonical URL of the page, the other URLs of the author are redirected to it
+    pub fn get_url(&self) -> String {
+        Link::make_path(&["author", &self.id.to_string(), &self.slug]) + "/"
+    }
+}
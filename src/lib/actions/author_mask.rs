@@ -1,19 +1,25 @@
 use diesel::sql_types::Text;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+use super::like_prefix;
+use super::link::Link;
 
 pub trait NvcMethods {
     fn get_length_by_name(&self, name: &str) -> usize;
-    fn get_where_like_clause(&self) -> String;
-    fn get_where_explicit_clause(&self) -> String;
+    /// The condition with the '?' placeholders and the values bound to them in order
+    fn get_where_like_clause(&self) -> (String, Vec<String>);
+    fn get_where_explicit_clause(&self) -> (String, Vec<String>);
 }
 
-#[derive(QueryableByName, Debug, Clone, Serialize)]
+/// The names of the author search, the query of the /authors/ page: ?last_name=Тол&first_name=Л
+#[derive(QueryableByName, Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
 pub struct AuthorMask {
     #[sql_type = "Text"] pub first_name: String,
     #[sql_type = "Text"] pub middle_name: String,
     #[sql_type = "Text"] pub last_name: String,
 }
 impl AuthorMask {
+    /// The legacy URLs mark the empty name by '-'
     fn decode(mask: String) -> String {
         if &mask == "-" {
             String::new()
@@ -22,36 +28,39 @@ impl AuthorMask {
         }
     }
 
-    pub fn encode(val: String) -> String {
-        if val.is_empty() {
-            String::from("-")
-        } else {
-            val
+    pub fn get_by_name(&self, name: &str) -> &str {
+        match name {
+            "first_name" => &self.first_name,
+            "middle_name" => &self.middle_name,
+            "last_name" => &self.last_name,
+            _ => "",
         }
     }
 
-    pub fn get_encoded_by_name(&self, name: &str) -> String {
+    /// The mask with the name replaced by the value
+    pub fn with_name(&self, name: &str, value: &str) -> Self {
+        let mut mask = self.clone();
         match name {
-            "first_name" => Self::encode(self.first_name.clone()),
-            "middle_name" => Self::encode(self.middle_name.clone()),
-            "last_name" => Self::encode(self.last_name.clone()),
-            _ => String::new()
+            "first_name" => mask.first_name = String::from(value),
+            "middle_name" => mask.middle_name = String::from(value),
+            "last_name" => mask.last_name = String::from(value),
+            _ => {},
         }
+        mask
     }
 
-    pub fn get_encoded_by_name_previous(&self, name: &str) -> String {
-        let mut name = self.get_encoded_by_name(name);
-        if "-" != &name{
-            name.pop();
-        }
-        return name;
+    /// The query parameters of the names, the empty names are omitted from the links
+    pub fn get_params(&self) -> Vec<(&'static str, String)> {
+        vec![
+            ("first_name", self.first_name.clone()),
+            ("middle_name", self.middle_name.clone()),
+            ("last_name", self.last_name.clone()),
+        ]
     }
 
-    pub fn get_uri(&self) -> String {
-        format!("{}/{}/{}",
-            Self::encode(self.first_name.clone()),
-            Self::encode(self.middle_name.clone()),
-            Self::encode(self.last_name.clone()))
+    /// The link to the search page of the mask
+    pub fn get_link(&self, label: &str) -> Link {
+        Link::with_query(label, &["authors"], &self.get_params())
     }
 
     pub fn new(first_name: String, middle_name: String, last_name: String) -> Self {
@@ -82,55 +91,87 @@ impl NvcMethods for AuthorMask {
             _ => 0
         }
     }
-    fn get_where_like_clause(&self) -> String {
+    fn get_where_like_clause(&self) -> (String, Vec<String>) {
         let mut clauses = Vec::new();
+        let mut values = Vec::new();
         if !self.first_name.is_empty()
         {
-            clauses.push(format!("first_name LIKE '{}%'", self.first_name));
+            clauses.push(r"first_name_key LIKE ? ESCAPE '\'".to_owned());
+            values.push(like_prefix(&self.first_name));
         }
         if !self.middle_name.is_empty() {
             if !clauses.is_empty() {
                 clauses.push("AND".to_owned());
             }
-            clauses.push(format!("middle_name LIKE '{}%'", self.middle_name));
+            clauses.push(r"middle_name_key LIKE ? ESCAPE '\'".to_owned());
+            values.push(like_prefix(&self.middle_name));
         }
         if !self.last_name.is_empty() {
             if !clauses.is_empty() {
                 clauses.push("AND".to_owned());
             }
-            clauses.push(format!("last_name LIKE '{}%'", self.last_name));
+            clauses.push(r"last_name_key LIKE ? ESCAPE '\'".to_owned());
+            values.push(like_prefix(&self.last_name));
         }
 
         return if clauses.is_empty() {
-           String::new()
+           (String::new(), values)
         } else {
-            "WHERE ".to_owned() + &clauses.join(" ")
+            ("WHERE ".to_owned() + &clauses.join(" "), values)
         }
     }
 
-    fn get_where_explicit_clause(&self) -> String {
+    fn get_where_explicit_clause(&self) -> (String, Vec<String>) {
         let mut clauses = Vec::new();
+        let mut values = Vec::new();
         if !self.first_name.is_empty()
         {
-            clauses.push(format!("first_name = '{}'", self.first_name));
+            clauses.push("first_name = ?".to_owned());
+            values.push(self.first_name.clone());
         }
         if !self.middle_name.is_empty() {
             if !clauses.is_empty() {
                 clauses.push("AND".to_owned());
             }
-            clauses.push(format!("middle_name = '{}'", self.middle_name));
+            clauses.push("middle_name = ?".to_owned());
+            values.push(self.middle_name.clone());
         }
         if !self.last_name.is_empty() {
             if !clauses.is_empty() {
                 clauses.push("AND".to_owned());
             }
-            clauses.push(format!("last_name = '{}'", self.last_name));
+            clauses.push("last_name = ?".to_owned());
+            values.push(self.last_name.clone());
         }
 
         return if clauses.is_empty() {
-           String::new()
+           (String::new(), values)
         } else {
-            "WHERE ".to_owned() + &clauses.join(" ")
+            ("WHERE ".to_owned() + &clauses.join(" "), values)
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_get_where_explicit_clause() {
+        let mask = AuthorMask::new(String::from("Flann"), String::from("-"), String::from("O'Brien"));
+        assert_eq!(mask.get_where_explicit_clause(), (
+            String::from("WHERE first_name = ? AND last_name = ?"),
+            vec![String::from("Flann"), String::from("O'Brien")]
+        ));
+        let mask = AuthorMask::new(String::from("-"), String::from("-"), String::from("-"));
+        assert_eq!(mask.get_where_explicit_clause(), (String::new(), Vec::new()));
+    }
+
+    #[test]
+    fn test_get_link() {
+        let mask = AuthorMask::new(String::from("-"), String::from("-"), String::from("Тол"));
+        assert_eq!(mask.get_link("").get_url(), "/authors/?last_name=%D0%A2%D0%BE%D0%BB");
+        assert_eq!(mask.with_name("first_name", "Л").get_link("").query, "first_name=%D0%9B&last_name=%D0%A2%D0%BE%D0%BB");
+        assert_eq!(mask.with_name("last_name", "").get_link("").get_url(), "/authors/");
+    }
+}
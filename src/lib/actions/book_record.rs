@@ -2,16 +2,20 @@ use diesel::prelude::*;
 use diesel::sql_query;
 use diesel::sql_types::Text;
 use diesel::sql_types::Integer;
+use diesel::sql_types::BigInt;
 use num_format::{Locale, ToFormattedString};
 
 use serde::Serialize;
 use super::QueryResult;
-use super::NvcMethods;
+use super::Id;
+use super::TitleEntry;
 use super::SqliteConnection;
 
 
 #[derive(QueryableByName, Debug, Clone, Serialize)]
 pub struct BookRecord {
+    #[sql_type = "Integer"] pub book_id: i32,
+    #[sql_type = "Integer"] pub canon_id: i32,
     #[sql_type = "Text"] pub book_title: String,
     #[sql_type = "Text"] pub book_file: String,
 
@@ -20,43 +24,107 @@ pub struct BookRecord {
 
     #[sql_type = "Text"] pub arch_name: String,
     #[sql_type = "Text"] pub arch_home: String,
+    #[sql_type = "Text"] pub arch_kind: String,
+
+    #[sql_type = "Text"] pub doc_version: String,
+    #[sql_type = "Text"] pub doc_date: String,
+    #[sql_type = "Text"] pub program_used: String,
+    #[sql_type = "Text"] pub src_url: String,
+    #[sql_type = "Text"] pub creator: String,
+
+    #[sql_type = "Text"] pub publisher: String,
+    #[sql_type = "Text"] pub city: String,
+    #[sql_type = "Text"] pub year: String,
+    #[sql_type = "Text"] pub isbn: String,
+
+    #[sql_type = "Text"] pub translators: String,
+    #[sql_type = "Text"] pub src_title: String,
+    #[sql_type = "Text"] pub src_authors: String,
+    #[sql_type = "Text"] pub src_lang: String,
+
+    #[sql_type = "BigInt"] pub downloads: i64,
 }
 impl BookRecord {
-    pub fn load_by_author_and_title(conn: &SqliteConnection, author: &dyn NvcMethods, title: &String) -> QueryResult<Vec<Self>>{
+    /// The versions of the book: the books of the same title written by the author
+    pub fn load_by_author_and_title(conn: &SqliteConnection, author: Id, title: &str) -> QueryResult<Vec<Self>>{
         let query = format!(
             r#"
-            SELECT book_title, book_file, book_size, book_crc32, arch_name, arch_home
+            SELECT books.id AS book_id, ifnull(canon_id, books.id) AS canon_id,
+                   book_title, book_file, book_size, book_crc32, arch_name, archive_homes.arch_home, arch_kind,
+                   ifnull(doc_version, '') AS doc_version, ifnull(doc_date, '') AS doc_date,
+                   ifnull(program_used, '') AS program_used, ifnull(src_url, '') AS src_url, ifnull(creator, '') AS creator,
+                   ifnull(publisher, '') AS publisher, ifnull(city, '') AS city, ifnull(year, '') AS year,
+                   ifnull((SELECT group_concat(isbn, ', ') FROM isbns WHERE isbns.book_id = books.id), '') AS isbn,
+                   ifnull((SELECT group_concat(translators_view.last_name || ' ' || translators_view.first_name, ', ')
+                           FROM translators_view WHERE translators_view.book_id = books.id), '') AS translators,
+                   ifnull(src_title, '') AS src_title, ifnull(src_authors, '') AS src_authors, ifnull(src_lang, '') AS src_lang,
+                   (SELECT count(*) FROM downloads WHERE downloads.book_id = books.id) AS downloads
             FROM title_links
             JOIN author_links ON (author_links.book_id = title_links.book_id)
             LEFT JOIN authors ON (author_links.author_id = authors.id)
             LEFT JOIN titles ON (title_links.title_id = titles.id)
             LEFT JOIN books ON (title_links.book_id = books.id)
             LEFT JOIN archives ON (books.arch_id = archives.id)
-            {where_clause}
-            AND book_title = '{title}'
+            LEFT JOIN archive_homes ON (archive_homes.arch_id = archives.id)
+            LEFT JOIN documents ON (documents.book_id = books.id)
+            LEFT JOIN publications ON (publications.book_id = books.id)
+            LEFT JOIN originals ON (originals.book_id = books.id)
+            WHERE author_links.author_id = {author} AND author_links.role = 'author' AND book_title = ?
+            ORDER BY canon_id, doc_date, books.id
             "#,
-            where_clause = author.get_where_explicit_clause(),
-            title = title
+            author = author
+        );
+
+        sql_query(&query).bind::<Text, _>(title).load(conn)
+    }
+
+    pub fn load_by_isbn(conn: &SqliteConnection, isbn_key: &String) -> QueryResult<Vec<TitleEntry>> {
+        let query = format!(
+            r#"
+            SELECT min(isbns.book_id) AS book_id, min(authors.id) AS author_id, book_title, first_name, middle_name, last_name
+            FROM isbns
+            JOIN title_links ON (title_links.book_id = isbns.book_id)
+            JOIN author_links ON (author_links.book_id = isbns.book_id)
+            LEFT JOIN authors ON (author_links.author_id = authors.id)
+            LEFT JOIN titles ON (title_links.title_id = titles.id)
+            WHERE isbn_key = '{isbn}' AND role = 'author'
+            GROUP BY book_title, last_name, first_name, middle_name
+            ORDER BY book_title, last_name, first_name, middle_name
+            "#,
+            isbn = isbn_key
         );
 
         sql_query(&query).load(conn)
     }
 
-    pub fn load_by_archive_and_book(conn: &SqliteConnection, archive: &String, book: &String)-> QueryResult<BookRecord> {
+    /// The condition with the '?' placeholders and the values bound to them in order
+    fn load_one(conn: &SqliteConnection, condition: &str, values: &[String]) -> QueryResult<BookRecord> {
         let query = format!(
             r#"
-            SELECT book_title, book_file, book_size, book_crc32, arch_name, arch_home
+            SELECT books.id AS book_id, ifnull(canon_id, books.id) AS canon_id,
+                   book_title, book_file, book_size, book_crc32, arch_name, archive_homes.arch_home, arch_kind,
+                   ifnull(doc_version, '') AS doc_version, ifnull(doc_date, '') AS doc_date,
+                   ifnull(program_used, '') AS program_used, ifnull(src_url, '') AS src_url, ifnull(creator, '') AS creator,
+                   ifnull(publisher, '') AS publisher, ifnull(city, '') AS city, ifnull(year, '') AS year,
+                   ifnull((SELECT group_concat(isbn, ', ') FROM isbns WHERE isbns.book_id = books.id), '') AS isbn,
+                   ifnull((SELECT group_concat(translators_view.last_name || ' ' || translators_view.first_name, ', ')
+                           FROM translators_view WHERE translators_view.book_id = books.id), '') AS translators,
+                   ifnull(src_title, '') AS src_title, ifnull(src_authors, '') AS src_authors, ifnull(src_lang, '') AS src_lang,
+                   (SELECT count(*) FROM downloads WHERE downloads.book_id = books.id) AS downloads
             FROM title_links
             LEFT JOIN titles ON (title_links.title_id = titles.id)
             LEFT JOIN books ON (title_links.book_id = books.id)
             LEFT JOIN archives ON (books.arch_id = archives.id)
-            WHERE arch_name = '{archive}' and book_file = '{file}'
+            LEFT JOIN archive_homes ON (archive_homes.arch_id = archives.id)
+            LEFT JOIN documents ON (documents.book_id = books.id)
+            LEFT JOIN publications ON (publications.book_id = books.id)
+            LEFT JOIN originals ON (originals.book_id = books.id)
+            WHERE {condition}
             "#,
-            archive = archive,
-            file = book
+            condition = condition
         );
 
-        let records: Vec<BookRecord> = sql_query(&query).load(conn)?;
+        let records: Vec<BookRecord> = super::load_bound(conn, &query, values)?;
         if let Some(record) = records.first() {
             Ok(record.clone())
         } else {
@@ -66,32 +134,68 @@ impl BookRecord {
 
     }
 
+    pub fn load_by_id(conn: &SqliteConnection, book: i32) -> QueryResult<BookRecord> {
+        Self::load_one(conn, &format!("books.id = {}", book), &[])
+    }
+
+    pub fn load_by_archive_and_book(conn: &SqliteConnection, archive: &String, book: &String) -> QueryResult<BookRecord> {
+        Self::load_one(conn, "arch_name = ? AND book_file = ?", &[archive.clone(), book.clone()])
+    }
+
 }
 
 #[derive(Debug, Clone, Serialize)]
 pub struct BookStringified {
-    pub book_url: String,
-    pub book_zip_url: String,
+    pub book_id: i32,
+    pub canon_id: i32,
     pub book_title: String,
     pub book_file: String,
     pub book_size: String,
     pub book_crc32: String,
     pub arch_name: String,
     pub arch_home: String,
+    pub doc_version: String,
+    pub doc_date: String,
+    pub program_used: String,
+    pub src_url: String,
+    pub creator: String,
+    pub publisher: String,
+    pub city: String,
+    pub year: String,
+    pub isbns: Vec<String>,
+    pub translators: String,
+    pub src_title: String,
+    pub src_authors: String,
+    pub src_lang: String,
+    pub downloads: i64,
 }
 impl BookStringified {
     pub fn transform(books: Vec<BookRecord>) -> Vec<Self> {
         let mut result = Vec::new();
         for book in books {
             result.push(Self {
-                book_url: format!("<a href='/download/{}/{}'>fb2</a>", book.arch_name, book.book_file),
-                book_zip_url: format!("<a href='/download_zip/{}/{}'>fb2.zip</a>", book.arch_name, book.book_file),
+                book_id: book.book_id,
+                canon_id: book.canon_id,
                 book_title: book.book_title,
                 book_file: book.book_file,
                 book_size: format!("{}", book.book_size.to_formatted_string(&Locale::fr)),
                 book_crc32: format!("{:#02X}", book.book_crc32),
                 arch_name: book.arch_name,
-                arch_home: book.arch_home
+                arch_home: book.arch_home,
+                doc_version: book.doc_version,
+                doc_date: book.doc_date,
+                program_used: book.program_used,
+                src_url: book.src_url,
+                creator: book.creator,
+                publisher: book.publisher,
+                city: book.city,
+                year: book.year,
+                isbns: book.isbn.split(", ").filter(|isbn| !isbn.is_empty()).map(String::from).collect(),
+                translators: book.translators,
+                src_title: book.src_title,
+                src_authors: book.src_authors,
+                src_lang: book.src_lang,
+                downloads: book.downloads,
             });
         }
         return result;
@@ -1,6 +1,6 @@
 use std::io;
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::ffi::OsStr;
 use std::convert::TryFrom;
 use actix_files::NamedFile;
@@ -9,12 +9,38 @@ use sanitize_filename;
 
 use super::book_record::BookRecord;
 use super::super::parser;
+use super::super::models::SourceKind;
+
+/// The KOReader document hash: MD5 of the 1 Kb samples at the offsets 0 and 1024 * 4^i,
+/// the reader is rewound to the start
+pub fn partial_md5<R: io::Read + io::Seek>(reader: &mut R) -> io::Result<String> {
+    let mut ctx = md5::Context::new();
+    let mut buffer = [0u8; 1024];
+    for i in 0..12 {
+        let offset = if 0 == i { 0 } else { 1024u64 << (2 * (i - 1)) };
+        reader.seek(io::SeekFrom::Start(offset))?;
+        let mut size = 0;
+        while size < buffer.len() {
+            match reader.read(&mut buffer[size..])? {
+                0 => break,
+                readed => size += readed,
+            }
+        }
+        if 0 == size {
+            break;
+        }
+        ctx.consume(&buffer[0..size]);
+    }
+    reader.seek(io::SeekFrom::Start(0))?;
+    Ok(format!("{:x}", ctx.compute()))
+}
 
 #[derive(Debug)]
 pub struct DownloadContext {
     pub book: BookRecord,
     pub workdir: String,
     pub files: Vec<String>,
+    pub document: String,
 }
 
 impl Drop for DownloadContext {
@@ -32,6 +58,7 @@ impl DownloadContext {
             book: book,
             workdir: workdir.clone(),
             files: Vec::new(),
+            document: String::new(),
         }
     }
 
@@ -44,6 +71,15 @@ impl DownloadContext {
         Ok(())
     }
 
+    fn extract(&self, outfile: &Path) -> io::Result<()> {
+        let source = Path::new(&self.book.arch_home).join(&self.book.arch_name);
+        if SourceKind::parse(&self.book.arch_kind).is_zipped() {
+            Self::unzip(&source, &self.book.book_file, outfile)
+        } else {
+            fs::copy(&source, outfile).map(|_| ())
+        }
+    }
+
     fn zip(infile: &Path, outfile: &Path) -> io::Result<()> {
         let arch = fs::File::create(outfile)?;
         let mut archive = zip::ZipWriter::new(arch);
@@ -76,23 +112,28 @@ impl DownloadContext {
         return Ok(name);
     }
 
+    /// The book file may be in a subdirectory of the archive, only its name is kept
+    fn get_work_path(&self) -> PathBuf {
+        let name = Path::new(&self.book.book_file).file_name().and_then(OsStr::to_str).unwrap_or_default();
+        Path::new(&self.workdir).join(sanitize_filename::sanitize(name))
+    }
+
     pub fn get_unzipped_stream(&mut self) -> io::Result<NamedFile> {
-        let arch = Path::new(&self.book.arch_home).join(&self.book.arch_name);
-        let unzipped = Path::new(&self.workdir).join(&self.book.book_file);
-        Self::unzip(&arch, &self.book.book_file, &unzipped)?;
+        let unzipped = self.get_work_path();
+        self.extract(&unzipped)?;
 
         self.files.push(unzipped.to_string_lossy().to_string());
 
         let book_name = Self::make_name(unzipped.as_path(), &self.book.book_file)?;
-        let file = fs::File::open(unzipped)?;
+        let mut file = fs::File::open(unzipped)?;
+        self.document = partial_md5(&mut file)?;
         NamedFile::from_file(file, book_name)
         //NamedFile::open(outfile)
     }
 
     pub fn get_zipped_stream(&mut self) -> io::Result<NamedFile> {
-        let arch = Path::new(&self.book.arch_home).join(&self.book.arch_name);
-        let unzipped = Path::new(&self.workdir).join(&self.book.book_file);
-        Self::unzip(&arch, &self.book.book_file, &unzipped)?;
+        let unzipped = self.get_work_path();
+        self.extract(&unzipped)?;
         self.files.push(unzipped.to_string_lossy().to_string());
 
         let ext = unzipped.extension().and_then(OsStr::to_str).unwrap_or("");
@@ -102,8 +143,21 @@ impl DownloadContext {
         self.files.push(zipped.to_string_lossy().to_string());
 
         let book_name = Self::make_name(unzipped.as_path(), &self.book.book_file)?;
-        let file = fs::File::open(zipped)?;
+        let mut file = fs::File::open(zipped)?;
+        self.document = partial_md5(&mut file)?;
         NamedFile::from_file(file, format!("{}.zip", book_name))
         //NamedFile::open(zipped)
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_partial_md5() {
+        let data: Vec<u8> = (0..70000usize).map(|i| ((i * 7 + i / 256) % 256) as u8).collect();
+        assert_eq!(partial_md5(&mut io::Cursor::new(data)).unwrap(), "630956e9ad835a13bdb64a0a7eccc983");
+        assert_eq!(partial_md5(&mut io::Cursor::new(Vec::new())).unwrap(), "d41d8cd98f00b204e9800998ecf8427e");
+    }
+}
@@ -0,0 +1,41 @@
+use serde::Serialize;
+use super::recent_context::RecentBook;
+
+/// The Atom subscription feed of the latest loaded books
+#[derive(Debug, Clone, Serialize)]
+pub struct FeedContext {
+    /// The specific part of the tag URI of the feed
+    pub id: String,
+    pub title: String,
+    pub author: String,
+    pub self_url: String,
+    pub alternate_url: String,
+    pub updated: String,
+    pub books: Vec<RecentBook>,
+}
+impl FeedContext {
+    pub const FEED_SIZE: usize = 50;
+
+    /// The feeds of no single author are written by the library
+    pub const LIBRARY_AUTHOR: &'static str = "fb2service";
+
+    pub fn new(id: String, title: String, author: String, self_url: String, alternate_url: String) -> Self {
+        Self {
+            id: id,
+            title: title,
+            author: author,
+            self_url: self_url,
+            alternate_url: alternate_url,
+            updated: chrono::Utc::now().format("%Y-%m-%dT%H:%M:%SZ").to_string(),
+            books: Vec::new(),
+        }
+    }
+
+    /// The feed is updated when its latest book was loaded
+    pub fn load_books(&mut self, books: Vec<RecentBook>) {
+        if let Some(book) = books.first() {
+            self.updated = book.updated.clone();
+        }
+        self.books = books;
+    }
+}
@@ -0,0 +1,16 @@
+use serde::Serialize;
+use super::link::Link;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct IsbnContext {
+    pub isbn: String,
+    pub titles: Vec<Link>,
+}
+impl IsbnContext {
+    pub fn new(isbn: String) -> Self {
+        Self {
+            isbn: isbn,
+            titles: Vec::new(),
+        }
+    }
+}
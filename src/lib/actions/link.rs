@@ -0,0 +1,76 @@
+use serde::Serialize;
+use super::paging::encode_query_value;
+
+/// The link of the page, the template renders it with the url helper:
+/// <a href="{{url path}}/{{#if query}}?{{query}}{{/if}}">{{label}}</a>
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Link {
+    pub label: String,
+    pub path: Vec<String>,
+    pub query: String,
+}
+impl Link {
+    pub fn new<S: AsRef<str>>(label: &str, path: &[S]) -> Self {
+        Self {
+            label: String::from(label),
+            path: path.iter().map(|part| String::from(part.as_ref())).collect(),
+            query: String::new(),
+        }
+    }
+
+    /// The link to the page of the query, the parameters of the empty values are omitted
+    pub fn with_query<S: AsRef<str>>(label: &str, path: &[S], params: &[(&str, String)]) -> Self {
+        Self {
+            query: Self::make_query(params),
+            ..Self::new(label, path)
+        }
+    }
+
+    /// The absolute path made of the percent-encoded parts, the empty part is the '-' wildcard
+    pub fn make_path<S: AsRef<str>>(parts: &[S]) -> String {
+        parts.iter()
+            .map(|part| match part.as_ref() {
+                "" => String::from("-"),
+                part => encode_query_value(part),
+            })
+            .fold(String::new(), |path, part| path + "/" + &part)
+    }
+
+    /// The query string of the percent-encoded values, the empty values are omitted
+    pub fn make_query(params: &[(&str, String)]) -> String {
+        params.iter()
+            .filter(|(_, value)| !value.is_empty())
+            .map(|(name, value)| format!("{}={}", name, encode_query_value(value)))
+            .collect::<Vec<String>>()
+            .join("&")
+    }
+
+    /// The absolute URL of the link
+    pub fn get_url(&self) -> String {
+        match self.query.as_str() {
+            "" => Self::make_path(&self.path) + "/",
+            query => format!("{}/?{}", Self::make_path(&self.path), query),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_make_path() {
+        assert_eq!(Link::make_path(&["authors", "", "-", "Толстой"]), "/authors/-/-/%D0%A2%D0%BE%D0%BB%D1%81%D1%82%D0%BE%D0%B9");
+        assert_eq!(Link::make_path(&["titles", "<b>?#/'"]), "/titles/%3Cb%3E%3F%23%2F%27");
+        assert_eq!(Link::make_path(&["download", "a.zip", "1.fb2"]), "/download/a.zip/1.fb2");
+        assert_eq!(Link::make_path::<&str>(&[]), "");
+    }
+
+    #[test]
+    fn test_with_query() {
+        let link = Link::with_query("Тол", &["authors"], &[("first_name", String::new()), ("last_name", String::from("Тол & Co"))]);
+        assert_eq!(link.query, "last_name=%D0%A2%D0%BE%D0%BB%20%26%20Co");
+        assert_eq!(link.get_url(), "/authors/?last_name=%D0%A2%D0%BE%D0%BB%20%26%20Co");
+        assert_eq!(Link::with_query("Все", &["authors"], &[("last_name", String::new())]).get_url(), "/authors/");
+    }
+}
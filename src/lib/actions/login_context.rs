@@ -0,0 +1,89 @@
+use serde::{Deserialize, Serialize};
+
+use super::QueryResult;
+use super::SqliteConnection;
+use crate::models::{Save, Session, SessionRecord, UserRecord};
+
+/// The credentials sent with the request: the session cookie of the browser
+/// or the HTTP basic authorization of the OPDS client
+#[derive(Debug, Clone)]
+pub enum Credentials {
+    Anonymous,
+    Session(String),
+    Basic(String, String),
+}
+
+/// Returns the user of the credentials, the wrong credentials are the anonymous ones
+pub fn authenticate(conn: &SqliteConnection, credentials: &Credentials) -> Option<UserRecord> {
+    match credentials {
+        Credentials::Anonymous => None,
+        Credentials::Session(token) => SessionRecord::find_user(conn, token).ok(),
+        Credentials::Basic(name, password) => UserRecord::authenticate(conn, name, password),
+    }
+}
+
+/// Opens the session of the user, returns the token for the cookie or None for the wrong password
+pub fn login(conn: &SqliteConnection, name: &str, password: &str) -> QueryResult<Option<String>> {
+    match UserRecord::authenticate(conn, name, password) {
+        Some(user) => {
+            let session = Session::new(user.id);
+            SessionRecord::save(conn, &session)?;
+            Ok(Some(session.token))
+        },
+        None => Ok(None),
+    }
+}
+
+pub fn logout(conn: &SqliteConnection, token: &str) -> QueryResult<usize> {
+    SessionRecord::remove(conn, token)
+}
+
+/// The page to return after the login, only the local paths are accepted
+pub fn get_local_path(path: &str) -> String {
+    if path.starts_with('/') && !path.starts_with("//") && !path.contains('\\') {
+        String::from(path)
+    } else {
+        String::from("/")
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct LoginForm {
+    pub user_name: String,
+    pub password: String,
+    pub next: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct LoginQuery {
+    pub next: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LoginContext {
+    pub user_name: String,
+    pub next: String,
+    pub failed: bool,
+}
+impl LoginContext {
+    pub fn new(user_name: &str, next: &Option<String>, failed: bool) -> Self {
+        Self {
+            user_name: String::from(user_name),
+            next: get_local_path(next.as_ref().map(String::as_str).unwrap_or("/")),
+            failed: failed,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_get_local_path() {
+        assert_eq!(get_local_path("/shelf/"), "/shelf/");
+        assert_eq!(get_local_path("//evil.example/"), "/");
+        assert_eq!(get_local_path("/\\evil.example/"), "/");
+        assert_eq!(get_local_path("http://evil.example/"), "/");
+    }
+}
@@ -1,7 +1,10 @@
 use diesel::prelude::*;
 use diesel::r2d2::{self, ConnectionManager};
 use diesel::sql_query;
-use diesel::sql_types::Text;
+use diesel::sql_types::{Integer, Text};
+use crate::models::{ArchiveRecord, AuthorRole, DownloadFormat, Find, Id, Load, Series, SeriesRecord, ShelfRecord};
+use crate::worker::{LibraryJob, LoadStatus};
+use crate::normalize::{sort_key, from_sort_key, capitalize};
 
 pub type QueryResult<T> = std::result::Result<T, diesel::result::Error>;
 pub type ConnectionPool = r2d2::Pool<ConnectionManager<SqliteConnection>>;
@@ -9,24 +12,92 @@ pub type ConnectionPool = r2d2::Pool<ConnectionManager<SqliteConnection>>;
 pub mod author_mask;
 pub use author_mask::{AuthorMask, NvcMethods};
 pub mod author_context;
-pub use author_context::{FindAuthorContext, AuthorContext};
+pub use author_context::{AuthorEntry, FindAuthorContext, AuthorListContext, AuthorContext};
 pub mod root_context;
-pub use root_context::RootContext;
+pub use root_context::{RootContext, SearchQuery};
+pub mod link;
+pub use link::Link;
 pub mod title_context;
-pub use title_context::{TitleMask, TitleContext, FindTitleContext};
+pub use title_context::{TitleMask, TitleEntry, BookTitle, TitleContext, FindTitleContext, TitleListContext, BookVersions};
 pub mod book_record;
 pub use book_record::{BookRecord, BookStringified};
 pub mod download_context;
 pub use download_context::DownloadContext;
+pub mod isbn_context;
+pub use isbn_context::IsbnContext;
+pub mod paging;
+pub use paging::{Paging, PageQuery, SortOrder};
+pub mod recent_context;
+pub use recent_context::{RecentQuery, RecentContext, RecentBook, RecentArchive};
+pub mod feed_context;
+pub use feed_context::FeedContext;
+pub mod login_context;
+pub use login_context::{Credentials, LoginForm, LoginQuery, LoginContext};
+pub mod shelf_context;
+pub use shelf_context::{ShelfQuery, ShelfForm, ShelfBook, ShelfGroup, ShelfContext};
+pub mod sync_context;
+pub use sync_context::{SyncError, ProgressForm, ProgressReply};
+pub mod annotation_context;
+pub use annotation_context::{ReviewForm, TagForm, BookAnnotations, TagContext};
+pub mod popular_context;
+pub use popular_context::{PopularBook, PopularContext};
+pub mod admin_context;
+pub use admin_context::{AdminAccess, LoadForm, ArchiveView, LibraryStats, AdminContext};
+
+
+
+/// The readers wait for the commits of the library worker instead of failing with 'database is locked'
+#[derive(Debug)]
+struct BusyTimeout;
+impl r2d2::CustomizeConnection<SqliteConnection, r2d2::Error> for BusyTimeout {
+    fn on_acquire(&self, conn: &mut SqliteConnection) -> Result<(), r2d2::Error> {
+        use diesel::connection::SimpleConnection;
+        conn.batch_execute(&format!("PRAGMA busy_timeout = {};", crate::database::BUSY_TIMEOUT_MS))
+            .map_err(r2d2::Error::QueryError)
+    }
+}
 
-
-
-pub fn get_connection_pool() -> ConnectionPool {
-    let connspec = std::env::var("DATABASE_URL").expect("DATABASE_URL");
-    let manager = ConnectionManager::<SqliteConnection>::new(connspec);
+pub fn create_connection_pool(database: &str, size: u32) -> Result<ConnectionPool, String> {
+    let manager = ConnectionManager::<SqliteConnection>::new(database);
     r2d2::Pool::builder()
+        .max_size(size)
+        .connection_customizer(Box::new(BusyTimeout))
         .build(manager)
-        .expect("Failed to create pool.")
+        .map_err(|e| format!("Failed to create pool for {}: {}", database, e))
+}
+
+/// The LIKE pattern of the values starting with the prefix, the wildcards of the prefix
+/// are escaped by the backslash, so the condition needs ESCAPE '\'
+pub(crate) fn like_prefix(prefix: &str) -> String {
+    let mut pattern = String::new();
+    for c in sort_key(prefix).chars() {
+        if c == '\\' || c == '%' || c == '_' {
+            pattern.push('\\');
+        }
+        pattern.push(c);
+    }
+    pattern + "%"
+}
+
+/// Loads the query with the values bound to its '?' placeholders in order,
+/// the navigation conditions have at most three of them and the tag filter the fourth
+fn load_bound<T: diesel::deserialize::QueryableByName<diesel::sqlite::Sqlite>>(conn: &SqliteConnection, query: &str, values: &[String]) -> QueryResult<Vec<T>> {
+    let query = sql_query(query);
+    match values {
+        [] => query.load(conn),
+        [a] => query.bind::<Text, _>(a).load(conn),
+        [a, b] => query.bind::<Text, _>(a).bind::<Text, _>(b).load(conn),
+        [a, b, c] => query.bind::<Text, _>(a).bind::<Text, _>(b).bind::<Text, _>(c).load(conn),
+        [a, b, c, d] => query.bind::<Text, _>(a).bind::<Text, _>(b).bind::<Text, _>(c).bind::<Text, _>(d).load(conn),
+        _ => Err(diesel::result::Error::QueryBuilderError(format!("Too many values to bind: {}", values.len()).into())),
+    }
+}
+
+/// Turns the sort key prefixes, already in the alphabet order, into the navigation buttons
+fn to_buttons(prefixes: Vec<String>) -> Vec<String> {
+    let mut buttons: Vec<String> = prefixes.iter().map(|prefix| capitalize(&from_sort_key(prefix))).collect();
+    buttons.dedup();
+    buttons
 }
 
 pub fn get_next_valid_chars(conn: &SqliteConnection, table: &str, column: &str, chars: &String) -> QueryResult<Vec<String>>
@@ -36,19 +107,17 @@ pub fn get_next_valid_chars(conn: &SqliteConnection, table: &str, column: &str,
         #[sql_type = "Text"] pub content: String,
     }
     let query = format!(r#"
-        SELECT DISTINCT substr({column}, 1, {len}) AS content
+        SELECT DISTINCT substr({column}_key, 1, {len}) AS content
         FROM {table}
-        WHERE {column} LIKE '{chars}%' ORDER BY content"#,
+        WHERE {column}_key LIKE ? ESCAPE '\' ORDER BY content"#,
             table = table,
             column = column,
-            chars = chars,
-            len = 1 + chars.chars().count());
+            len = 1 + sort_key(chars).chars().count());
 
-    sql_query(&query)
-        .load::<DbString>(conn)
+    load_bound::<DbString>(conn, &query, &[like_prefix(chars)])
         .map(|list|
-            list.iter().map(|s|
-                s.content.clone()).collect()
+            to_buttons(list.iter().map(|s|
+                s.content.clone()).collect())
             )
 }
 
@@ -58,99 +127,129 @@ pub fn get_next_valid(conn: &SqliteConnection, table: &str, column: &str, mask:
     pub struct DbString {
         #[sql_type = "Text"] pub content: String,
     }
+    let (where_clause, values) = mask.get_where_like_clause();
     let query = format!(r#"
-        SELECT DISTINCT substr({column}, 1, {len}) AS content
+        SELECT DISTINCT substr({column}_key, 1, {len}) AS content
         FROM {table} {where_clause}
         ORDER BY content"#,
             table = table,
             column = column,
-            where_clause = mask.get_where_like_clause(),
+            where_clause = where_clause,
             len = mask.get_length_by_name(column) + 1
     );
 
-    sql_query(&query)
-        .load::<DbString>(conn)
+    load_bound::<DbString>(conn, &query, &values)
         .map(|list|
-            list.iter().map(|s|
-                s.content.clone()).collect()
+            to_buttons(list.iter().map(|s|
+                s.content.clone()).collect())
             )
 }
 
-pub fn get_authors(conn: &SqliteConnection, mask: &dyn NvcMethods) -> QueryResult<Vec<AuthorMask>>
+pub fn get_authors(conn: &SqliteConnection, mask: &dyn NvcMethods, paging: &mut Paging) -> QueryResult<Vec<AuthorEntry>>
 {
+    // Books are numbered in the load order
+    let order = match paging.order {
+        SortOrder::Added => "max(author_links.book_id) DESC, last_name_key, first_name_key, middle_name_key",
+        _ => "last_name_key, first_name_key, middle_name_key",
+    };
+    let (where_clause, values) = mask.get_where_like_clause();
     let query = format!(r#"
-            SELECT DISTINCT first_name, middle_name, last_name
-            FROM authors {where_clause}
-            ORDER BY last_name, first_name, middle_name"#,
-        where_clause = mask.get_where_like_clause()
+            SELECT min(authors.id) AS author_id, first_name, middle_name, last_name
+            FROM authors
+            LEFT JOIN author_links ON (author_links.author_id = authors.id)
+            {where_clause}
+            GROUP BY first_name, middle_name, last_name
+            ORDER BY {order}
+            {limit}"#,
+        where_clause = where_clause,
+        order = order,
+        limit = paging.get_limit_clause()
     );
 
-    sql_query(&query).load(conn)
+    load_bound(conn, &query, &values).map(|authors| paging.take(authors))
 }
 
-pub fn get_titles_by_authors(conn: &SqliteConnection, mask: &dyn NvcMethods) -> QueryResult<Vec<String>>
-{
-    #[derive(QueryableByName, Debug, Clone)]
-    pub struct DbString {
-        #[sql_type = "Text"] pub content: String,
+/// The canonical book of the versions, the annotations of the users belong to it
+fn canon_id_of(book_column: &str) -> String {
+    format!("ifnull((SELECT canon_id FROM documents WHERE documents.book_id = {book}), {book})", book = book_column)
+}
+
+fn and_clause(where_clause: String, condition: &str) -> String {
+    if where_clause.is_empty() {
+        format!("WHERE {}", condition)
+    } else {
+        format!("{} AND {}", where_clause, condition)
     }
+}
+
+/// The order of the grouped titles, the books without series go after the series
+fn titles_order(order: SortOrder) -> &'static str {
+    match order {
+        SortOrder::Name => "min(book_title_key)",
+        SortOrder::Added => "max(author_links.book_id) DESC, min(book_title_key)",
+        SortOrder::Serno => "min(series_name_key) IS NULL, min(series_name_key), min(serno), min(book_title_key)",
+    }
+}
+
+pub fn get_titles_by_authors(conn: &SqliteConnection, mask: &dyn NvcMethods, role: AuthorRole, paging: &mut Paging) -> QueryResult<Vec<BookTitle>>
+{
+    let (where_clause, mut values) = mask.get_where_explicit_clause();
+    let (annotation_clause, annotation_values) = paging.get_annotation_clause("author_links.book_id");
+    values.extend(annotation_values);
     let query = format!(r#"
-        SELECT DISTINCT book_title as content
+        SELECT min(author_links.book_id) AS book_id, book_title
         FROM author_links
         JOIN title_links ON (author_links.book_id = title_links.book_id)
         LEFT JOIN authors ON (author_links.author_id = authors.id)
         LEFT JOIN titles ON (title_links.title_id = titles.id)
+        LEFT JOIN series_links ON (series_links.book_id = author_links.book_id)
+        LEFT JOIN series ON (series_links.series_id = series.id)
         {where_clause}
-        ORDER BY content"#,
-        where_clause = mask.get_where_explicit_clause()
+        GROUP BY book_title
+        ORDER BY {order}
+        {limit}"#,
+        where_clause = and_clause(where_clause, &format!("role = '{}'", role.as_str())) + &annotation_clause,
+        order = titles_order(paging.order),
+        limit = paging.get_limit_clause()
     );
 
-    sql_query(&query)
-        .load::<DbString>(conn)
-        .map(|list|
-            list.iter().map(|s|
-                s.content.clone()).collect()
-            )
+    load_bound(conn, &query, &values).map(|titles| paging.take(titles))
 }
 
-pub fn get_titles_with_author(conn: &SqliteConnection, mask: &dyn NvcMethods) -> QueryResult<Vec<TitleMask>>
+pub fn get_titles_with_author(conn: &SqliteConnection, mask: &dyn NvcMethods, paging: &mut Paging) -> QueryResult<Vec<TitleEntry>>
 {
-    #[derive(QueryableByName, Debug, Clone)]
-    pub struct DbString {
-        #[sql_type = "Text"] pub content: String,
-    }
+    let (where_clause, mut values) = mask.get_where_like_clause();
+    let (annotation_clause, annotation_values) = paging.get_annotation_clause("author_links.book_id");
+    values.extend(annotation_values);
     let query = format!(r#"
-        SELECT DISTINCT book_title, last_name, first_name, middle_name
+        SELECT min(author_links.book_id) AS book_id, min(authors.id) AS author_id, book_title, last_name, first_name, middle_name
         FROM author_links
         JOIN title_links ON (author_links.book_id = title_links.book_id)
         LEFT JOIN authors ON (author_links.author_id = authors.id)
         LEFT JOIN titles ON (title_links.title_id = titles.id)
+        LEFT JOIN series_links ON (series_links.book_id = author_links.book_id)
+        LEFT JOIN series ON (series_links.series_id = series.id)
         {where_clause}
-        ORDER BY book_title, last_name, first_name, middle_name"#,
-        where_clause = mask.get_where_like_clause()
+        GROUP BY book_title, last_name, first_name, middle_name
+        ORDER BY {order}, min(last_name_key), min(first_name_key), min(middle_name_key)
+        {limit}"#,
+        where_clause = and_clause(where_clause, "role = 'author'") + &annotation_clause,
+        order = titles_order(paging.order),
+        limit = paging.get_limit_clause()
     );
 
-    sql_query(&query).load::<TitleMask>(conn)
-}
-
-pub fn urify_authors(url: &str, authors: Vec<AuthorMask>) -> Vec<String> {
-    authors.iter().map(|author|
-        format!("<a href='/{}/{}/{}/{}/'>{}</a>",
-            url,
-            AuthorMask::encode(author.first_name.clone()),
-            AuthorMask::encode(author.middle_name.clone()),
-            AuthorMask::encode(author.last_name.clone()),
-            author.get_full_name())
-        ).collect()
+    load_bound(conn, &query, &values).map(|titles| paging.take(titles))
 }
 
-pub fn get_authors_page(conn: &SqliteConnection, url: &str, mask: &AuthorMask) -> QueryResult<FindAuthorContext> {
+pub fn get_authors_page(conn: &SqliteConnection, mask: &AuthorMask, mut paging: Paging) -> QueryResult<FindAuthorContext> {
 
-    let mut ctx = FindAuthorContext::new(url, mask);
+    let mut ctx = FindAuthorContext::new(mask);
+    paging.set_filter(&mask.get_params());
     if !mask.is_empty()
     {
-        ctx.authors = urify_authors("author", get_authors(conn, mask)?);
+        ctx.authors = get_authors(conn, mask, &mut paging)?.iter().map(AuthorEntry::get_link).collect();
     }
+    ctx.paging = paging;
 
     ctx.load_first_name_nvc(get_next_valid(conn, "authors", "first_name", mask)?);
     ctx.load_middle_name_nvc(get_next_valid(conn, "authors", "middle_name", mask)?);
@@ -159,81 +258,307 @@ pub fn get_authors_page(conn: &SqliteConnection, url: &str, mask: &AuthorMask) -
     return Ok(ctx);
 }
 
-pub fn urify_titles_by_authors(url: &str, masks: &Vec<TitleMask>) -> Vec<String> {
-    masks.iter().map(|mask|
-        format!("<a href='/{}/{}/{}/{}/{}/'>{} ({} {} {})</a>",
-            url,
-            AuthorMask::encode(mask.first_name.clone()),
-            AuthorMask::encode(mask.middle_name.clone()),
-            AuthorMask::encode(mask.last_name.clone()),
-            mask.book_title,
-            mask.book_title,
-            mask.last_name, mask.first_name, mask.middle_name
-        )
-    ).collect()
-}
-
-pub fn load_titles_page(conn: &SqliteConnection, url: &str, mask: &TitleMask) -> QueryResult<FindTitleContext> {
+pub fn load_titles_page(conn: &SqliteConnection, url: &str, mask: &TitleMask, mut paging: Paging) -> QueryResult<FindTitleContext> {
 
     let mut ctx = FindTitleContext::new(url, &mask);
-    ctx.title_and_author = get_titles_with_author(conn, mask)?;
     if !mask.is_empty()
     {
-        ctx.titles = urify_titles_by_authors("title", &ctx.title_and_author);
+        ctx.title_and_author = get_titles_with_author(conn, mask, &mut paging)?;
+        ctx.titles = ctx.title_and_author.iter().map(TitleEntry::get_link).collect();
     }
+    ctx.paging = paging;
     ctx.load_title_nvc(get_next_valid(conn, "titles", "book_title", mask)?);
     return Ok(ctx);
 }
 
-pub fn urify_titles(url: &str, author: &AuthorMask, titles: Vec<String>) -> Vec<String> {
-    titles.iter().map(|title|
-        format!("<a href='/{url}/{author}/{title}/'>{title}</a>",
-            url = url,
-            author = author.get_uri(),
-            title = title)
-        ).collect()
+/// The page of the authors for the API and the OPDS catalog, the links to the next pages keep the mask
+pub fn load_authors_list(conn: &SqliteConnection, mask: &AuthorMask, mut paging: Paging) -> QueryResult<AuthorListContext> {
+
+    paging.set_filter(&mask.get_params());
+    let authors = get_authors(conn, mask, &mut paging)?;
+    let mut ctx = AuthorListContext::new(mask, paging);
+    ctx.authors = authors;
+    return Ok(ctx);
+}
+
+/// The page of the titles for the API and the OPDS catalog, the links to the next pages keep the mask
+pub fn load_titles_list(conn: &SqliteConnection, mask: &TitleMask, mut paging: Paging) -> QueryResult<TitleListContext> {
+
+    paging.set_filter(&[("title", mask.book_title.clone())]);
+    let titles = get_titles_with_author(conn, mask, &mut paging)?;
+    let mut ctx = TitleListContext::new(mask, paging);
+    ctx.titles = titles;
+    return Ok(ctx);
 }
 
-pub fn get_author_ctx(conn: &SqliteConnection, url: &str, author: &AuthorMask) -> QueryResult<AuthorContext> {
+/// The written and the translated titles of the author are paged separately
+pub fn load_author_ctx(conn: &SqliteConnection, id: Id, mut paging: Paging, mut translated: Paging) -> QueryResult<AuthorContext> {
 
-    let mut ctx = AuthorContext::new(url, author);
-    ctx.titles = urify_titles("title", author, get_titles_by_authors(conn, author)?);
+    let author = AuthorEntry::load(conn, id)?;
+    let mask = author.get_mask();
+    let mut ctx = AuthorContext::new(&author);
+    ctx.titles = get_titles_by_authors(conn, &mask, AuthorRole::Author, &mut paging)?.iter().map(BookTitle::get_link).collect();
+    ctx.translated = get_titles_by_authors(conn, &mask, AuthorRole::Translator, &mut translated)?.iter().map(BookTitle::get_link).collect();
+    ctx.paging = paging;
+    ctx.translated_paging = translated;
+    ctx.series = get_series_by_authors(conn, &mask)?;
 
     return Ok(ctx);
 }
 
+/// The canonical URL of the author page found by the names of the legacy URL
+pub fn find_author_url(conn: &SqliteConnection, author: &AuthorMask) -> QueryResult<String> {
+    AuthorEntry::find(conn, author).map(|author| author.get_url())
+}
+
 pub fn get_root_page(conn: &SqliteConnection) -> QueryResult<RootContext> {
 
     let empty = String::new();
     let mut ctx = RootContext::new();
     ctx.first_name_nvc = get_next_valid_chars(conn, "authors", "first_name", &empty)?
         .iter()
-        .map(|value| format!("<a href='/authors/-/-/{}/'>{}</a>", AuthorMask::encode(value.clone()),value)).collect();
+        .map(|value| AuthorMask::default().with_name("first_name", value).get_link(value)).collect();
 
     ctx.middle_name_nvc = get_next_valid_chars(conn, "authors", "middle_name", &empty)?
         .iter()
-        .map(|value| format!("<a href='/authors/-/{}/-/'>{}</a>", AuthorMask::encode(value.clone()),value)).collect();
+        .map(|value| AuthorMask::default().with_name("middle_name", value).get_link(value)).collect();
 
     ctx.last_name_nvc = get_next_valid_chars(conn, "authors", "last_name", &empty)?
         .iter()
-        .map(|value| format!("<a href='/authors/-/-/{}/'>{}</a>", AuthorMask::encode(value.clone()),value)).collect();
+        .map(|value| AuthorMask::default().with_name("last_name", value).get_link(value)).collect();
 
     ctx.book_title_nvc = get_next_valid_chars(conn, "titles", "book_title", &empty)?
         .iter()
-        .map(|value| format!("<a href='/titles/{}/'>{}</a>", AuthorMask::encode(value.clone()),value)).collect();
+        .map(|value| Link::new(value, &["titles", value])).collect();
+
+    return Ok(ctx);
+}
+
+/// The versions of the title of the book by its first author
+pub fn load_book_ctx(conn: &SqliteConnection, book: Id, credentials: &Credentials)-> QueryResult<TitleContext> {
+
+    let entry = TitleEntry::load_by_book(conn, book)?;
+    let mut ctx = TitleContext::new(&entry);
+    ctx.load_books(BookStringified::transform(BookRecord::load_by_author_and_title(conn, entry.author_id, &entry.book_title)?));
+    let user = login_context::authenticate(conn, credentials);
+    if let Some(ref user) = user {
+        ctx.load_shelves(user, ShelfRecord::load_for_user(conn, user.id)?);
+    }
+    ctx.load_annotations(conn, user.map(|user| user.id))?;
+    return Ok(ctx);
+}
+
+/// The canonical URL of the book page found by the names of the legacy URL
+pub fn find_book_url(conn: &SqliteConnection, author: &AuthorMask, title: &String) -> QueryResult<String> {
+    let author = AuthorEntry::find(conn, author)?;
+    let books = BookRecord::load_by_author_and_title(conn, author.author_id, title)?;
+    let book = books.iter().map(|book| book.book_id).min().ok_or(diesel::result::Error::NotFound)?;
+    TitleEntry::load_by_book(conn, book).map(|entry| entry.get_url())
+}
 
+pub fn load_isbn_ctx(conn: &SqliteConnection, isbn: &String)-> QueryResult<IsbnContext> {
+
+    let mut ctx = IsbnContext::new(isbn.clone());
+    if let Some(key) = crate::models::Isbn::make_key(isbn) {
+        ctx.titles = BookRecord::load_by_isbn(conn, &key)?.iter().map(TitleEntry::get_link).collect();
+    }
+    return Ok(ctx);
+}
+
+pub fn load_recent_ctx(conn: &SqliteConnection, query: &RecentQuery, paging: Paging)-> QueryResult<RecentContext> {
+
+    let mut ctx = RecentContext::new(query, paging);
+    ctx.paging.set_filter(&[("days", ctx.days.to_string()), ("genre", ctx.genre.clone())]);
+    ctx.archives = RecentArchive::load(conn, &ctx.since)?;
+    ctx.genres = RecentBook::load_genres(conn, &ctx.since)?;
+    ctx.books = RecentBook::load(conn, &ctx.since, &ctx.genre, &mut ctx.paging)?;
     return Ok(ctx);
 }
 
-pub fn load_author_title_ctx(conn: &SqliteConnection, author: &AuthorMask, title: &String)-> QueryResult<TitleContext> {
+pub fn load_popular_ctx(conn: &SqliteConnection, query: &RecentQuery, paging: Paging)-> QueryResult<PopularContext> {
 
-    let mut ctx = TitleContext::new(author, title.clone());
-    ctx.books = BookStringified::transform(BookRecord::load_by_author_and_title(conn, author, &title)?);
+    let mut ctx = PopularContext::new(query, paging);
+    ctx.paging.set_filter(&[("days", ctx.days.to_string()), ("genre", ctx.genre.clone())]);
+    let books = RecentBook::load_popular(conn, &ctx.since, &ctx.genre, &mut ctx.paging)?;
+    ctx.load_books(conn, books)?;
     return Ok(ctx);
 }
 
-pub fn load_download_ctx(conn: &SqliteConnection, workdir: String, archive: &String, book: &String)-> QueryResult<DownloadContext> {
+pub fn load_author_feed(conn: &SqliteConnection, id: Id) -> QueryResult<FeedContext> {
+
+    let author = AuthorEntry::load(conn, id)?;
+    let mask = author.get_mask();
+    let mut ctx = FeedContext::new(
+        format!("author/{}", author.author_id),
+        format!("Новые книги: {}", author.get_full_name().trim()),
+        String::from(author.get_full_name().trim()),
+        format!("/atom/author/{}/", author.author_id),
+        author.get_url());
+    ctx.load_books(RecentBook::load_for_author(conn, &mask, FeedContext::FEED_SIZE)?);
+    return Ok(ctx);
+}
 
-    let record = BookRecord::load_by_archive_and_book(conn, archive, book)?;
+pub fn load_series_feed(conn: &SqliteConnection, id: Id) -> QueryResult<FeedContext> {
+
+    let series = SeriesRecord::load(conn, id)?;
+    let mut ctx = FeedContext::new(
+        format!("series/{}", series.id),
+        format!("Новые книги серии: {}", series.series_name),
+        String::from(FeedContext::LIBRARY_AUTHOR),
+        format!("/atom/series/{}/", series.id),
+        String::from("/"));
+    ctx.load_books(RecentBook::load_for_series(conn, series.id, FeedContext::FEED_SIZE)?);
+    return Ok(ctx);
+}
+
+/// The series of the legacy feed URL keyed by the name
+pub fn find_series_id(conn: &SqliteConnection, name: &str) -> QueryResult<Id> {
+    SeriesRecord::find(conn, &Series { series_name: String::from(name) })
+}
+
+/// The series of the author linked to their feeds
+pub fn get_series_by_authors(conn: &SqliteConnection, mask: &dyn NvcMethods) -> QueryResult<Vec<Link>>
+{
+    #[derive(QueryableByName, Debug, Clone)]
+    pub struct DbSeries {
+        #[sql_type = "Integer"] pub series_id: Id,
+        #[sql_type = "Text"] pub series_name: String,
+    }
+    let (where_clause, values) = mask.get_where_explicit_clause();
+    let query = format!(r#"
+        SELECT DISTINCT series.id AS series_id, series_name
+        FROM author_links
+        JOIN series_links ON (author_links.book_id = series_links.book_id)
+        LEFT JOIN authors ON (author_links.author_id = authors.id)
+        LEFT JOIN series ON (series_links.series_id = series.id)
+        {where_clause}
+        ORDER BY series_name_key"#,
+        where_clause = and_clause(where_clause, "role = 'author'")
+    );
+
+    load_bound::<DbSeries>(conn, &query, &values)
+        .map(|list|
+            list.iter().map(|s|
+                Link::new(&s.series_name, &["atom", "series", &s.series_id.to_string()])).collect()
+            )
+}
+
+/// Returns None for the anonymous user
+pub fn load_shelf_ctx(conn: &SqliteConnection, credentials: &Credentials, query: &ShelfQuery)-> QueryResult<Option<ShelfContext>> {
+
+    match login_context::authenticate(conn, credentials) {
+        Some(user) => {
+            let mut ctx = ShelfContext::new(&user, query);
+            ctx.load_shelves(conn, user.id)?;
+            Ok(Some(ctx))
+        },
+        None => Ok(None),
+    }
+}
+
+/// Returns false for the anonymous user
+pub fn update_shelf(conn: &SqliteConnection, credentials: &Credentials, book: Id, form: &ShelfForm)-> QueryResult<bool> {
+
+    match login_context::authenticate(conn, credentials) {
+        Some(user) => shelf_context::update_shelf(conn, user.id, book, form).map(|_| true),
+        None => Ok(false),
+    }
+}
+
+/// Returns false for the anonymous user
+pub fn update_review(conn: &SqliteConnection, credentials: &Credentials, book: Id, form: &ReviewForm)-> QueryResult<bool> {
+
+    match login_context::authenticate(conn, credentials) {
+        Some(user) => annotation_context::update_review(conn, user.id, book, form).map(|_| true),
+        None => Ok(false),
+    }
+}
+
+/// Returns false for the anonymous user
+pub fn update_tags(conn: &SqliteConnection, credentials: &Credentials, book: Id, form: &TagForm)-> QueryResult<bool> {
+
+    match login_context::authenticate(conn, credentials) {
+        Some(user) => annotation_context::update_tags(conn, user.id, book, form).map(|_| true),
+        None => Ok(false),
+    }
+}
+
+pub fn load_annotations(conn: &SqliteConnection, book: Id)-> QueryResult<BookAnnotations> {
+
+    BookAnnotations::load(conn, book, None)
+}
+
+/// The empty tag lists the all tags
+pub fn load_tag_ctx(conn: &SqliteConnection, tag: &str, paging: Paging)-> QueryResult<TagContext> {
+
+    let mut ctx = TagContext::new(tag, paging);
+    if ctx.tag.is_empty() {
+        ctx.tags = TagContext::load_tags(conn)?;
+    } else {
+        ctx.books = RecentBook::load(conn, "", "", &mut ctx.paging)?;
+    }
+    return Ok(ctx);
+}
+
+/// Logs the served file and remembers its KOReader hash for the progress sync
+pub fn record_download(conn: &SqliteConnection, credentials: &Credentials, book: Id, document: String, format: DownloadFormat)-> QueryResult<()> {
+
+    let user = login_context::authenticate(conn, credentials).map(|user| user.id);
+    popular_context::log_download(conn, book, user, format)?;
+    sync_context::save_book_digest(conn, book, document)?;
+    Ok(())
+}
+
+pub fn load_download_ctx(conn: &SqliteConnection, workdir: String, book: Id)-> QueryResult<DownloadContext> {
+
+    let record = BookRecord::load_by_id(conn, book)?;
     return Ok(DownloadContext::new(&workdir, record));
-}
\ No newline at end of file
+}
+
+/// The id of the book found by the names of the legacy download URL
+pub fn find_download_id(conn: &SqliteConnection, archive: &String, book: &String)-> QueryResult<Id> {
+    BookRecord::load_by_archive_and_book(conn, archive, book).map(|record| record.book_id)
+}
+
+/// The admin pages are allowed to the users listed in the config only
+pub fn check_admin(conn: &SqliteConnection, credentials: &Credentials, admins: &[String]) -> QueryResult<AdminAccess> {
+    Ok(match login_context::authenticate(conn, credentials) {
+        Some(user) if admins.contains(&user.user_name) => AdminAccess::Granted(user.user_name),
+        Some(_) => AdminAccess::Denied,
+        None => AdminAccess::Anonymous,
+    })
+}
+
+pub fn load_admin_ctx(conn: &SqliteConnection, user_name: &str, library: &str, database: &str, status: LoadStatus) -> QueryResult<AdminContext> {
+    let mut ctx = AdminContext::new(user_name, library, database, LibraryStats::load(conn)?, status);
+    ctx.load_archives(conn)?;
+    Ok(ctx)
+}
+
+/// The loads of the requested new archive or of the all new archives of the library
+pub fn find_load_jobs(conn: &SqliteConnection, library: &str, form: &LoadForm) -> QueryResult<Vec<LibraryJob>> {
+    let archives = ArchiveView::load_all(conn)?;
+    let library = std::path::Path::new(library);
+    let sources = if form.path.is_empty() {
+        admin_context::find_new_sources(&archives, library)
+    } else {
+        admin_context::resolve_source(&archives, library, &form.path).into_iter().collect()
+    };
+    Ok(sources.into_iter().map(LibraryJob::Load).collect())
+}
+
+pub fn find_remove_job(conn: &SqliteConnection, archive: Id) -> QueryResult<LibraryJob> {
+    ArchiveRecord::load(conn, archive).map(|record| LibraryJob::Remove(record.id, record.arch_name))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_like_prefix() {
+        assert_eq!(like_prefix("Тол"), sort_key("тол") + "%");
+        assert_eq!(like_prefix("100%_a\\b"), "100\\%\\_a\\\\b%");
+        assert_eq!(like_prefix("O'Brien"), "o'brien%");
+    }
+}
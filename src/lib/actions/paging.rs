@@ -0,0 +1,216 @@
+use serde::{Deserialize, Serialize};
+
+pub(crate) fn encode_query_value(value: &str) -> String {
+    value.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => (b as char).to_string(),
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SortOrder {
+    Name,
+    Added,
+    Serno,
+}
+impl SortOrder {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "name" => Some(SortOrder::Name),
+            "added" => Some(SortOrder::Added),
+            "serno" => Some(SortOrder::Serno),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SortOrder::Name => "name",
+            SortOrder::Added => "added",
+            SortOrder::Serno => "serno",
+        }
+    }
+}
+
+/// The query string of the listings: ?page=2&size=50&sort=added&tag=classic&rating=4
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct PageQuery {
+    pub page: Option<usize>,
+    pub size: Option<usize>,
+    pub sort: Option<String>,
+    pub tag: Option<String>,
+    pub rating: Option<i32>,
+    /// The page of the second listing: the translations of the author page
+    pub translated: Option<usize>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Paging {
+    pub page: usize,
+    pub size: usize,
+    pub sort: String,
+    pub tag: String,
+    pub rating: i32,
+    pub previous: Option<String>,
+    pub next: Option<String>,
+    #[serde(skip)]
+    pub order: SortOrder,
+    #[serde(skip)]
+    pub filter: String,
+    #[serde(skip)]
+    pub param: &'static str,
+}
+impl Paging {
+    pub const DEFAULT_SIZE: usize = 100;
+    pub const MAX_SIZE: usize = 1000;
+
+    pub fn new(query: &PageQuery) -> Self {
+        Self::with_order(query, SortOrder::Name)
+    }
+
+    /// Uses the order when the query has no valid sort
+    pub fn with_order(query: &PageQuery, order: SortOrder) -> Self {
+        let order = query.sort.as_ref().and_then(|sort| SortOrder::parse(sort)).unwrap_or(order);
+        let page = query.page.unwrap_or(1).max(1);
+        Self {
+            page: page,
+            size: query.size.unwrap_or(Self::DEFAULT_SIZE).max(1).min(Self::MAX_SIZE),
+            sort: String::from(order.as_str()),
+            tag: query.tag.as_ref().map(|tag| tag.trim().to_string()).unwrap_or_default(),
+            rating: query.rating.unwrap_or(0).max(0).min(5),
+            previous: None,
+            next: None,
+            order: order,
+            filter: String::new(),
+            param: "page",
+        }
+    }
+
+    /// The paging of the second listing of the page, its page is passed by the parameter,
+    /// the links of both listings keep the page of the other one
+    pub fn with_second(&mut self, page: Option<usize>, param: &'static str) -> Self {
+        let mut second = self.clone();
+        second.page = page.unwrap_or(1).max(1);
+        second.param = param;
+        second.filter += &format!("&{}={}", self.param, self.page);
+        self.filter += &format!("&{}={}", param, second.page);
+        second
+    }
+
+    /// Keeps the listing filter (name=value pairs) in the links to the neighbour pages
+    pub fn set_filter(&mut self, filter: &[(&str, String)]) {
+        self.filter = filter.iter()
+            .filter(|(_, value)| !value.is_empty())
+            .map(|(name, value)| format!("&{}={}", name, encode_query_value(value)))
+            .collect();
+    }
+
+    /// Requests one extra row to know whether the next page exists
+    pub fn get_limit_clause(&self) -> String {
+        let offset = (self.page.max(1) - 1).saturating_mul(self.size).min(i64::MAX as usize);
+        format!("LIMIT {} OFFSET {}", self.size + 1, offset)
+    }
+
+    /// Keeps only the books tagged with the tag and rated not less than the rating by the users,
+    /// the annotations belong to the canonical book of the column. The tag is bound to the '?' placeholder.
+    pub fn get_annotation_clause(&self, book_column: &str) -> (String, Vec<String>) {
+        let mut clause = String::new();
+        let mut values = Vec::new();
+        if !self.tag.is_empty() {
+            clause += &format!(
+                " AND {book} IN (SELECT book_id FROM tag_links JOIN tags ON (tags.id = tag_links.tag_id) WHERE tag_name = ?)",
+                book = super::canon_id_of(book_column));
+            values.push(self.tag.clone());
+        }
+        if self.rating > 0 {
+            clause += &format!(
+                " AND {book} IN (SELECT book_id FROM reviews WHERE rating > 0 GROUP BY book_id HAVING avg(rating) >= {rating})",
+                book = super::canon_id_of(book_column),
+                rating = self.rating);
+        }
+        (clause, values)
+    }
+
+    pub fn get_query(&self, page: usize) -> String {
+        let mut query = format!("{}={}&size={}&sort={}", self.param, page, self.size, self.sort);
+        if !self.tag.is_empty() {
+            query += &format!("&tag={}", encode_query_value(&self.tag));
+        }
+        if self.rating > 0 {
+            query += &format!("&rating={}", self.rating);
+        }
+        query + &self.filter
+    }
+
+    /// Cuts the extra row loaded by the limit clause and fills the links to the neighbour pages
+    pub fn take<T>(&mut self, mut items: Vec<T>) -> Vec<T> {
+        if self.page > 1 {
+            self.previous = Some(self.get_query(self.page - 1));
+        }
+        if items.len() > self.size {
+            items.truncate(self.size);
+            self.next = Some(self.get_query(self.page.saturating_add(1)));
+        }
+        items
+    }
+}
+impl Default for Paging {
+    fn default() -> Self {
+        Self::new(&PageQuery::default())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_take() {
+        let mut paging = Paging::new(&PageQuery { page: Some(2), size: Some(2), sort: Some(String::from("added")), ..Default::default() });
+        assert_eq!(paging.get_limit_clause(), "LIMIT 3 OFFSET 2");
+        assert_eq!(paging.take(vec![1, 2, 3]), vec![1, 2]);
+        assert_eq!(paging.previous, Some(String::from("page=1&size=2&sort=added")));
+        assert_eq!(paging.next, Some(String::from("page=3&size=2&sort=added")));
+
+        let mut paging = Paging::new(&PageQuery { page: Some(0), size: Some(5000), sort: Some(String::from("bad")), ..Default::default() });
+        assert_eq!(paging.take(vec![1]), vec![1]);
+        assert_eq!((paging.page, paging.size, paging.order), (1, Paging::MAX_SIZE, SortOrder::Name));
+        assert_eq!((paging.previous, paging.next), (None, None));
+
+        let mut paging = Paging::with_order(&PageQuery::default(), SortOrder::Added);
+        paging.set_filter(&[("days", String::from("7")), ("genre", String::from("sf & fantasy")), ("empty", String::new())]);
+        assert_eq!(paging.get_query(1), "page=1&size=100&sort=added&days=7&genre=sf%20%26%20fantasy");
+
+        let mut paging = Paging::new(&PageQuery { page: Some(usize::MAX), size: Some(Paging::MAX_SIZE), ..Default::default() });
+        assert_eq!(paging.get_limit_clause(), format!("LIMIT {} OFFSET {}", Paging::MAX_SIZE + 1, i64::MAX));
+        paging.page = 0;
+        assert_eq!(paging.get_limit_clause(), format!("LIMIT {} OFFSET 0", Paging::MAX_SIZE + 1));
+    }
+
+    #[test]
+    fn test_with_second() {
+        let mut paging = Paging::new(&PageQuery { page: Some(2), size: Some(1), translated: Some(3), ..Default::default() });
+        let mut translated = paging.with_second(Some(3), "translated");
+        assert_eq!(translated.get_limit_clause(), "LIMIT 2 OFFSET 2");
+        assert_eq!(paging.take(vec![1, 2]), vec![1]);
+        assert_eq!(translated.take(vec![1]), vec![1]);
+        assert_eq!(paging.next, Some(String::from("page=3&size=1&sort=name&translated=3")));
+        assert_eq!(translated.previous, Some(String::from("translated=2&size=1&sort=name&page=2")));
+        assert_eq!(translated.next, None);
+    }
+
+    #[test]
+    fn test_annotation_filter() {
+        let paging = Paging::default();
+        assert_eq!(paging.get_annotation_clause("books.id"), (String::new(), Vec::new()));
+
+        let paging = Paging::new(&PageQuery { tag: Some(String::from(" it's ")), rating: Some(9), ..Default::default() });
+        assert_eq!(paging.get_query(2), "page=2&size=100&sort=name&tag=it%27s&rating=5");
+        let (clause, values) = paging.get_annotation_clause("books.id");
+        assert!(clause.contains("tag_name = ?"));
+        assert_eq!(values, vec![String::from("it's")]);
+        assert!(clause.contains("HAVING avg(rating) >= 5"));
+    }
+}
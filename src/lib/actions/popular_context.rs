@@ -0,0 +1,78 @@
+use diesel::sql_query;
+use diesel::sql_types::{BigInt, Integer};
+use diesel::RunQueryDsl;
+use serde::Serialize;
+
+use super::QueryResult;
+use super::SqliteConnection;
+use super::paging::Paging;
+use super::recent_context::{RecentBook, RecentQuery};
+use crate::models::{Download, DownloadFormat, DownloadRecord, Id, Save};
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PopularBook {
+    #[serde(flatten)]
+    pub book: RecentBook,
+    pub downloads: i64,
+}
+
+/// The most downloaded books of the period: ?days=30&genre=sf_fantasy
+#[derive(Debug, Clone, Serialize)]
+pub struct PopularContext {
+    pub days: i64,
+    pub genre: String,
+    pub since: String,
+    pub updated: String,
+    pub books: Vec<PopularBook>,
+    pub paging: Paging,
+}
+impl PopularContext {
+    pub const DEFAULT_DAYS: i64 = 30;
+    pub const MAX_DAYS: i64 = 3650;
+
+    pub fn new(query: &RecentQuery, paging: Paging) -> Self {
+        let now = chrono::Utc::now();
+        let days = query.days.unwrap_or(Self::DEFAULT_DAYS).max(1).min(Self::MAX_DAYS);
+        Self {
+            days: days,
+            genre: query.genre.clone().unwrap_or_default(),
+            since: (now - chrono::Duration::days(days)).format("%Y-%m-%d %H:%M:%S").to_string(),
+            updated: now.format("%Y-%m-%dT%H:%M:%SZ").to_string(),
+            books: Vec::new(),
+            paging: paging,
+        }
+    }
+
+    /// Adds the download counters of the period to the books
+    pub fn load_books(&mut self, conn: &SqliteConnection, books: Vec<RecentBook>) -> QueryResult<()> {
+        #[derive(QueryableByName, Debug, Clone)]
+        struct DownloadCount {
+            #[sql_type = "Integer"] book_id: i32,
+            #[sql_type = "BigInt"] downloads: i64,
+        }
+        let ids: Vec<String> = books.iter().map(|book| book.book_id.to_string()).collect();
+        let counts: Vec<DownloadCount> = sql_query(format!(
+            r#"
+            SELECT book_id, count(*) AS downloads
+            FROM downloads
+            WHERE download_time >= '{since}' AND book_id IN ({ids})
+            GROUP BY book_id
+            "#,
+            since = self.since,
+            ids = ids.join(", ")
+        )).load(conn)?;
+
+        self.books = books.into_iter().map(|book| {
+            let downloads = counts.iter()
+                .find(|count| count.book_id == book.book_id)
+                .map(|count| count.downloads)
+                .unwrap_or(0);
+            PopularBook { book, downloads }
+        }).collect();
+        Ok(())
+    }
+}
+
+pub fn log_download(conn: &SqliteConnection, book: Id, user: Option<Id>, format: DownloadFormat) -> QueryResult<usize> {
+    DownloadRecord::save(conn, &Download::new(book, user, format))
+}
@@ -0,0 +1,286 @@
+use diesel::sql_query;
+use diesel::sql_types::{BigInt, Double, Integer, Text};
+use diesel::RunQueryDsl;
+use serde::{Deserialize, Serialize};
+
+use super::QueryResult;
+use super::SqliteConnection;
+use super::paging::{Paging, SortOrder};
+use super::author_mask::NvcMethods;
+use crate::models::{Id, ShelfKind};
+
+/// The query string of the new arrivals: ?days=7&genre=sf_fantasy
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct RecentQuery {
+    pub days: Option<i64>,
+    pub genre: Option<String>,
+}
+
+/// Converts the stored load time 'YYYY-MM-DD HH:MM:SS' (UTC) to the RFC 3339 used by Atom
+fn to_rfc3339(loaded: &str) -> String {
+    if loaded.is_empty() {
+        String::new()
+    } else {
+        format!("{}Z", loaded.replacen(' ', "T", 1))
+    }
+}
+
+fn split_list(value: &str) -> Vec<String> {
+    value.split(':')
+        .filter(|item| !item.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+#[derive(QueryableByName, Debug, Clone)]
+struct RecentRow {
+    #[sql_type = "Integer"] book_id: i32,
+    #[sql_type = "Text"] book_title: String,
+    #[sql_type = "Text"] book_file: String,
+    #[sql_type = "BigInt"] book_size: i64,
+    #[sql_type = "Text"] book_loaded: String,
+    #[sql_type = "Text"] arch_name: String,
+    #[sql_type = "Text"] authors: String,
+    #[sql_type = "Text"] genres: String,
+    #[sql_type = "Text"] series: String,
+    #[sql_type = "Integer"] serno: i32,
+    #[sql_type = "Double"] rating: f64,
+    #[sql_type = "Text"] tags: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RecentBook {
+    pub book_id: i32,
+    pub book_title: String,
+    pub book_file: String,
+    pub book_size: i64,
+    pub book_loaded: String,
+    pub updated: String,
+    pub arch_name: String,
+    pub authors: Vec<String>,
+    pub genres: Vec<String>,
+    pub series: String,
+    pub serno: i32,
+    pub rating: f64,
+    pub tags: Vec<String>,
+}
+impl From<RecentRow> for RecentBook {
+    fn from(row: RecentRow) -> Self {
+        Self {
+            book_id: row.book_id,
+            book_title: row.book_title,
+            book_file: row.book_file,
+            book_size: row.book_size,
+            updated: to_rfc3339(&row.book_loaded),
+            book_loaded: row.book_loaded,
+            arch_name: row.arch_name,
+            authors: split_list(&row.authors),
+            genres: split_list(&row.genres),
+            series: row.series,
+            serno: row.serno,
+            rating: row.rating,
+            tags: split_list(&row.tags),
+        }
+    }
+}
+impl RecentBook {
+    /// The condition with the '?' placeholders and the values bound to them in order
+    fn load_rows(conn: &SqliteConnection, condition: &str, values: &[String], order: &str, limit: &str) -> QueryResult<Vec<RecentRow>> {
+        let query = format!(
+            r#"
+            SELECT
+                books.id AS book_id,
+                ifnull((SELECT book_title FROM titles_view WHERE titles_view.book_id = books.id), '') AS book_title,
+                book_file,
+                book_size,
+                book_loaded,
+                arch_name,
+                ifnull((SELECT group_concat(trim(last_name || ' ' || first_name || ' ' || middle_name), ':')
+                        FROM authors_view WHERE authors_view.book_id = books.id), '') AS authors,
+                ifnull((SELECT group_concat(genre_name, ':')
+                        FROM genres_view WHERE genres_view.book_id = books.id), '') AS genres,
+                ifnull((SELECT series_name FROM series_links JOIN series ON (series.id = series_links.series_id)
+                        WHERE series_links.book_id = books.id ORDER BY series_links.id LIMIT 1), '') AS series,
+                ifnull((SELECT serno FROM series_links
+                        WHERE series_links.book_id = books.id ORDER BY series_links.id LIMIT 1), 0) AS serno,
+                ifnull((SELECT round(avg(rating), 1) FROM reviews
+                        WHERE rating > 0 AND reviews.book_id = {canon_id}), 0.0) AS rating,
+                ifnull((SELECT group_concat(tag_name, ':') FROM tags
+                        WHERE tags.id IN (SELECT tag_id FROM tag_links WHERE tag_links.book_id = {canon_id})), '') AS tags
+            FROM books
+            JOIN archives ON (books.arch_id = archives.id)
+            WHERE {condition}
+            ORDER BY {order}
+            {limit}
+            "#,
+            canon_id = super::canon_id_of("books.id"),
+            condition = condition,
+            order = order,
+            limit = limit
+        );
+        super::load_bound(conn, &query, values)
+    }
+
+    /// The books of the genre and of the annotations of the paging, with the values to bind
+    fn get_filter_clause(genre: &str, paging: &Paging) -> (String, Vec<String>) {
+        let (mut clause, mut values) = if genre.is_empty() {
+            (String::new(), Vec::new())
+        } else {
+            (String::from(" AND books.id IN (SELECT book_id FROM genres_view WHERE genre_name = ?)"), vec![String::from(genre)])
+        };
+        let (annotation_clause, annotation_values) = paging.get_annotation_clause("books.id");
+        clause += &annotation_clause;
+        values.extend(annotation_values);
+        (clause, values)
+    }
+
+    pub fn load(conn: &SqliteConnection, since: &str, genre: &str, paging: &mut Paging) -> QueryResult<Vec<Self>> {
+        let (filter, values) = Self::get_filter_clause(genre, paging);
+        let condition = format!("book_loaded >= '{}'{}", since, filter);
+        let order = match paging.order {
+            SortOrder::Name => "book_title, books.id DESC",
+            _ => "book_loaded DESC, books.id DESC",
+        };
+        let rows = Self::load_rows(conn, &condition, &values, order, &paging.get_limit_clause())?;
+        Ok(paging.take(rows).into_iter().map(RecentBook::from).collect())
+    }
+
+    /// The books downloaded since the time, the most downloaded first
+    pub fn load_popular(conn: &SqliteConnection, since: &str, genre: &str, paging: &mut Paging) -> QueryResult<Vec<Self>> {
+        let (filter, values) = Self::get_filter_clause(genre, paging);
+        let condition = format!("books.id IN (SELECT book_id FROM downloads WHERE download_time >= '{}'){}", since, filter);
+        let order = format!(
+            "(SELECT count(*) FROM downloads WHERE downloads.book_id = books.id AND download_time >= '{since}') DESC, books.id DESC",
+            since = since
+        );
+        let rows = Self::load_rows(conn, &condition, &values, &order, &paging.get_limit_clause())?;
+        Ok(paging.take(rows).into_iter().map(RecentBook::from).collect())
+    }
+
+    /// The latest loaded books written by the author
+    pub fn load_for_author(conn: &SqliteConnection, author: &dyn NvcMethods, limit: usize) -> QueryResult<Vec<Self>> {
+        let (where_clause, values) = author.get_where_explicit_clause();
+        let condition = format!(
+            r#"book_loaded <> '' AND books.id IN (
+                SELECT author_links.book_id
+                FROM author_links
+                JOIN authors ON (author_links.author_id = authors.id)
+                {where_clause})"#,
+            where_clause = super::and_clause(where_clause, "role = 'author'")
+        );
+        let rows = Self::load_rows(conn, &condition, &values, "book_loaded DESC, books.id DESC", &format!("LIMIT {}", limit))?;
+        Ok(rows.into_iter().map(RecentBook::from).collect())
+    }
+
+    /// The latest loaded books of the series
+    pub fn load_for_series(conn: &SqliteConnection, series: Id, limit: usize) -> QueryResult<Vec<Self>> {
+        let condition = format!(
+            "book_loaded <> '' AND books.id IN (SELECT book_id FROM series_links WHERE series_id = {series})",
+            series = series
+        );
+        let rows = Self::load_rows(conn, &condition, &[], "book_loaded DESC, books.id DESC", &format!("LIMIT {}", limit))?;
+        Ok(rows.into_iter().map(RecentBook::from).collect())
+    }
+
+    /// The books on the shelf of the user, the latest put first
+    pub fn load_for_shelf(conn: &SqliteConnection, user: Id, shelf: ShelfKind) -> QueryResult<Vec<Self>> {
+        let condition = format!(
+            "books.id IN (SELECT book_id FROM shelves WHERE user_id = {user} AND shelf = '{shelf}')",
+            user = user,
+            shelf = shelf.as_str()
+        );
+        let order = format!(
+            "(SELECT shelf_added FROM shelves WHERE shelves.book_id = books.id AND user_id = {user}) DESC, books.id DESC",
+            user = user
+        );
+        let rows = Self::load_rows(conn, &condition, &[], &order, "")?;
+        Ok(rows.into_iter().map(RecentBook::from).collect())
+    }
+
+    /// The genres of the books loaded since the time, to filter the arrivals
+    pub fn load_genres(conn: &SqliteConnection, since: &str) -> QueryResult<Vec<String>> {
+        #[derive(QueryableByName, Debug, Clone)]
+        pub struct DbString {
+            #[sql_type = "Text"] pub content: String,
+        }
+        let query = format!(
+            r#"
+            SELECT DISTINCT genre_name AS content
+            FROM genres_view
+            JOIN books ON (books.id = genres_view.book_id)
+            WHERE book_loaded >= '{since}'
+            ORDER BY genre_name
+            "#,
+            since = since
+        );
+        sql_query(&query)
+            .load::<DbString>(conn)
+            .map(|list| list.into_iter().map(|s| s.content).collect())
+    }
+}
+
+#[derive(QueryableByName, Debug, Clone, Serialize)]
+pub struct RecentArchive {
+    #[sql_type = "Text"] pub arch_name: String,
+    #[sql_type = "Text"] pub arch_loaded: String,
+    #[sql_type = "BigInt"] pub books: i64,
+}
+impl RecentArchive {
+    /// The archives which brought books since the time, the latest first
+    pub fn load(conn: &SqliteConnection, since: &str) -> QueryResult<Vec<Self>> {
+        let query = format!(
+            r#"
+            SELECT arch_name, arch_loaded, count(books.id) AS books
+            FROM archives
+            JOIN books ON (books.arch_id = archives.id)
+            WHERE book_loaded >= '{since}'
+            GROUP BY archives.id
+            ORDER BY max(book_loaded) DESC, arch_name
+            "#,
+            since = since
+        );
+        sql_query(&query).load(conn)
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RecentContext {
+    pub days: i64,
+    pub genre: String,
+    pub since: String,
+    pub updated: String,
+    pub archives: Vec<RecentArchive>,
+    pub genres: Vec<String>,
+    pub books: Vec<RecentBook>,
+    pub paging: Paging,
+}
+impl RecentContext {
+    pub const DEFAULT_DAYS: i64 = 7;
+    pub const MAX_DAYS: i64 = 3650;
+
+    pub fn new(query: &RecentQuery, paging: Paging) -> Self {
+        let now = chrono::Utc::now();
+        let days = query.days.unwrap_or(Self::DEFAULT_DAYS).max(1).min(Self::MAX_DAYS);
+        Self {
+            days: days,
+            genre: query.genre.clone().unwrap_or_default(),
+            since: (now - chrono::Duration::days(days)).format("%Y-%m-%d %H:%M:%S").to_string(),
+            updated: now.format("%Y-%m-%dT%H:%M:%SZ").to_string(),
+            archives: Vec::new(),
+            genres: Vec::new(),
+            books: Vec::new(),
+            paging: paging,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_to_rfc3339() {
+        assert_eq!(to_rfc3339("2026-10-19 15:00:00"), "2026-10-19T15:00:00Z");
+        assert_eq!(to_rfc3339(""), "");
+    }
+}
@@ -1,11 +1,14 @@
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+use super::author_mask::AuthorMask;
+use super::link::Link;
+use super::paging::encode_query_value;
 
 #[derive(Debug, Clone, Serialize)]
 pub struct RootContext {
-    pub first_name_nvc: Vec<String>,
-    pub middle_name_nvc: Vec<String>,
-    pub last_name_nvc: Vec<String>,
-    pub book_title_nvc: Vec<String>,
+    pub first_name_nvc: Vec<Link>,
+    pub middle_name_nvc: Vec<Link>,
+    pub last_name_nvc: Vec<Link>,
+    pub book_title_nvc: Vec<Link>,
 }
 impl RootContext {
     pub fn new() -> Self {
@@ -17,3 +20,42 @@ impl RootContext {
         }
     }
 }
+
+/// The search box of the page header
+#[derive(Debug, Clone, Deserialize)]
+pub struct SearchQuery {
+    #[serde(default)]
+    pub q: String,
+    #[serde(default)]
+    pub by: String,
+}
+impl SearchQuery {
+    /// The page of the titles or of the authors by the last name starting with the query
+    pub fn get_url(&self) -> String {
+        let value = self.q.trim();
+        if value.is_empty() {
+            return String::from("/");
+        }
+        match self.by.as_str() {
+            "author" => AuthorMask::default().with_name("last_name", value).get_link(value).get_url(),
+            _ => format!("/titles/{}/", encode_query_value(value)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn query(q: &str, by: &str) -> SearchQuery {
+        SearchQuery { q: String::from(q), by: String::from(by) }
+    }
+
+    #[test]
+    fn test_get_url() {
+        assert_eq!(query(" Война ", "title").get_url(), "/titles/%D0%92%D0%BE%D0%B9%D0%BD%D0%B0/");
+        assert_eq!(query("Tolstoy", "author").get_url(), "/authors/?last_name=Tolstoy");
+        assert_eq!(query("a/b", "").get_url(), "/titles/a%2Fb/");
+        assert_eq!(query("  ", "author").get_url(), "/");
+    }
+}
@@ -0,0 +1,122 @@
+use diesel::sql_query;
+use diesel::sql_types::Double;
+use diesel::RunQueryDsl;
+use serde::{Deserialize, Serialize};
+
+use super::QueryResult;
+use super::SqliteConnection;
+use super::recent_context::RecentBook;
+use crate::models::{Id, ShelfKind, ShelfRecord, UserRecord};
+
+/// The query string of the shelf pages: ?shelf=reading
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ShelfQuery {
+    pub shelf: Option<String>,
+}
+
+/// The form of the title page: shelf=want|reading|read|remove
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ShelfForm {
+    pub shelf: String,
+}
+
+/// The book on the shelf with the latest reading progress synced by the devices of the user
+#[derive(Debug, Clone, Serialize)]
+pub struct ShelfBook {
+    #[serde(flatten)]
+    pub book: RecentBook,
+    pub percent: Option<i32>,
+}
+impl ShelfBook {
+    /// The progress of the any served version of the book
+    fn load_percentage(conn: &SqliteConnection, user: Id, book: Id) -> QueryResult<Option<f64>> {
+        #[derive(QueryableByName, Debug, Clone)]
+        struct DbDouble {
+            #[sql_type = "Double"] percentage: f64,
+        }
+        let query = format!(
+            r#"
+            SELECT percentage
+            FROM reading_progress
+            JOIN book_digests ON (book_digests.document = reading_progress.document)
+            LEFT JOIN documents ON (documents.book_id = book_digests.book_id)
+            WHERE user_id = {user} AND ifnull(documents.canon_id, book_digests.book_id) = {book}
+            ORDER BY progress_time DESC
+            LIMIT 1
+            "#,
+            user = user,
+            book = book
+        );
+        sql_query(&query)
+            .load::<DbDouble>(conn)
+            .map(|rows| rows.first().map(|row| row.percentage))
+    }
+
+    pub fn load(conn: &SqliteConnection, user: Id, book: RecentBook) -> QueryResult<Self> {
+        let percentage = Self::load_percentage(conn, user, book.book_id)?;
+        Ok(Self {
+            book: book,
+            percent: percentage.map(|value| (value * 100.0).round() as i32),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ShelfGroup {
+    pub shelf: String,
+    pub label: String,
+    pub books: Vec<ShelfBook>,
+}
+impl ShelfGroup {
+    pub fn load(conn: &SqliteConnection, user: Id, shelf: ShelfKind) -> QueryResult<Self> {
+        let mut books = Vec::new();
+        for book in RecentBook::load_for_shelf(conn, user, shelf)? {
+            books.push(ShelfBook::load(conn, user, book)?);
+        }
+        Ok(Self {
+            shelf: String::from(shelf.as_str()),
+            label: String::from(shelf.get_label()),
+            books: books,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ShelfContext {
+    pub user_name: String,
+    pub shelf: String,
+    pub updated: String,
+    pub shelves: Vec<ShelfGroup>,
+}
+impl ShelfContext {
+    pub fn new(user: &UserRecord, query: &ShelfQuery) -> Self {
+        Self {
+            user_name: user.user_name.clone(),
+            shelf: query.shelf.as_ref()
+                .and_then(|shelf| ShelfKind::parse(shelf))
+                .map(|shelf| String::from(shelf.as_str()))
+                .unwrap_or_default(),
+            updated: chrono::Utc::now().format("%Y-%m-%dT%H:%M:%SZ").to_string(),
+            shelves: Vec::new(),
+        }
+    }
+
+    /// Loads the selected shelf or the all shelves of the user
+    pub fn load_shelves(&mut self, conn: &SqliteConnection, user: Id) -> QueryResult<()> {
+        for shelf in ShelfKind::ALL.iter() {
+            if self.shelf.is_empty() || self.shelf == shelf.as_str() {
+                self.shelves.push(ShelfGroup::load(conn, user, *shelf)?);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Moves the book to the shelf of the user or removes it from the shelves
+pub fn update_shelf(conn: &SqliteConnection, user: Id, book: Id, form: &ShelfForm) -> QueryResult<usize> {
+    match ShelfKind::parse(&form.shelf) {
+        Some(shelf) => ShelfRecord::put(conn, user, book, shelf),
+        None if form.shelf == "remove" => ShelfRecord::remove(conn, user, book),
+        None => Ok(0),
+    }
+}
@@ -0,0 +1,105 @@
+use serde::{Deserialize, Serialize};
+
+use super::QueryResult;
+use super::SqliteConnection;
+use crate::models::{BookDigest, BookDigestRecord, Id, ReadingProgress, ReadingProgressRecord, Save, UserRecord};
+
+/// The error codes of the KOReader progress sync protocol
+pub const ERROR_UNAUTHORIZED: i32 = 2001;
+pub const ERROR_INVALID_REQUEST: i32 = 2003;
+pub const ERROR_DOCUMENT_MISSING: i32 = 2004;
+pub const ERROR_REGISTRATION_DISABLED: i32 = 2005;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SyncError {
+    pub code: i32,
+    pub message: String,
+}
+impl SyncError {
+    pub fn new(code: i32) -> Self {
+        let message = match code {
+            ERROR_UNAUTHORIZED => "Unauthorized",
+            ERROR_DOCUMENT_MISSING => "Field 'document' not provided.",
+            ERROR_REGISTRATION_DISABLED => "User registration is disabled.",
+            _ => "Invalid request",
+        };
+        Self {
+            code: code,
+            message: String::from(message),
+        }
+    }
+}
+
+/// The body of PUT /syncs/progress
+#[derive(Debug, Clone, Deserialize)]
+pub struct ProgressForm {
+    pub document: String,
+    pub progress: String,
+    pub percentage: f64,
+    #[serde(default)]
+    pub device: String,
+    #[serde(default)]
+    pub device_id: String,
+}
+
+/// The body of GET /syncs/progress/{document}, empty when the document was not synced yet
+#[derive(Debug, Clone, Serialize)]
+pub struct ProgressReply {
+    pub document: String,
+    pub progress: String,
+    pub percentage: f64,
+    pub device: String,
+    pub device_id: String,
+    pub timestamp: i64,
+}
+impl From<ReadingProgressRecord> for ProgressReply {
+    fn from(record: ReadingProgressRecord) -> Self {
+        Self {
+            document: record.document,
+            progress: record.progress,
+            percentage: record.percentage,
+            device: record.device,
+            device_id: record.device_id,
+            timestamp: record.progress_time,
+        }
+    }
+}
+
+/// The device authenticates every request with the x-auth-user and x-auth-key headers
+pub fn authenticate(conn: &SqliteConnection, name: &str, key: &str) -> Option<UserRecord> {
+    if name.is_empty() || key.is_empty() {
+        return None;
+    }
+    UserRecord::authenticate_sync(conn, name, key)
+}
+
+/// Returns the time of the saved progress
+pub fn save_progress(conn: &SqliteConnection, user: Id, form: &ProgressForm) -> QueryResult<i64> {
+    let progress = ReadingProgress {
+        user_id: user,
+        document: form.document.clone(),
+        progress: form.progress.clone(),
+        percentage: form.percentage,
+        device: form.device.clone(),
+        device_id: form.device_id.clone(),
+        progress_time: chrono::Utc::now().timestamp(),
+    };
+    ReadingProgressRecord::save(conn, &progress)?;
+    Ok(progress.progress_time)
+}
+
+pub fn load_progress(conn: &SqliteConnection, user: Id, document: &str) -> QueryResult<Option<ProgressReply>> {
+    match ReadingProgressRecord::find_for_user(conn, user, document) {
+        Ok(record) => Ok(Some(ProgressReply::from(record))),
+        Err(diesel::result::Error::NotFound) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// Remembers the hash of the served file to find the book of the synced document
+pub fn save_book_digest(conn: &SqliteConnection, book: Id, document: String) -> QueryResult<usize> {
+    if document.is_empty() {
+        return Ok(0);
+    }
+    BookDigestRecord::save(conn, &BookDigest::new(book, document))
+}
@@ -1,14 +1,28 @@
-use serde::Serialize;
-use diesel::sql_types::Text;
+use serde::{Deserialize, Serialize};
+use diesel::sql_query;
+use diesel::sql_types::{Integer, Text};
+use diesel::RunQueryDsl;
+use crate::normalize::slugify;
 use super::author_mask::{AuthorMask, NvcMethods};
 use super::book_record::BookStringified;
+use super::link::Link;
+use super::paging::Paging;
+use super::annotation_context::BookAnnotations;
+use crate::models::{Id, ShelfKind, ShelfRecord, UserRecord};
+use super::{like_prefix, QueryResult, SqliteConnection};
 
 
-#[derive(QueryableByName, Debug, Clone, Serialize)]
+/// The title search, the query of the API and the OPDS catalog: ?title=Вой
+#[derive(QueryableByName, Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
 pub struct TitleMask {
+    #[serde(rename = "title")]
     #[sql_type = "Text"] pub book_title: String,
+    #[serde(skip)]
     #[sql_type = "Text"] pub first_name: String,
+    #[serde(skip)]
     #[sql_type = "Text"] pub middle_name: String,
+    #[serde(skip)]
     #[sql_type = "Text"] pub last_name: String,
 }
 impl TitleMask {
@@ -35,38 +49,97 @@ impl NvcMethods for TitleMask {
         }
     }
 
-    fn get_where_like_clause(&self) -> String {
+    fn get_where_like_clause(&self) -> (String, Vec<String>) {
         if self.book_title.is_empty()
         {
-            String::new()
+            (String::new(), Vec::new())
         }
         else
         {
-            "WHERE ".to_owned() + &format!("book_title LIKE '{}%'", self.book_title)
+            (String::from(r"WHERE book_title_key LIKE ? ESCAPE '\'"), vec![like_prefix(&self.book_title)])
         }
     }
 
-    fn get_where_explicit_clause(&self) -> String {
+    fn get_where_explicit_clause(&self) -> (String, Vec<String>) {
         if self.book_title.is_empty()
         {
-            String::new()
+            (String::new(), Vec::new())
         }
         else
         {
-            "WHERE ".to_owned() + &format!("book_title = '{}%'", self.book_title)
+            (String::from("WHERE book_title = ?"), vec![self.book_title.clone()])
         }
     }
 }
 
 
 
+/// The title with its author and the first book of them, the id-based links are made of it
+#[derive(QueryableByName, Debug, Clone, Serialize)]
+pub struct TitleEntry {
+    #[sql_type = "Integer"] pub book_id: i32,
+    #[sql_type = "Integer"] pub author_id: i32,
+    #[sql_type = "Text"] pub book_title: String,
+    #[sql_type = "Text"] pub first_name: String,
+    #[sql_type = "Text"] pub middle_name: String,
+    #[sql_type = "Text"] pub last_name: String,
+}
+impl TitleEntry {
+    /// The first author of the book
+    pub fn load_by_book(conn: &SqliteConnection, book: Id) -> QueryResult<Self> {
+        let query = format!(r#"
+            SELECT title_links.book_id, authors.id AS author_id, book_title, first_name, middle_name, last_name
+            FROM title_links
+            JOIN author_links ON (author_links.book_id = title_links.book_id)
+            JOIN authors ON (author_links.author_id = authors.id)
+            JOIN titles ON (title_links.title_id = titles.id)
+            WHERE title_links.book_id = {book} AND role = 'author'
+            ORDER BY author_links.id
+            LIMIT 1"#,
+            book = book
+        );
+        sql_query(&query).get_result(conn)
+    }
+
+    pub fn get_author(&self) -> AuthorMask {
+        AuthorMask {
+            first_name: self.first_name.clone(),
+            middle_name: self.middle_name.clone(),
+            last_name: self.last_name.clone(),
+        }
+    }
+
+    pub fn get_link(&self) -> Link {
+        Link::new(
+            &format!("{} ({} {} {})", self.book_title, self.last_name, self.first_name, self.middle_name),
+            &["book", &self.book_id.to_string(), &slugify(&self.book_title)])
+    }
+
+    pub fn get_url(&self) -> String {
+        Link::make_path(&self.get_link().path) + "/"
+    }
+}
+
+/// The title of the author and the first book of them
+#[derive(QueryableByName, Debug, Clone, Serialize)]
+pub struct BookTitle {
+    #[sql_type = "Integer"] pub book_id: i32,
+    #[sql_type = "Text"] pub book_title: String,
+}
+impl BookTitle {
+    pub fn get_link(&self) -> Link {
+        Link::new(&self.book_title, &["book", &self.book_id.to_string(), &slugify(&self.book_title)])
+    }
+}
+
 #[derive(Debug, Clone, Serialize)]
 pub struct FindTitleContext {
     pub root_url: String,
     pub book_title: String,
-    pub titles_nvc: Vec<String>,
-    pub title_and_author: Vec<TitleMask>,
-    pub titles: Vec<String>,
+    pub titles_nvc: Vec<Link>,
+    pub title_and_author: Vec<TitleEntry>,
+    pub titles: Vec<Link>,
+    pub paging: Paging,
 }
 impl FindTitleContext {
     pub fn new(url: &str, mask: &TitleMask) -> Self {
@@ -76,35 +149,125 @@ impl FindTitleContext {
             titles_nvc: Vec::new(),
             title_and_author: Vec::new(),
             titles: Vec::new(),
+            paging: Paging::default(),
         }
     }
 
     pub fn load_title_nvc(&mut self, nvc: Vec<String>) {
         self.titles_nvc = nvc.iter().map(|title|
-            format!("<a href='/{}/{}/'> {} </a>",
-                self.root_url,
-                AuthorMask::encode(title.clone()),
-                title)).collect();
+            Link::new(title, &[&self.root_url, title])).collect();
+    }
+}
+
+/// The titles of the mask with their authors listed by the API and the OPDS catalog
+#[derive(Debug, Clone, Serialize)]
+pub struct TitleListContext {
+    pub title: String,
+    pub updated: String,
+    pub titles: Vec<TitleEntry>,
+    pub paging: Paging,
+}
+impl TitleListContext {
+    pub fn new(mask: &TitleMask, paging: Paging) -> Self {
+        Self {
+            title: mask.book_title.clone(),
+            updated: chrono::Utc::now().format("%Y-%m-%dT%H:%M:%SZ").to_string(),
+            titles: Vec::new(),
+            paging: paging,
+        }
     }
 }
 
+/// The versions of the same book found in the different archives
+#[derive(Debug, Clone, Serialize)]
+pub struct BookVersions {
+    pub canon_id: i32,
+    pub shelf: String,
+    pub shelf_label: String,
+    pub annotations: Option<BookAnnotations>,
+    pub books: Vec<BookStringified>,
+}
+
 #[derive(Debug, Clone, Serialize)]
 pub struct TitleContext {
+    pub book_id: i32,
+    pub slug: String,
+    pub author_id: i32,
+    pub author_slug: String,
     pub first_name: String,
     pub middle_name: String,
     pub last_name: String,
     pub title: String,
-    pub books: Vec<BookStringified>,
+    pub user_name: String,
+    pub versions: Vec<BookVersions>,
 }
 impl TitleContext {
-    pub fn new(author: &AuthorMask, title: String) -> Self {
+    pub fn new(entry: &TitleEntry) -> Self {
+        let author = entry.get_author();
         Self{
-            first_name: author.get_encoded_by_name("first_name"),
-            middle_name: author.get_encoded_by_name("middle_name"),
-            last_name: author.get_encoded_by_name("last_name"),
-            title: title,
-            books: Vec::new(),
+            book_id: entry.book_id,
+            slug: slugify(&entry.book_title),
+            author_id: entry.author_id,
+            author_slug: slugify(&author.get_full_name()),
+            first_name: author.first_name.clone(),
+            middle_name: author.middle_name.clone(),
+            last_name: author.last_name.clone(),
+            title: entry.book_title.clone(),
+            user_name: String::new(),
+            versions: Vec::new(),
+        }
+    }
+
+    /// The canonical URL of the page, the other URLs of the book are redirected to it
+    pub fn get_url(&self) -> String {
+        Link::make_path(&["book", &self.book_id.to_string(), &self.slug]) + "/"
+    }
+
+    pub fn load_books(&mut self, books: Vec<BookStringified>) {
+        for book in books {
+            match self.versions.iter_mut().find(|group| group.canon_id == book.canon_id) {
+                Some(group) => group.books.push(book),
+                None => self.versions.push(BookVersions {
+                    canon_id: book.canon_id,
+                    shelf: String::new(),
+                    shelf_label: String::new(),
+                    annotations: None,
+                    books: vec![book]
+                }),
+            }
         }
     }
 
+    pub fn load_annotations(&mut self, conn: &SqliteConnection, user: Option<Id>) -> QueryResult<()> {
+        for group in self.versions.iter_mut() {
+            group.annotations = Some(BookAnnotations::load(conn, group.canon_id, user)?);
+        }
+        Ok(())
+    }
+
+    /// Marks the books already put on the shelves of the user
+    pub fn load_shelves(&mut self, user: &UserRecord, shelves: Vec<ShelfRecord>) {
+        self.user_name = user.user_name.clone();
+        for record in shelves {
+            if let Some(group) = self.versions.iter_mut().find(|group| group.canon_id == record.book_id) {
+                if let Some(shelf) = ShelfKind::parse(&record.shelf) {
+                    group.shelf = String::from(shelf.as_str());
+                    group.shelf_label = String::from(shelf.get_label());
+                }
+            }
+        }
+    }
+
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_get_where_explicit_clause() {
+        assert_eq!(TitleMask::new(String::from("Tom's Midnight Garden")).get_where_explicit_clause(),
+            (String::from("WHERE book_title = ?"), vec![String::from("Tom's Midnight Garden")]));
+        assert_eq!(TitleMask::new(String::new()).get_where_explicit_clause(), (String::new(), Vec::new()));
+    }
 }
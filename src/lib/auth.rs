@@ -0,0 +1,172 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use hmac::{Hmac, Mac, NewMac};
+use rand::RngCore;
+use sha1::Sha1;
+
+/// The scheme tag of the stored password hashes: pbkdf2-sha1$iterations$salt$hash
+const SCHEME: &str = "pbkdf2-sha1";
+const ITERATIONS: u32 = 100_000;
+const SALT_SIZE: usize = 16;
+const HASH_SIZE: usize = 20;
+/// The OPDS readers and KOReader send the credentials with every request,
+/// the verified ones are remembered for a while to not derive the key each time
+const VERIFIED_TTL: Duration = Duration::from_secs(300);
+
+lazy_static! {
+    /// The process-wide random key of the cache entries, the cache never keeps the passwords
+    static ref VERIFIED_KEY: Vec<u8> = random_bytes(HASH_SIZE);
+    static ref VERIFIED: Mutex<HashMap<Vec<u8>, Instant>> = Mutex::new(HashMap::new());
+}
+
+fn pbkdf2_sha1(password: &[u8], salt: &[u8], iterations: u32) -> [u8; HASH_SIZE] {
+    let mut result = [0u8; HASH_SIZE];
+    pbkdf2::pbkdf2::<Hmac<Sha1>>(password, salt, iterations, &mut result);
+    result
+}
+
+/// The cache key depends on the stored hash, so the changed password drops the old entries
+fn verified_key(password: &str, stored: &str) -> Vec<u8> {
+    let mut mac = Hmac::<Sha1>::new_from_slice(&VERIFIED_KEY).expect("HMAC accepts any key size");
+    mac.update(stored.as_bytes());
+    mac.update(&[0]);
+    mac.update(password.as_bytes());
+    mac.finalize().into_bytes().to_vec()
+}
+
+fn is_verified(key: &[u8]) -> bool {
+    let mut verified = VERIFIED.lock().unwrap_or_else(|e| e.into_inner());
+    let now = Instant::now();
+    verified.retain(|_, expires| *expires > now);
+    verified.contains_key(key)
+}
+
+fn set_verified(key: Vec<u8>) {
+    let mut verified = VERIFIED.lock().unwrap_or_else(|e| e.into_inner());
+    verified.insert(key, Instant::now() + VERIFIED_TTL);
+}
+
+/// Compares without the early exit to not leak the position of the difference
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b.iter()).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+pub fn random_bytes(size: usize) -> Vec<u8> {
+    let mut bytes = vec![0u8; size];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    bytes
+}
+
+/// Makes the password hash to store in the users table
+pub fn hash_password(password: &str) -> String {
+    let salt = random_bytes(SALT_SIZE);
+    let hash = pbkdf2_sha1(password.as_bytes(), &salt, ITERATIONS);
+    format!("{}${}${}${}", SCHEME, ITERATIONS, base64::encode(&salt), base64::encode(hash))
+}
+
+pub fn verify_password(password: &str, stored: &str) -> bool {
+    let parts: Vec<&str> = stored.split('$').collect();
+    if parts.len() != 4 || parts[0] != SCHEME {
+        return false;
+    }
+    let key = verified_key(password, stored);
+    if is_verified(&key) {
+        return true;
+    }
+    let valid = match (parts[1].parse::<u32>(), base64::decode(parts[2]), base64::decode(parts[3])) {
+        (Ok(iterations), Ok(salt), Ok(hash)) if iterations > 0 => {
+            constant_time_eq(&pbkdf2_sha1(password.as_bytes(), &salt, iterations), &hash)
+        },
+        _ => false,
+    };
+    if valid {
+        set_verified(key);
+    }
+    valid
+}
+
+/// The key sent by the KOReader progress sync instead of the password
+pub fn make_sync_key(password: &str) -> String {
+    format!("{:x}", md5::compute(password.as_bytes()))
+}
+
+/// Makes the random session token stored in the cookie
+pub fn make_token() -> String {
+    random_bytes(32).iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Parses the value of the 'Authorization: Basic ...' header into the user name and the password
+pub fn parse_basic_auth(header: &str) -> Option<(String, String)> {
+    let mut parts = header.trim().splitn(2, ' ');
+    if !parts.next()?.eq_ignore_ascii_case("basic") {
+        return None;
+    }
+    let decoded = String::from_utf8(base64::decode(parts.next()?.trim()).ok()?).ok()?;
+    let mut credentials = decoded.splitn(2, ':');
+    Some((String::from(credentials.next()?), String::from(credentials.next()?)))
+}
+
+/// Checks that the Origin or the Referer of the request points to the host of the service
+pub fn is_same_origin(source: &str, host: &str) -> bool {
+    match source.split("://").nth(1) {
+        Some(rest) => rest.split('/').next().is_some_and(|authority| authority.eq_ignore_ascii_case(host)),
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    #[test]
+    fn test_pbkdf2_sha1_rfc6070() {
+        assert_eq!(hex(&pbkdf2_sha1(b"password", b"salt", 1)), "0c60c80f961f0e71f3a9b524af6012062fe037a6");
+        assert_eq!(hex(&pbkdf2_sha1(b"password", b"salt", 2)), "ea6c014dc72d6f8ccd1ed92ace1d41f0d8de8957");
+        assert_eq!(hex(&pbkdf2_sha1(b"password", b"salt", 4096)), "4b007901b765489abead49d926f721d065a429c1");
+    }
+
+    #[test]
+    fn test_verify_password() {
+        let stored = hash_password("secret");
+        assert!(verify_password("secret", &stored));
+        assert!(!verify_password("Secret", &stored));
+        assert!(!verify_password("secret", "plain"));
+        assert_ne!(stored, hash_password("secret"));
+    }
+
+    #[test]
+    fn test_verified_cache() {
+        let stored = hash_password("cached");
+        assert!(verify_password("cached", &stored));
+        assert!(is_verified(&verified_key("cached", &stored)));
+        assert!(verify_password("cached", &stored));
+        assert!(!verify_password("cached!", &stored));
+        assert!(!is_verified(&verified_key("cached", &hash_password("cached"))));
+    }
+
+    #[test]
+    fn test_make_sync_key() {
+        assert_eq!(make_sync_key("password"), "5f4dcc3b5aa765d61d8327deb882cf99");
+    }
+
+    #[test]
+    fn test_parse_basic_auth() {
+        assert_eq!(parse_basic_auth("Basic dXNlcjpwYTpzcw=="), Some((String::from("user"), String::from("pa:ss"))));
+        assert_eq!(parse_basic_auth("Bearer dXNlcjpwYTpzcw=="), None);
+        assert_eq!(parse_basic_auth("Basic !!!"), None);
+    }
+
+    #[test]
+    fn test_is_same_origin() {
+        assert!(is_same_origin("http://books.local:8080", "books.local:8080"));
+        assert!(is_same_origin("https://Books.Local/admin/", "books.local"));
+        assert!(!is_same_origin("http://evil.example/books.local", "books.local"));
+        assert!(!is_same_origin("http://books.local.evil.example", "books.local"));
+        assert!(!is_same_origin("null", "books.local"));
+    }
+}
@@ -0,0 +1,278 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use serde::Deserialize;
+
+/// The parts of the service which may be switched off
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Features {
+    /// The OPDS catalog and the Atom feeds
+    pub opds: bool,
+    /// The JSON API
+    pub api: bool,
+    /// The logins, the shelves, the ratings, the reviews and the tags
+    pub accounts: bool,
+    /// The KOReader progress sync
+    pub kosync: bool,
+}
+impl Default for Features {
+    fn default() -> Self {
+        Self {
+            opds: true,
+            api: true,
+            accounts: true,
+            kosync: true,
+        }
+    }
+}
+impl Features {
+    pub const NAMES: &'static [&'static str] = &["opds", "api", "accounts", "kosync"];
+
+    pub fn disable(&mut self, name: &str) -> Result<(), String> {
+        match name {
+            "opds" => self.opds = false,
+            "api" => self.api = false,
+            "accounts" => self.accounts = false,
+            "kosync" => self.kosync = false,
+            _ => return Err(format!("Unknown feature '{}', expected one of: {}", name, Self::NAMES.join(", "))),
+        }
+        Ok(())
+    }
+}
+
+/// The settings of fb2service: the defaults are overridden by the config file
+/// and then by the command line
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// The addresses to listen, e.g. 127.0.0.1:8080
+    pub bind: Vec<String>,
+    /// The path of the SQLite database
+    pub database: String,
+    pub templates: String,
+    /// The stylesheet and the scripts of the web UI served under /static/, empty to serve nothing
+    pub static_dir: String,
+    /// The directory of the temporary files made by the downloads
+    pub workdir: String,
+    pub pool_size: u32,
+    pub log_level: String,
+    /// The scheme and the host prepended to the OPDS and Atom links, empty for the relative links
+    pub base_url: String,
+    /// The users allowed to the /admin pages, nobody when empty
+    pub admins: Vec<String>,
+    /// The directory scanned by the admin pages for the new archives, empty to disable the loads
+    pub library: String,
+    /// The library roots by the name, the service finds the archives stored relative to them at these paths
+    pub roots: BTreeMap<String, String>,
+    pub features: Features,
+}
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            bind: Vec::new(),
+            database: String::new(),
+            templates: String::from("./templates"),
+            static_dir: String::from("./static"),
+            workdir: String::from("/tmp"),
+            pool_size: 10,
+            log_level: String::from("info"),
+            base_url: String::new(),
+            admins: Vec::new(),
+            library: String::new(),
+            roots: BTreeMap::new(),
+            features: Features::default(),
+        }
+    }
+}
+impl Config {
+    pub const LOG_LEVELS: &'static [&'static str] = &["off", "error", "warn", "info", "debug", "trace"];
+    pub const MAX_POOL_SIZE: u32 = 64;
+    /// The date of the tag URIs, the feed ids must never change
+    pub const TAG_YEAR: u32 = 2020;
+
+    pub fn parse(content: &str) -> Result<Self, String> {
+        toml::from_str(content).map_err(|e| format!("Invalid config: {}", e))
+    }
+
+    pub fn load(path: &str) -> Result<Self, String> {
+        let content = fs::read_to_string(path).map_err(|e| format!("Can't read config {}: {}", path, e))?;
+        Self::parse(&content).map_err(|e| format!("{} in {}", e, path))
+    }
+
+    /// The environment variables used before the config file was introduced
+    /// fill the bind address and the database not set by the config file
+    pub fn apply_env(&mut self) {
+        if let (true, Ok(bind)) = (self.bind.is_empty(), std::env::var("INTERFACE")) {
+            self.bind = vec![bind];
+        }
+        if let (true, Ok(database)) = (self.database.is_empty(), std::env::var("DATABASE_URL")) {
+            self.database = database;
+        }
+    }
+
+    /// Checks the settings which would fail the service later, at the first request
+    pub fn validate(&self) -> Result<(), String> {
+        if self.bind.is_empty() {
+            return Err(String::from("The bind address is not set, e.g.: 192.168.0.1:8080"));
+        }
+        for bind in self.bind.iter() {
+            let port = bind.rsplitn(2, ':').next().unwrap_or("");
+            if !bind.contains(':') || port.parse::<u16>().is_err() {
+                return Err(format!("Invalid bind address '{}', expected HOST:PORT", bind));
+            }
+        }
+        if self.database.is_empty() {
+            return Err(String::from("The database is not set"));
+        }
+        if !Path::new(&self.database).is_file() {
+            return Err(format!("The database {} is not found", self.database));
+        }
+        Self::check_dir("templates", &self.templates)?;
+        if !self.static_dir.is_empty() {
+            Self::check_dir("static", &self.static_dir)?;
+        }
+        Self::check_dir("work", &self.workdir)?;
+        if !self.library.is_empty() {
+            Self::check_dir("library", &self.library)?;
+        }
+        for (name, path) in self.roots.iter() {
+            if name.is_empty() {
+                return Err(format!("The name of the root {} is empty", path));
+            }
+            Self::check_dir(&format!("{} root", name), path)?;
+        }
+        if 0 == self.pool_size || self.pool_size > Self::MAX_POOL_SIZE {
+            return Err(format!("Invalid pool size {}, expected 1..{}", self.pool_size, Self::MAX_POOL_SIZE));
+        }
+        if !Self::LOG_LEVELS.contains(&self.log_level.as_str()) {
+            return Err(format!("Invalid log level '{}', expected one of: {}", self.log_level, Self::LOG_LEVELS.join(", ")));
+        }
+        if !self.base_url.is_empty() && !self.base_url.starts_with("http://") && !self.base_url.starts_with("https://") {
+            return Err(format!("Invalid base URL '{}', expected http://HOST or https://HOST", self.base_url));
+        }
+        Ok(())
+    }
+
+    fn check_dir(name: &str, path: &str) -> Result<(), String> {
+        if Path::new(path).is_dir() {
+            Ok(())
+        } else {
+            Err(format!("The {} directory {} is not found", name, path))
+        }
+    }
+
+    /// Adds the root given as NAME=DIR
+    pub fn add_root(&mut self, value: &str) -> Result<(), String> {
+        let mut parts = value.splitn(2, '=');
+        match (parts.next(), parts.next()) {
+            (Some(name), Some(path)) if !name.trim().is_empty() && !path.trim().is_empty() => {
+                self.roots.insert(String::from(name.trim()), String::from(path.trim()));
+                Ok(())
+            },
+            _ => Err(format!("Invalid root '{}', expected NAME=DIR", value)),
+        }
+    }
+
+    /// The admin pages need the logins
+    pub fn has_admin(&self) -> bool {
+        self.features.accounts && !self.admins.is_empty()
+    }
+
+    /// The base URL without the trailing slash to prepend to the absolute paths
+    pub fn get_base_url(&self) -> String {
+        String::from(self.base_url.trim_end_matches('/'))
+    }
+
+    /// The prefix of the tag URIs (RFC 4151) identifying the feeds and their entries,
+    /// the authority is the host of the base URL
+    pub fn get_tag_prefix(&self) -> String {
+        let host = self.base_url
+            .trim_start_matches("https://")
+            .trim_start_matches("http://")
+            .split(|c| c == '/' || c == ':')
+            .next()
+            .unwrap_or_default();
+        format!("tag:{},{}:", if host.is_empty() { "localhost" } else { host }, Self::TAG_YEAR)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn valid() -> Config {
+        Config {
+            bind: vec![String::from("127.0.0.1:8080")],
+            database: String::from("Cargo.toml"),
+            templates: String::from("."),
+            static_dir: String::from("."),
+            workdir: String::from("."),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_parse() {
+        let config = Config::parse(r#"
+            bind = ["127.0.0.1:8080", "[::1]:8080"]
+            database = "/lib/books.db"
+            pool_size = 4
+            base_url = "https://books.example.org/"
+            admins = ["bob"]
+
+            [roots]
+            librusec = "/mnt/books/lib.rus.ec"
+
+            [features]
+            kosync = false
+        "#).unwrap();
+        assert_eq!(config.bind.len(), 2);
+        assert_eq!(config.pool_size, 4);
+        assert_eq!(config.templates, "./templates");
+        assert_eq!(config.static_dir, "./static");
+        assert_eq!(config.get_base_url(), "https://books.example.org");
+        assert_eq!(config.get_tag_prefix(), "tag:books.example.org,2020:");
+        assert_eq!(Config::default().get_tag_prefix(), "tag:localhost,2020:");
+        assert_eq!(config.roots.get("librusec").map(String::as_str), Some("/mnt/books/lib.rus.ec"));
+        assert_eq!(config.features, Features { kosync: false, ..Default::default() });
+        assert!(config.has_admin());
+        assert!(!Config::default().has_admin());
+
+        assert!(Config::parse("pool_size = \"many\"").is_err());
+        assert!(Config::parse("unknown = 1").is_err());
+    }
+
+    #[test]
+    fn test_validate() {
+        assert_eq!(valid().validate(), Ok(()));
+        assert!(Config { bind: Vec::new(), ..valid() }.validate().is_err());
+        assert!(Config { bind: vec![String::from("localhost")], ..valid() }.validate().is_err());
+        assert!(Config { database: String::from("missing.db"), ..valid() }.validate().is_err());
+        assert!(Config { static_dir: String::from("missing"), ..valid() }.validate().is_err());
+        assert!(Config { library: String::from("missing"), ..valid() }.validate().is_err());
+        let roots = |name: &str, path: &str| vec![(String::from(name), String::from(path))].into_iter().collect();
+        assert_eq!(Config { roots: roots("librusec", "."), ..valid() }.validate(), Ok(()));
+        assert!(Config { roots: roots("librusec", "missing"), ..valid() }.validate().is_err());
+        assert!(Config { pool_size: 0, ..valid() }.validate().is_err());
+        assert!(Config { log_level: String::from("loud"), ..valid() }.validate().is_err());
+        assert!(Config { base_url: String::from("books.example.org"), ..valid() }.validate().is_err());
+    }
+
+    #[test]
+    fn test_add_root() {
+        let mut config = Config::default();
+        assert_eq!(config.add_root("librusec=/mnt/books/lib.rus.ec"), Ok(()));
+        assert_eq!(config.roots.get("librusec").map(String::as_str), Some("/mnt/books/lib.rus.ec"));
+        assert!(config.add_root("/mnt/books").is_err());
+        assert!(config.add_root("=/mnt/books").is_err());
+    }
+
+    #[test]
+    fn test_disable() {
+        let mut features = Features::default();
+        assert_eq!(features.disable("api"), Ok(()));
+        assert!(!features.api && features.opds);
+        assert!(features.disable("search").is_err());
+    }
+}
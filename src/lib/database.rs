@@ -1,5 +1,6 @@
 
 use std::env;
+use std::path::{Path, PathBuf};
 use std::collections::HashMap;
 use std::hash::Hash;
 use std::fmt::Debug;
@@ -11,26 +12,43 @@ use fb2parser::FictionBook;
 use crate::models::*;
 
 fn establish_connection() -> SqliteConnection {
-    use diesel::prelude::Connection;
-    use crate::diesel::connection::SimpleConnection;
-
     dotenv().ok();
     let database_url = env::var("DATABASE_URL")
         .expect("DATABASE_URL must be set");
-    let conn: SqliteConnection = Connection::establish(&database_url)
-        .expect(&format!("Error connecting to {}", database_url));
-
-    let queries = vec![
-        "PRAGMA cache_size = -262144;   /* 256 * 1024 Kb = 256 Mb */",
-        "PRAGMA journal_mode = MEMORY;  /* Fast but unsave journal */ ",
-        "PRAGMA temp_store = MEMORY; ",
-        "PRAGMA synchronous = OFF; "
-    ];    
+    connect(&database_url, true).unwrap_or_else(|e| panic!("{}", e))
+}
+
+/// The time the connection waits for the other writer before 'database is locked'
+pub const BUSY_TIMEOUT_MS: u32 = 10000;
+
+/// The bulk load turns off the journal on the disk, the load by the running service keeps it
+/// and waits for the readers instead
+fn connect(database_url: &str, bulk: bool) -> Result<SqliteConnection, String> {
+    use diesel::prelude::Connection;
+    use crate::diesel::connection::SimpleConnection;
+
+    let conn: SqliteConnection = Connection::establish(database_url)
+        .map_err(|e| format!("Error connecting to {}: {}", database_url, e))?;
+
+    let busy_timeout = format!("PRAGMA busy_timeout = {}; ", BUSY_TIMEOUT_MS);
+    let queries = if bulk {
+        vec![
+            "PRAGMA cache_size = -262144;   /* 256 * 1024 Kb = 256 Mb */",
+            "PRAGMA journal_mode = MEMORY;  /* Fast but unsave journal */ ",
+            "PRAGMA temp_store = MEMORY; ",
+            "PRAGMA synchronous = OFF; "
+        ]
+    } else {
+        vec![
+            "PRAGMA temp_store = MEMORY; ",
+            busy_timeout.as_str()
+        ]
+    };
     for query in &queries {
-        conn.batch_execute(query).expect(&format!("Can't execute: {}", query));
+        conn.batch_execute(query).map_err(|e| format!("Can't execute: {}: {}", query, e))?;
     }
 
-    return conn;
+    Ok(conn)
 }
 
 pub enum SaveResult {
@@ -58,7 +76,7 @@ pub struct Storage<T: Clone+Eq+Hash+Debug> {
 impl <T: Clone+Eq+Hash+Debug> Drop for Storage<T> {
     fn drop(&mut self) {
         let inner_type = std::any::type_name::<T>().rsplit(':').take(1).next();
-        println!("Storage<{:>12}> hits/size: {:>5}/{:>6}, quered/inserted/total: {:>6}/{:>6}/{:>6}", 
+        log::debug!("Storage<{:>12}> hits/size: {:>5}/{:>6}, quered/inserted/total: {:>6}/{:>6}/{:>6}",
             inner_type.unwrap(),
             self.hits, self.map.len(),
             self.quered, self.added, self.count
@@ -110,11 +128,23 @@ pub struct Manager{
     pub title_links: Storage<TitleLink>,
     pub genres: Storage<Genre>,
     pub genre_links: Storage<GenreLink>,
+    pub documents: Storage<Document>,
+    pub publications: Storage<Publication>,
+    pub isbns: Storage<Isbn>,
+    pub originals: Storage<Original>,
+    pub series: Storage<Series>,
+    pub series_links: Storage<SeriesLink>,
+    pub roots: Roots,
 }
 impl Manager {
     pub fn new() -> Self {
+        Self::with_connection(establish_connection())
+    }
+
+    fn with_connection(conn: SqliteConnection) -> Self {
+        let roots = Roots::load(&conn).expect("Failed to load library roots");
         Self{
-            conn: establish_connection(),
+            conn: conn,
             archives: Storage::new(),
             books: Storage::new(),
             authors: Storage::new(),
@@ -123,9 +153,25 @@ impl Manager {
             title_links: Storage::new(),
             genres: Storage::new(),
             genre_links: Storage::new(),
+            documents: Storage::new(),
+            publications: Storage::new(),
+            isbns: Storage::new(),
+            originals: Storage::new(),
+            series: Storage::new(),
+            series_links: Storage::new(),
+            roots: roots,
         }
     }
 
+    /// The manager of the database shared with the running service
+    pub fn open(database_url: &str) -> Result<Self, String> {
+        Ok(Self::with_connection(connect(database_url, false)?))
+    }
+
+    pub fn get_connection(&self) -> &SqliteConnection {
+        &self.conn
+    }
+
     pub fn find_archive(&self, uuid: &String) -> Option<Id> {
         ArchiveRecord::find_uniq(&self.conn, uuid)
     }
@@ -134,30 +180,308 @@ impl Manager {
         BookRecord::find_uniq(&self.conn, archive_id, name, crc)
     }
 
+    pub fn find_book_by_file(&self, archive_id: Id, name: &str) -> QueryResult<Option<BookRecord>> {
+        BookRecord::find_by_file(&self.conn, archive_id, name)
+    }
+
+    /// The book loaded before with the same size and CRC
+    pub fn find_book_by_content(&self, book: &Book) -> QueryResult<Option<Id>> {
+        BookRecord::find_by_content(&self.conn, book.book_size, book.book_crc32)
+    }
+
+    /// Sets the entry data of the book imported from INPX before the archive was found
+    pub fn fill_book(&mut self, book_id: Id, entry: &Book) -> QueryResult<usize> {
+        self.books.map.clear();
+        BookRecord::move_to(&self.conn, book_id, entry)
+    }
+
+    /// Saves the archive inside the root relative to it
     pub fn save_archive(&mut self, archive: Archive) -> SaveResult {
+        let archive = self.split_home(archive);
         self.archives.save::<ArchiveRecord>(&self.conn, archive)
     }
 
+    /// Completes the archive imported from INPX before it was found, returns the id of the completed archive
+    pub fn complete_archive(&mut self, archive: Archive) -> QueryResult<Option<Id>> {
+        let archive = self.split_home(archive);
+        ArchiveRecord::complete(&self.conn, &archive)
+    }
+
+    fn split_home(&self, mut archive: Archive) -> Archive {
+        let (root, home) = self.roots.split(Path::new(&archive.arch_home));
+        archive.root_id = root;
+        archive.arch_home = home;
+        archive
+    }
+
+    /// Sets the directory of the root, the archives inside it loaded with the absolute paths become
+    /// relative to it. Returns the id of the root and the number of such archives.
+    pub fn rebase_root(&mut self, name: &str, path: &Path) -> Result<(Id, usize), String> {
+        let path = path.canonicalize().map_err(|e| format!("Can't find root directory {}: {}", path.to_string_lossy(), e))?;
+        let rebased = RootRecord::rebase(&self.conn, &Root::new(name, &path.to_string_lossy()))
+            .map_err(|e| format!("Failed to rebase root {}: {}", name, e))?;
+        self.roots = Roots::load(&self.conn).map_err(|e| e.to_string())?;
+        Ok(rebased)
+    }
+
+    /// The absolute path of the loaded archive
+    pub fn get_archive_path(&self, archive: &ArchiveRecord) -> PathBuf {
+        self.roots.get_home(archive.root_id, &archive.arch_home).join(&archive.arch_name)
+    }
+
+    /// The archive loaded before, found by the name, the size and the head hash
+    pub fn find_archive_by_head(&self, archive: &Archive) -> QueryResult<Option<Id>> {
+        ArchiveRecord::find_by_head(&self.conn, archive)
+    }
+
+    /// Upgrades the archive loaded before the full hashes to the full hash of the same archive
+    pub fn upgrade_archive(&self, archive: &Archive) -> usize {
+        ArchiveRecord::upgrade(&self.conn, archive).expect(&format!("Failed to upgrade archive {:?}", archive))
+    }
+
+    /// Marks the archive loaded completely, the interrupted load leaves it unfinished
+    pub fn finish_archive(&self, archive_id: Id) {
+        ArchiveRecord::set_done(&self.conn, archive_id).expect(&format!("Failed to finish archive {}", archive_id));
+    }
+
+    /// Finds the loaded archive by the id, the path or the file name
+    pub fn find_archive_by(&self, value: &str) -> Result<Id, String> {
+        if let Ok(id) = value.parse::<Id>() {
+            return ArchiveRecord::load(&self.conn, id)
+                .map(|archive| archive.id)
+                .map_err(|_| format!("Archive {} is not found", id));
+        }
+        let path = Path::new(value);
+        let name = path.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default();
+        let home = path.parent()
+            .filter(|home| !home.as_os_str().is_empty())
+            .map(|home| home.canonicalize().unwrap_or(home.to_path_buf()));
+        let found: Vec<ArchiveRecord> = ArchiveRecord::find_by_name(&self.conn, &name)
+            .map_err(|e| e.to_string())?
+            .into_iter()
+            .filter(|archive| home.as_ref().map_or(true, |home| self.roots.get_home(archive.root_id, &archive.arch_home) == *home))
+            .collect();
+        match found.as_slice() {
+            [archive] => Ok(archive.id),
+            [] => Err(format!("Archive '{}' is not found", value)),
+            _ => Err(format!("Archive name '{}' is ambiguous, use the id or the path of the archive", value)),
+        }
+    }
+
+    /// Removes the archive with its books and the rows left without the books,
+    /// returns the number of the removed books
+    pub fn remove_archive(&mut self, archive_id: Id) -> QueryResult<usize> {
+        let removed = ArchiveRecord::remove(&self.conn, archive_id)?;
+        self.clear_cache();
+        Ok(removed)
+    }
+
+    /// Frees the content hash of the archive replaced by the new version of it
+    pub fn retire_archive(&mut self, archive_id: Id) -> QueryResult<usize> {
+        self.archives.map.retain(|_, id| *id != archive_id);
+        ArchiveRecord::retire(&self.conn, archive_id)
+    }
+
+    /// Attaches the books of the old archive found unchanged among the entries of the new one,
+    /// returns the number of the moved books
+    pub fn move_books(&mut self, old_id: Id, entries: &HashMap<String, Book>) -> QueryResult<usize> {
+        let mut moved = 0;
+        for book in BookRecord::load_for_archive(&self.conn, old_id)? {
+            let entry = entries.get(&book.book_file)
+                .filter(|entry| entry.book_crc32 == book.book_crc32 && entry.book_size == book.book_size);
+            if let Some(entry) = entry {
+                BookRecord::move_to(&self.conn, book.id, entry)?;
+                moved += 1;
+            }
+        }
+        self.books.map.clear();
+        Ok(moved)
+    }
+
+    /// The removed rows could be cached
+    fn clear_cache(&mut self) {
+        self.archives.map.clear();
+        self.books.map.clear();
+        self.authors.map.clear();
+        self.author_links.map.clear();
+        self.titles.map.clear();
+        self.title_links.map.clear();
+        self.genres.map.clear();
+        self.genre_links.map.clear();
+        self.documents.map.clear();
+        self.publications.map.clear();
+        self.isbns.map.clear();
+        self.originals.map.clear();
+        self.series.map.clear();
+        self.series_links.map.clear();
+    }
+
     pub fn save_book(&mut self, arc_id: Id, file: &ZipFile) -> SaveResult {
         self.books.save::<BookRecord>(&self.conn, Book::new(arc_id, file))
     }
 
+    pub fn save_book_record(&mut self, book: Book) -> SaveResult {
+        self.books.save::<BookRecord>(&self.conn, book)
+    }
+
+    pub fn save_lang(&self, book_id: Id, lang: &str) {
+        BookRecord::set_lang(&self.conn, book_id, lang).expect(&format!("Failed to set language of book {}", book_id));
+    }
+
+    /// Marks the book deleted from the library, the book stays for the shelves and the annotations
+    pub fn mark_deleted(&self, book_id: Id) {
+        BookRecord::set_deleted(&self.conn, book_id, true).expect(&format!("Failed to mark book {} deleted", book_id));
+    }
+
+    pub fn save_title(&mut self, book_id: Id, title: Title) {
+        let id = self.titles.save::<TitleRecord>(&self.conn, title).get_id();
+        self.title_links.save::<TitleLinkRecord>(&self.conn, TitleLink::new(book_id, id));
+    }
+
+    /// The merged name variants, the same FB2 author id and the same folded name
+    /// resolve to the known author, only the new ones are added
+    fn resolve_author(&mut self, author: Author) -> Id {
+        if let Some(&id) = self.authors.map.get(&author) {
+            self.authors.count += 1;
+            self.authors.hits += 1;
+            return id;
+        }
+        let known = AuthorAliasRecord::find_author(&self.conn, &author)
+            .or_else(|| AuthorRecord::find_variant(&self.conn, &author));
+        match known {
+            Some(id) => {
+                self.authors.count += 1;
+                self.authors.quered += 1;
+                self.authors.map.insert(author, id);
+                id
+            }
+            None => self.authors.save::<AuthorRecord>(&self.conn, author).get_id(),
+        }
+    }
+
+    pub fn save_author(&mut self, book_id: Id, author: Author) {
+        let id = self.resolve_author(author);
+        self.author_links.save::<AuthorLinkRecord>(&self.conn, AuthorLink::new(book_id, id));
+    }
+
+    pub fn save_translator(&mut self, book_id: Id, translator: Author) {
+        let id = self.resolve_author(translator);
+        self.author_links.save::<AuthorLinkRecord>(&self.conn, AuthorLink::with_role(book_id, id, AuthorRole::Translator));
+    }
+
+    pub fn save_genre(&mut self, book_id: Id, genre: Genre) {
+        let id = self.genres.save::<GenreRecord>(&self.conn, genre).get_id();
+        self.genre_links.save::<GenreLinkRecord>(&self.conn, GenreLink::new(book_id, id));
+    }
+
+    pub fn save_series(&mut self, book_id: Id, series: Series, serno: i32) {
+        if series.series_name.is_empty() {
+            return;
+        }
+        let id = self.series.save::<SeriesRecord>(&self.conn, series).get_id();
+        self.series_links.save::<SeriesLinkRecord>(&self.conn, SeriesLink::new(book_id, id, serno));
+    }
+
     pub fn save_content(&mut self, book_id: Id, fb2: &FictionBook) {
-        let conn = &self.conn;
+        if let Some(ref lang) = fb2.description.title_info.lang {
+            self.save_lang(book_id, &lang.text.trim().to_lowercase());
+        }
         if let Some(ref title) = fb2.description.title_info.book_title {
-            {
-                let id = self.titles.save::<TitleRecord>(conn, Title::from(title)).get_id();
-                self.title_links.save::<TitleLinkRecord>(conn, TitleLink::new(book_id, id));    
-            }
+            self.save_title(book_id, Title::from(title));
             for author in &fb2.get_authors() {
-                let id = self.authors.save::<AuthorRecord>(&self.conn, Author::from(author)).get_id();
-                self.author_links.save::<AuthorLinkRecord>(conn, AuthorLink::new(book_id, id));
+                self.save_author(book_id, Author::from(author));
+            }
+            for translator in &fb2.get_translators() {
+                self.save_translator(book_id, Author::from(translator));
             }
             for genre in &fb2.get_genres() {
-                let id = self.genres.save::<GenreRecord>(conn, Genre::from(genre)).get_id();
-                self.genre_links.save::<GenreLinkRecord>(conn, GenreLink::new(book_id, id));
+                self.save_genre(book_id, Genre::from(genre));
+            }
+            for sequence in &fb2.description.title_info.sequences {
+                let serno = sequence.number.as_ref().map(|number| SeriesLink::parse_serno(number)).unwrap_or_default();
+                self.save_series(book_id, Series::from(sequence), serno);
+            }
+            if let Some(original) = Original::from(book_id, fb2) {
+                self.originals.save::<OriginalRecord>(&self.conn, original);
+            }
+            self.save_document(book_id, Document::from_fb2(book_id, fb2));
+            if let Some(ref info) = fb2.description.publish_info {
+                self.save_publication(book_id, info);
             }
         }
     }
 
+    pub fn save_publication(&mut self, book_id: Id, info: &fb2parser::PublishInfo) {
+        self.publications.save::<PublicationRecord>(&self.conn, Publication::from(book_id, info));
+        for text in info.isbn.iter() {
+            for isbn in Isbn::parse(book_id, &text.text) {
+                self.isbns.save::<IsbnRecord>(&self.conn, isbn);
+            }
+        }
+    }
+
+    pub fn save_document(&mut self, book_id: Id, mut document: Document) -> Id {
+        document.canon_id = DocumentRecord::find_canon(&self.conn, &document).unwrap_or(book_id);
+        let canon_id = document.canon_id;
+        self.documents.save::<DocumentRecord>(&self.conn, document);
+        canon_id
+    }
+
+    pub fn rebuild_documents(&self) -> QueryResult<usize> {
+        DocumentRecord::rebuild(&self.conn)
+    }
+
+    /// Recalculates the navigation keys of the authors, the titles and the series, returns the counts
+    pub fn reindex(&self) -> QueryResult<(usize, usize, usize)> {
+        use crate::diesel::Connection;
+        self.conn.transaction(|| {
+            Ok((AuthorRecord::reindex(&self.conn)?, TitleRecord::reindex(&self.conn)?, SeriesRecord::reindex(&self.conn)?))
+        })
+    }
+
+    pub fn find_author_duplicates(&self) -> QueryResult<Vec<Vec<AuthorRecord>>> {
+        AuthorAliasRecord::find_duplicates(&self.conn)
+    }
+
+    pub fn merge_authors(&mut self, author: Id, alias: Id) -> QueryResult<()> {
+        // The merged record id could be cached for the alias name
+        self.authors.map.retain(|_, id| *id != alias);
+        AuthorAliasRecord::merge(&self.conn, author, alias)
+    }
+
+    /// Adds the user or changes the password of the existing one, returns the user id
+    pub fn save_user(&self, name: &str, password: &str) -> QueryResult<Id> {
+        let user = User::new(name, password);
+        match UserRecord::find(&self.conn, &user) {
+            Ok(id) => UserRecord::set_password(&self.conn, id, password).map(|_| id),
+            Err(_) => {
+                UserRecord::save(&self.conn, &user)?;
+                UserRecord::find(&self.conn, &user)
+            }
+        }
+    }
+
+    pub fn remove_user(&self, name: &str) -> QueryResult<usize> {
+        let user = UserRecord::find_by_name(&self.conn, name)?;
+        UserRecord::remove(&self.conn, user.id)
+    }
+
+    /// The transactions of the records started inside become the savepoints of this one
+    pub fn begin_transaction(&self) {
+        use crate::diesel::connection::{Connection, TransactionManager};
+        self.conn.transaction_manager().begin_transaction(&self.conn).expect("Can't begin transaction");
+    }
+
+    pub fn commit_transaction(&self) {
+        use crate::diesel::connection::{Connection, TransactionManager};
+        self.conn.transaction_manager().commit_transaction(&self.conn).expect("Can't commit transaction");
+    }
+
+    /// The rows saved by the transaction could be cached
+    pub fn rollback_transaction(&mut self) {
+        use crate::diesel::connection::{Connection, TransactionManager};
+        self.conn.transaction_manager().rollback_transaction(&self.conn).expect("Can't rollback transaction");
+        self.clear_cache();
+    }
+
 }
\ No newline at end of file
@@ -0,0 +1,386 @@
+use std::io::{self, Read, Write};
+use std::fs;
+use std::path::Path;
+use diesel::sql_query;
+use diesel::sql_types::{BigInt, Bool, Integer, Text};
+use diesel::RunQueryDsl;
+
+use crate::models::{Author, Genre, Title, Series, ArchiveRecord, SourceKind, SqliteConnection, QueryResult, Id};
+
+pub const FIELD_SEPARATOR: char = '\x04';
+pub const STRUCTURE_INFO: &str = "structure.info";
+pub const COLLECTION_INFO: &str = "collection.info";
+pub const VERSION_INFO: &str = "version.info";
+pub const DEFAULT_STRUCTURE: &str = "AUTHOR;GENRE;TITLE;SERIES;SERNO;FILE;SIZE;LIBID;DEL;EXT;DATE;LANG;LIBRATE;KEYWORDS;";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct InpStructure {
+    pub fields: Vec<String>,
+}
+impl InpStructure {
+    pub fn new(structure: &str) -> Self {
+        Self {
+            fields: structure
+                .trim()
+                .split(';')
+                .map(|field| field.trim().to_uppercase())
+                .filter(|field| !field.is_empty())
+                .collect()
+        }
+    }
+
+    fn position(&self, name: &str) -> Option<usize> {
+        self.fields.iter().position(|field| field == name)
+    }
+}
+impl Default for InpStructure {
+    fn default() -> Self {
+        Self::new(DEFAULT_STRUCTURE)
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct InpRecord {
+    pub authors: Vec<Author>,
+    pub genres: Vec<String>,
+    pub title: String,
+    pub series: String,
+    pub serno: String,
+    pub file: String,
+    pub size: i64,
+    pub libid: String,
+    pub deleted: bool,
+    pub ext: String,
+    pub date: String,
+    pub lang: String,
+}
+impl InpRecord {
+    pub fn parse(structure: &InpStructure, line: &str) -> Option<Self> {
+        let values: Vec<&str> = line.trim_end_matches(['\r', '\n']).split(FIELD_SEPARATOR).collect();
+        let get = |name: &str| -> String {
+            structure.position(name)
+                .and_then(|pos| values.get(pos))
+                .map(|value| value.trim().to_string())
+                .unwrap_or_default()
+        };
+
+        let file = get("FILE");
+        if file.is_empty() {
+            return None;
+        }
+        let ext = get("EXT");
+        Some(Self {
+            authors: Self::parse_authors(&get("AUTHOR")),
+            genres: Self::parse_list(&get("GENRE")),
+            title: get("TITLE"),
+            series: get("SERIES"),
+            serno: get("SERNO"),
+            file,
+            size: get("SIZE").parse().unwrap_or_default(),
+            libid: get("LIBID"),
+            deleted: get("DEL") == "1",
+            ext: if ext.is_empty() { String::from("fb2") } else { ext },
+            date: get("DATE"),
+            lang: get("LANG"),
+        })
+    }
+
+    fn parse_list(value: &str) -> Vec<String> {
+        value.split(':')
+            .map(|item| item.trim())
+            .filter(|item| !item.is_empty())
+            .map(|item| item.to_string())
+            .collect()
+    }
+
+    fn parse_authors(value: &str) -> Vec<Author> {
+        Self::parse_list(value).iter().map(|author| {
+            let mut names = author.split(',').map(|name| name.trim().to_string());
+            let last_name = names.next().unwrap_or_default();
+            let first_name = names.next().unwrap_or_default();
+            let middle_name = names.next().unwrap_or_default();
+            Author {
+                first_name,
+                middle_name,
+                last_name,
+                nickname: String::new(),
+                uuid: String::new(),
+            }
+        }).collect()
+    }
+
+    pub fn get_book_file(&self) -> String {
+        format!("{}.{}", self.file, self.ext)
+    }
+
+    pub fn get_title(&self) -> Title {
+        Title { book_title: self.title.clone() }
+    }
+
+    pub fn get_series(&self) -> Option<Series> {
+        if self.series.is_empty() {
+            None
+        } else {
+            Some(Series { series_name: self.series.clone() })
+        }
+    }
+
+    pub fn get_genres(&self) -> Vec<Genre> {
+        self.genres.iter().map(|genre| Genre { genre_name: genre.clone() }).collect()
+    }
+
+    /// Formats the record according to the DEFAULT_STRUCTURE
+    pub fn to_line(&self) -> String {
+        let authors: String = self.authors.iter()
+            .map(|author| format!("{},{},{}:", author.last_name, author.first_name, author.middle_name))
+            .collect();
+        let genres: String = self.genres.iter()
+            .map(|genre| format!("{}:", genre))
+            .collect();
+        let fields = vec![
+            authors,
+            genres,
+            self.title.clone(),
+            self.series.clone(),
+            self.serno.clone(),
+            self.file.clone(),
+            self.size.to_string(),
+            self.libid.clone(),
+            String::from(if self.deleted { "1" } else { "0" }),
+            self.ext.clone(),
+            self.date.clone(),
+            self.lang.clone(),
+            String::new(),
+            String::new(),
+        ];
+        let separator = FIELD_SEPARATOR.to_string();
+        format!("{}{}\r\n", fields.join(&separator), separator)
+    }
+}
+
+/// The content of the single .inp file from the INPX collection
+#[derive(Debug, Clone)]
+pub struct InpFile {
+    pub arch_name: String,
+    pub records: Vec<InpRecord>,
+}
+
+pub fn load_inpx(path: &Path) -> io::Result<Vec<InpFile>> {
+    let file = fs::File::open(path)?;
+    let mut archive = zip::ZipArchive::new(file)?;
+
+    let structure = match archive.by_name(STRUCTURE_INFO) {
+        Ok(mut info) => {
+            let mut content = String::new();
+            info.read_to_string(&mut content)?;
+            InpStructure::new(&content)
+        },
+        Err(_) => InpStructure::default(),
+    };
+
+    let mut result = Vec::new();
+    for i in 0..archive.len() {
+        let mut inp = archive.by_index(i)?;
+        let name = String::from(inp.name());
+        if !name.to_lowercase().ends_with(".inp") {
+            continue;
+        }
+        let mut content = Vec::new();
+        inp.read_to_end(&mut content)?;
+        let records = String::from_utf8_lossy(&content)
+            .lines()
+            .filter_map(|line| InpRecord::parse(&structure, line))
+            .collect();
+        let stem = Path::new(&name).file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+        result.push(InpFile {
+            arch_name: format!("{}.zip", stem),
+            records,
+        });
+    }
+    Ok(result)
+}
+
+#[derive(QueryableByName, Debug, Clone)]
+pub struct InpView {
+    #[sql_type = "Integer"] pub book_id: Id,
+    #[sql_type = "Text"] pub book_file: String,
+    #[sql_type = "BigInt"] pub book_size: i64,
+    #[sql_type = "Text"] pub book_loaded: String,
+    #[sql_type = "Text"] pub book_lang: String,
+    #[sql_type = "Bool"] pub book_deleted: bool,
+    #[sql_type = "Text"] pub book_title: String,
+    #[sql_type = "Text"] pub authors: String,
+    #[sql_type = "Text"] pub genres: String,
+    #[sql_type = "Text"] pub series: String,
+    #[sql_type = "Integer"] pub serno: i32,
+}
+impl InpView {
+    pub fn load_for_archive(conn: &SqliteConnection, archive: Id) -> QueryResult<Vec<Self>> {
+        let query = format!(
+            r#"
+            SELECT
+                books.id AS book_id,
+                book_file,
+                book_size,
+                book_loaded,
+                book_lang,
+                book_deleted,
+                ifnull((SELECT book_title FROM titles_view WHERE titles_view.book_id = books.id), '') AS book_title,
+                ifnull((SELECT group_concat(last_name || ',' || first_name || ',' || middle_name, ':')
+                        FROM authors_view WHERE authors_view.book_id = books.id), '') AS authors,
+                ifnull((SELECT group_concat(genre_name, ':')
+                        FROM genres_view WHERE genres_view.book_id = books.id), '') AS genres,
+                ifnull((SELECT series_name FROM series_links JOIN series ON (series.id = series_links.series_id)
+                        WHERE series_links.book_id = books.id ORDER BY series_links.id LIMIT 1), '') AS series,
+                ifnull((SELECT serno FROM series_links
+                        WHERE series_links.book_id = books.id ORDER BY series_links.id LIMIT 1), 0) AS serno
+            FROM books
+            WHERE arch_id = {archive}
+            ORDER BY book_file
+            "#,
+            archive = archive
+        );
+        sql_query(&query).load(conn)
+    }
+
+    pub fn to_record(&self) -> InpRecord {
+        let (file, ext) = match self.book_file.rfind('.') {
+            Some(pos) => (&self.book_file[..pos], &self.book_file[pos + 1..]),
+            None => (self.book_file.as_str(), ""),
+        };
+        let libid = if !file.is_empty() && file.chars().all(|c| c.is_ascii_digit()) {
+            String::from(file)
+        } else {
+            self.book_id.to_string()
+        };
+        InpRecord {
+            authors: InpRecord::parse_authors(&self.authors),
+            genres: InpRecord::parse_list(&self.genres),
+            title: self.book_title.clone(),
+            series: self.series.clone(),
+            serno: if self.serno > 0 { self.serno.to_string() } else { String::new() },
+            file: String::from(file),
+            size: self.book_size,
+            libid,
+            deleted: self.book_deleted,
+            ext: String::from(ext),
+            date: self.book_loaded.chars().take(10).collect(),
+            // The earlier loads accepted only the russian books and didn't keep the language
+            lang: if self.book_lang.is_empty() { String::from("ru") } else { self.book_lang.clone() },
+        }
+    }
+}
+
+pub fn export_inpx(conn: &SqliteConnection, path: &Path, name: &str) -> io::Result<Vec<InpFile>> {
+    let to_io = |e: diesel::result::Error| io::Error::other(e);
+
+    let mut catalog = Vec::new();
+    for archive in ArchiveRecord::load_all(conn).map_err(to_io)? {
+        if SourceKind::parse(&archive.arch_kind) != SourceKind::Zip {
+            continue;
+        }
+        let records = InpView::load_for_archive(conn, archive.id).map_err(to_io)?
+            .iter()
+            .map(InpView::to_record)
+            .collect();
+        catalog.push(InpFile {
+            arch_name: archive.arch_name.clone(),
+            records,
+        });
+    }
+
+    let file = fs::File::create(path)?;
+    let mut inpx = zip::ZipWriter::new(file);
+    let options = zip::write::FileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+
+    let file_name = path.file_name().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+    inpx.start_file(COLLECTION_INFO, options)?;
+    write!(inpx, "{}\r\n{}\r\n0\r\n{}\r\n", name, file_name, name)?;
+    inpx.start_file(VERSION_INFO, options)?;
+    write!(inpx, "{}\r\n", chrono::Local::now().format("%Y%m%d"))?;
+    inpx.start_file(STRUCTURE_INFO, options)?;
+    write!(inpx, "{}\r\n", DEFAULT_STRUCTURE)?;
+
+    for inp in catalog.iter() {
+        let stem = Path::new(&inp.arch_name).file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+        inpx.start_file(format!("{}.inp", stem), options)?;
+        for record in inp.records.iter() {
+            inpx.write_all(record.to_line().as_bytes())?;
+        }
+    }
+    inpx.finish()?;
+    Ok(catalog)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_default_structure() {
+        let line = "Калбазов,Константин,Геннадьевич:Иванов,Иван,:\x04sf_fantasy:sf_action:\x04Пограничник\x04Пограничник\x042\x04172403\x04651241\x04172403\x040\x04fb2\x042009-10-21\x04ru\x04\x04\x04\r\n";
+        let record = InpRecord::parse(&InpStructure::default(), line).unwrap();
+        assert_eq!(record.authors.len(), 2);
+        assert_eq!(record.authors[0].last_name, "Калбазов");
+        assert_eq!(record.authors[0].first_name, "Константин");
+        assert_eq!(record.authors[0].middle_name, "Геннадьевич");
+        assert_eq!(record.authors[1].middle_name, "");
+        assert_eq!(record.genres, vec!["sf_fantasy", "sf_action"]);
+        assert_eq!(record.title, "Пограничник");
+        assert_eq!(record.serno, "2");
+        assert_eq!(record.get_book_file(), "172403.fb2");
+        assert_eq!(record.size, 651241);
+        assert!(!record.deleted);
+        assert_eq!(record.lang, "ru");
+    }
+
+    #[test]
+    fn test_parse_custom_structure() {
+        let structure = InpStructure::new("FILE;EXT;TITLE;DEL;");
+        let record = InpRecord::parse(&structure, "12345\x04fb2\x04Title\x041").unwrap();
+        assert_eq!(record.get_book_file(), "12345.fb2");
+        assert_eq!(record.title, "Title");
+        assert!(record.deleted);
+        assert!(record.authors.is_empty());
+    }
+
+    #[test]
+    fn test_to_line_roundtrip() {
+        let line = "Калбазов,Константин,Геннадьевич:\x04sf_fantasy:\x04Пограничник\x04\x04\x04172403\x04651241\x04172403\x040\x04fb2\x04\x04ru\x04\x04\x04\r\n";
+        let record = InpRecord::parse(&InpStructure::default(), line).unwrap();
+        assert_eq!(record.to_line(), line);
+    }
+
+    #[test]
+    fn test_to_record() {
+        let view = InpView {
+            book_id: 7,
+            book_file: String::from("172403.fb2"),
+            book_size: 651241,
+            book_loaded: String::from("2009-10-21 12:30:00"),
+            book_lang: String::from("uk"),
+            book_deleted: true,
+            book_title: String::from("Пограничник"),
+            authors: String::from("Калбазов,Константин,Геннадьевич"),
+            genres: String::from("sf_fantasy"),
+            series: String::new(),
+            serno: 0,
+        };
+        let record = view.to_record();
+        assert_eq!(record.libid, "172403");
+        assert_eq!(record.ext, "fb2");
+        assert_eq!(record.date, "2009-10-21");
+        assert_eq!(record.lang, "uk");
+        assert!(record.deleted);
+
+        let record = InpView { book_loaded: String::new(), book_lang: String::new(), book_deleted: false, ..view }.to_record();
+        assert_eq!(record.date, "");
+        assert_eq!(record.lang, "ru");
+        assert!(!record.deleted);
+    }
+
+    #[test]
+    fn test_parse_without_file() {
+        assert_eq!(InpRecord::parse(&InpStructure::default(), ""), None);
+    }
+}
@@ -4,6 +4,16 @@ extern crate clap;
 extern crate zip;
 extern crate md5;
 extern crate sanitize_filename;
+extern crate crc32fast;
+extern crate chrono;
+extern crate sha1;
+extern crate rand;
+extern crate base64;
+extern crate toml;
+extern crate hmac;
+extern crate pbkdf2;
+#[macro_use]
+extern crate lazy_static;
 
 #[macro_use]
 extern crate diesel;
@@ -14,4 +24,12 @@ pub mod schema_views;
 pub mod schema;
 pub mod models;
 pub mod actions;
-pub mod parser;
\ No newline at end of file
+pub mod parser;
+pub mod inpx;
+pub mod normalize;
+pub mod auth;
+pub mod service_error;
+pub mod config;
+pub mod loader;
+pub mod worker;
+pub mod verifier;
@@ -0,0 +1,590 @@
+use fb2parser::FictionBook;
+use std::{fs, io, path, thread};
+use std::convert::TryFrom;
+use std::collections::{HashMap, HashSet};
+use std::io::Read;
+use std::sync::{mpsc, Arc, Mutex};
+use crate::database;
+use crate::models::{Archive, ArchiveRecord, Book, Document, Id, Load, QueryResult, SeriesLink, SourceKind, PREFIX_HASH, PREFIX_INPX};
+use crate::parser;
+use crate::inpx;
+
+/// The error messages kept for the report, the rest are only counted and printed
+pub const MAX_ERRORS: usize = 100;
+
+/// Loads the archives, the books and the INPX catalogs into the database,
+/// used by fb2loader and by the admin pages of fb2service
+pub struct Loader {
+    pub manager: database::Manager,
+    russian: HashSet<String>,
+    pub total_counter: usize,
+    pub error_counter: usize,
+    pub skip_counter: usize,
+    pub mismatch_counter: usize,
+    pub errors: Vec<String>,
+    progress: Option<Box<dyn FnMut(usize, usize) + Send>>,
+    archive_transactions: bool,
+}
+impl Loader {
+    pub fn new(manager: database::Manager) -> Self {
+        Self {
+            manager,
+            russian: vec!["ru", "rus", "russian", "ru-ru"]
+                .into_iter()
+                .map(String::from)
+                .collect(),
+            total_counter: 0,
+            error_counter: 0,
+            skip_counter: 0,
+            mismatch_counter: 0,
+            errors: Vec::new(),
+            progress: None,
+            archive_transactions: false,
+        }
+    }
+
+    /// Sets the callback receiving the processed and the total books of the loaded archive
+    pub fn set_progress<F: FnMut(usize, usize) + Send + 'static>(&mut self, progress: F) {
+        self.progress = Some(Box::new(progress));
+    }
+
+    /// Commits each archive on its own, so the failed load keeps the archives loaded before it
+    pub fn set_archive_transactions(&mut self, enabled: bool) {
+        self.archive_transactions = enabled;
+    }
+
+    fn report_progress(&mut self, done: usize, total: usize) {
+        if let Some(ref mut progress) = self.progress {
+            progress(done, total);
+        }
+    }
+
+    fn report_error(&mut self, message: String) {
+        log::error!("{}", message);
+        self.error_counter += 1;
+        if self.errors.len() < MAX_ERRORS {
+            self.errors.push(message);
+        }
+    }
+
+    pub fn load(&mut self, path: &path::Path) {
+        if path.is_dir() {
+            self.load_directory(path);
+        } else {
+            match SourceKind::detect(path) {
+                Some(kind) => self.load_archive(path, kind),
+                None => log::warn!("Unsupported source: {}", path.to_string_lossy()),
+            }
+        }
+    }
+
+    fn load_archive(&mut self, path: &path::Path, kind: SourceKind) {
+        if self.archive_transactions {
+            self.manager.begin_transaction();
+        }
+        match kind {
+            SourceKind::Fb2 => self.load_fb2(path),
+            kind => self.load_zip(path, kind),
+        }
+        if self.archive_transactions {
+            self.manager.commit_transaction();
+        }
+    }
+
+    fn load_directory(&mut self, path: &path::Path) {
+        let entries = match fs::read_dir(path) {
+            Ok(entries) => entries,
+            Err(err) => {
+                self.report_error(format!("{} : {}", path.to_string_lossy(), err));
+                return;
+            }
+        };
+        let mut entries: Vec<path::PathBuf> = entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .collect();
+        entries.sort();
+        for entry in entries {
+            if entry.is_dir() || SourceKind::detect(&entry).is_some() {
+                self.load(&entry);
+            }
+        }
+    }
+
+    /// Saves the archive, the archive imported from INPX before it was found is completed instead.
+    /// The incomplete archive is hashed by the first megabyte only, 'fb2loader rehash' hashes it fully.
+    fn save_archive(&mut self, path: &path::Path, kind: SourceKind, complete: bool) -> Option<Id> {
+        let (head, uuid) = match fs::File::open(path).and_then(|mut file| hash_content(&mut file, complete)) {
+            Ok(checksums) => checksums,
+            Err(err) => {
+                self.report_error(format!("{} : {}", path.to_string_lossy(), err));
+                return None;
+            }
+        };
+        let uuid = if complete { uuid } else { format!("{}{}", PREFIX_HASH, head) };
+        let archive = Archive::new(path, head, uuid, kind);
+        if complete {
+            if self.manager.upgrade_archive(&archive) > 0 {
+                log::info!("Archive checksum upgraded to the full one: {}", archive.arch_uuid);
+            }
+        } else {
+            match self.manager.find_archive_by_head(&archive) {
+                Ok(Some(id)) => {
+                    log::info!("Archive already loaded into DB record id is {}", id);
+                    return Some(id);
+                },
+                Ok(None) => {},
+                Err(err) => {
+                    self.report_error(format!("{} : {}", path.to_string_lossy(), err));
+                    return None;
+                }
+            }
+        }
+        match self.manager.complete_archive(archive.clone()) {
+            Ok(Some(id)) => {
+                log::info!("Archive imported from INPX is found, DB record id is {}", id);
+                return Some(id);
+            },
+            Ok(None) => {},
+            Err(err) => {
+                self.report_error(format!("{} : {}", path.to_string_lossy(), err));
+                return None;
+            }
+        }
+        match self.manager.save_archive(archive) {
+            database::SaveResult::CacheHit(id) => {
+                log::info!("Archive already loaded into DB record id is {}", id);
+                Some(id)
+            },
+            database::SaveResult::Quered(id) => {
+                log::info!("Archive already loaded into DB record id is {}", id);
+                Some(id)
+            },
+            database::SaveResult::Added(id) => {
+                Some(id)
+            }
+        }
+    }
+
+    /// Checks the book of the archive loaded with the other CRC: the book imported from INPX before
+    /// the archive was found gets the entry data, the book with the different CRC is reported.
+    /// Returns true when the book is loaded already.
+    fn check_loaded(&mut self, entry: &Book) -> bool {
+        let book = match self.manager.find_book_by_file(entry.arch_id, &entry.book_file) {
+            Ok(Some(book)) => book,
+            Ok(None) => return false,
+            Err(err) => {
+                self.report_error(format!("{} : {}", entry.book_file, err));
+                return true;
+            }
+        };
+        if 0 == book.book_crc32 {
+            if book.book_size != entry.book_size {
+                log::warn!("{} : size {} doesn't match catalog size {}", entry.book_file, entry.book_size, book.book_size);
+                self.mismatch_counter += 1;
+            }
+            if let Err(err) = self.manager.fill_book(book.id, entry) {
+                self.report_error(format!("{} : {}", entry.book_file, err));
+            }
+        } else if book.book_crc32 != entry.book_crc32 {
+            log::warn!("{} : CRC {:08X} doesn't match loaded CRC {:08X}", entry.book_file, entry.book_crc32, book.book_crc32);
+            self.mismatch_counter += 1;
+        }
+        true
+    }
+
+    fn is_accepted(&self, fb: &FictionBook) -> bool {
+        let lang = if let Some(ref el) = fb.description.title_info.lang {
+            &el.text
+        } else {
+            "ru"
+        }.to_lowercase();
+        self.russian.contains(&lang)
+    }
+
+    fn load_zip(&mut self, path: &path::Path, kind: SourceKind) {
+        log::info!("Loading archive: {}", path.to_string_lossy());
+        let mut archive = match fs::File::open(path).map_err(|e| e.to_string())
+            .and_then(|file| zip::ZipArchive::new(file).map_err(|e| e.to_string())) {
+            Ok(archive) => archive,
+            Err(err) => {
+                self.report_error(format!("{} : {}", path.to_string_lossy(), err));
+                return;
+            }
+        };
+        let arch_id = match self.save_archive(path, kind, true) {
+            Some(arch_id) => arch_id,
+            None => return,
+        };
+        self.total_counter += archive.len();
+
+        for i in 0..archive.len() {
+            self.report_progress(i, archive.len());
+            let mut zip_file = match archive.by_index(i) {
+                Ok(zip_file) => zip_file,
+                Err(err) => {
+                    self.report_error(format!("{} : {}", path.to_string_lossy(), err));
+                    continue;
+                }
+            };
+            if self.manager.find_book(arch_id, zip_file.name(), zip_file.crc32() as i64).is_some() {
+                continue
+            }
+            if self.check_loaded(&Book::new(arch_id, &zip_file)) {
+                continue
+            }
+            if let Some(header) = parser::load_header(&mut zip_file)
+            {
+                match FictionBook::try_from(header.as_bytes()) {
+                    Ok(fb) => {
+                        if self.is_accepted(&fb) {
+                            let book_id = self.manager.save_book(arch_id, &zip_file).get_id();
+                            self.manager.save_content(book_id, &fb);
+                        } else {
+                            self.skip_counter += 1;
+                        }
+                    },
+                    Err(err) =>  {
+                        let message = format!("{} : {:?} '{}'", zip_file.name(), err, header);
+                        self.report_error(message);
+                    }
+                }
+            }
+        }
+        self.report_progress(archive.len(), archive.len());
+        self.manager.finish_archive(arch_id);
+    }
+
+    fn read_zip_entries(arch_id: Id, path: &path::Path) -> HashMap<String, Book> {
+        let mut entries = HashMap::new();
+        if let Some(mut archive) = fs::File::open(path).ok().and_then(|file| zip::ZipArchive::new(file).ok()) {
+            for i in 0..archive.len() {
+                if let Ok(zip_file) = archive.by_index(i) {
+                    entries.insert(String::from(zip_file.name()), Book::new(arch_id, &zip_file));
+                }
+            }
+        }
+        entries
+    }
+
+    pub fn import_inpx(&mut self, inpx: &path::Path, home: &path::Path) {
+        let catalog = match inpx::load_inpx(inpx) {
+            Ok(catalog) => catalog,
+            Err(err) => {
+                self.report_error(format!("Can't read INPX catalog '{}' : {}", inpx.to_string_lossy(), err));
+                return;
+            }
+        };
+
+        for inp in catalog {
+            let path = home.join(&inp.arch_name);
+            log::info!("Importing archive: {}", path.to_string_lossy());
+            self.manager.begin_transaction();
+            let arch_id = if path.exists() {
+                self.save_archive(&path, SourceKind::Zip, false)
+            } else {
+                log::warn!("Archive {} not found, books will be imported without CRC check", inp.arch_name);
+                let uuid = format!("{}{}", PREFIX_INPX, inp.arch_name);
+                Some(self.manager.save_archive(Archive::new(&path, String::new(), uuid, SourceKind::Zip)).get_id())
+            };
+            let arch_id = match arch_id {
+                Some(arch_id) => arch_id,
+                None => {
+                    self.manager.rollback_transaction();
+                    continue;
+                }
+            };
+            let entries = Self::read_zip_entries(arch_id, &path);
+
+            for record in inp.records {
+                self.total_counter += 1;
+                let lang = if record.lang.is_empty() { String::from("ru") } else { record.lang.to_lowercase() };
+                if record.deleted {
+                    if let Ok(Some(book)) = self.manager.find_book_by_file(arch_id, &record.get_book_file()) {
+                        self.manager.mark_deleted(book.id);
+                    }
+                    self.skip_counter += 1;
+                    continue;
+                }
+                if !self.russian.contains(&lang) {
+                    self.skip_counter += 1;
+                    continue;
+                }
+
+                let book_file = record.get_book_file();
+                let book = match entries.get(&book_file) {
+                    Some(entry) => {
+                        if entry.book_size != record.size {
+                            log::warn!("{} : size {} doesn't match catalog size {}", book_file, entry.book_size, record.size);
+                            self.mismatch_counter += 1;
+                        }
+                        entry.clone()
+                    },
+                    None if entries.is_empty() => Book {
+                        arch_id,
+                        book_file,
+                        book_zip_size: 0,
+                        book_size: record.size,
+                        book_crc32: 0,
+                        book_offset: 0,
+                    },
+                    None => {
+                        log::warn!("{} : not found in {}", book_file, inp.arch_name);
+                        self.mismatch_counter += 1;
+                        continue;
+                    }
+                };
+
+                if self.manager.find_book(arch_id, &book.book_file, book.book_crc32).is_some() {
+                    continue
+                }
+                if !entries.is_empty() && self.check_loaded(&book) {
+                    continue
+                }
+                let book_id = self.manager.save_book_record(book).get_id();
+                self.manager.save_lang(book_id, &lang);
+                self.manager.save_title(book_id, record.get_title());
+                for author in record.authors.iter() {
+                    self.manager.save_author(book_id, author.clone());
+                }
+                for genre in record.get_genres() {
+                    self.manager.save_genre(book_id, genre);
+                }
+                if let Some(series) = record.get_series() {
+                    self.manager.save_series(book_id, series, SeriesLink::parse_serno(&record.serno));
+                }
+                self.manager.save_document(book_id, Document::new(book_id, &record.title, &record.authors));
+            }
+            self.manager.finish_archive(arch_id);
+            self.manager.commit_transaction();
+        }
+    }
+
+    /// Loads the new version of the archive: the unchanged books (the same name, size and CRC) move
+    /// to the new archive keeping their ids, the shelves and the annotations, then the rest of the old
+    /// archive is removed. Everything is rolled back if any step fails. Returns the numbers of the moved
+    /// and the removed books.
+    pub fn replace(&mut self, old_id: Id, path: &path::Path) -> Result<(usize, usize), String> {
+        let kind = match SourceKind::detect(path) {
+            Some(kind) if path.is_file() => kind,
+            _ => return Err(format!("Unsupported source: {}", path.to_string_lossy())),
+        };
+
+        self.manager.begin_transaction();
+        match self.replace_archive(old_id, path, kind) {
+            Ok(replaced) => {
+                self.manager.commit_transaction();
+                Ok(replaced)
+            },
+            Err(err) => {
+                self.manager.rollback_transaction();
+                Err(err)
+            }
+        }
+    }
+
+    fn replace_archive(&mut self, old_id: Id, path: &path::Path, kind: SourceKind) -> Result<(usize, usize), String> {
+        let name = path.to_string_lossy();
+        self.manager.retire_archive(old_id).map_err(|e| format!("{} : {}", name, e))?;
+        let arch_id = self.save_archive(path, kind, true).ok_or_else(|| format!("{} : can't save the archive", name))?;
+        let entries = if kind.is_zipped() {
+            Self::read_zip_entries(arch_id, path)
+        } else {
+            Book::from_file(arch_id, path).ok()
+                .map(|book| (book.book_file.clone(), book))
+                .into_iter()
+                .collect()
+        };
+        let moved = self.manager.move_books(old_id, &entries).map_err(|e| format!("{} : {}", name, e))?;
+
+        // The authors and the series of the old archive stay until the new books are linked to them
+        self.load(path);
+        let loaded = ArchiveRecord::load(self.manager.get_connection(), arch_id)
+            .map(|archive| archive.arch_done)
+            .map_err(|e| format!("{} : {}", name, e))?;
+        if !loaded {
+            return Err(format!("{} : the archive is not loaded", name));
+        }
+        let removed = self.manager.remove_archive(old_id).map_err(|e| format!("{} : {}", name, e))?;
+        Ok((moved, removed))
+    }
+
+    /// Replaces the first megabyte hashes of the archives loaded before the full hashes, the archives
+    /// are read by the several threads. Returns the number of the updated archives.
+    pub fn rehash(&mut self, jobs: usize) -> QueryResult<usize> {
+        let archives = ArchiveRecord::load_prefixed(self.manager.get_connection())?;
+        let paths: Vec<path::PathBuf> = archives.iter()
+            .map(|archive| self.manager.get_archive_path(archive))
+            .collect();
+        log::info!("Archives to rehash: {}", archives.len());
+
+        let mut updated = 0;
+        let results = parallel_checksums(&paths, jobs);
+        for ((archive, path), result) in archives.iter().zip(paths.iter()).zip(results) {
+            match result {
+                Ok((ref head, _)) if *head != archive.arch_head => {
+                    self.report_error(format!("{} : changed since it was loaded", path.to_string_lossy()));
+                },
+                Ok((head, uuid)) => {
+                    if ArchiveRecord::set_checksums(self.manager.get_connection(), archive.id, &head, &uuid)? > 0 {
+                        updated += 1;
+                    } else {
+                        self.report_error(format!("{} : the same content is loaded as the other archive", path.to_string_lossy()));
+                    }
+                },
+                Err(err) => {
+                    self.report_error(format!("{} : {}", path.to_string_lossy(), err));
+                }
+            }
+        }
+        Ok(updated)
+    }
+
+    fn load_fb2(&mut self, path: &path::Path) {
+        let mut file = match fs::File::open(path) {
+            Ok(file) => file,
+            Err(err) => {
+                self.report_error(format!("{} : {}", path.to_string_lossy(), err));
+                return;
+            }
+        };
+        self.total_counter += 1;
+        let book = match Book::from_file(0, path) {
+            Ok(book) => book,
+            Err(err) => {
+                self.report_error(format!("{} : {}", path.to_string_lossy(), err));
+                return;
+            }
+        };
+        match self.manager.find_book_by_content(&book) {
+            Ok(Some(id)) => {
+                log::info!("{} : the same book is already loaded into DB record id {}", path.to_string_lossy(), id);
+                self.skip_counter += 1;
+                return;
+            },
+            Ok(None) => {},
+            Err(err) => {
+                self.report_error(format!("{} : {}", path.to_string_lossy(), err));
+                return;
+            }
+        }
+        if let Some(header) = parser::load_header(&mut file)
+        {
+            match FictionBook::try_from(header.as_bytes()) {
+                Ok(fb) => {
+                    if self.is_accepted(&fb) {
+                        let arch_id = match self.save_archive(path, SourceKind::Fb2, true) {
+                            Some(arch_id) => arch_id,
+                            None => return,
+                        };
+                        let book = self.manager.save_book_record(Book { arch_id, ..book });
+                        self.manager.save_content(book.get_id(), &fb);
+                        self.manager.finish_archive(arch_id);
+                    } else {
+                        self.skip_counter += 1;
+                    }
+                },
+                Err(err) =>  {
+                    let message = format!("{} : {:?} '{}'", path.to_string_lossy(), err, header);
+                    self.report_error(message);
+                }
+            }
+        }
+    }
+}
+
+/// The size of the archive head hashed for the quick change detection
+pub const HEAD_SIZE: usize = 1024 * 1024;
+
+/// The MD5 of the first megabyte and of the whole content read in one pass, the whole content
+/// is read only when it is complete, otherwise both hashes are the head one
+pub fn hash_content<R: Read>(reader: &mut R, complete: bool) -> io::Result<(String, String)> {
+    let mut buffer = vec![0; HEAD_SIZE];
+    let mut head = md5::Context::new();
+    let mut full = md5::Context::new();
+    let mut total = 0;
+    loop {
+        let readed = match reader.read(&mut buffer) {
+            Ok(readed) => readed,
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        if 0 == readed {
+            break;
+        }
+        if total < HEAD_SIZE {
+            head.consume(&buffer[0..readed.min(HEAD_SIZE - total)]);
+        }
+        full.consume(&buffer[0..readed]);
+        total += readed;
+        if !complete && total >= HEAD_SIZE {
+            break;
+        }
+    }
+    let head = format!("{:X}", head.compute());
+    let full = if complete { format!("{:X}", full.compute()) } else { head.clone() };
+    Ok((head, full))
+}
+
+/// The head and the full hash of the file
+pub fn checksums(path: &path::Path) -> io::Result<(String, String)> {
+    hash_content(&mut fs::File::open(path)?, true)
+}
+
+/// The checksums of the files calculated by the several threads, in the order of the paths
+pub fn parallel_checksums(paths: &[path::PathBuf], jobs: usize) -> Vec<io::Result<(String, String)>> {
+    let owned: Vec<path::PathBuf> = paths.to_vec();
+    let queue = Arc::new(Mutex::new(owned.into_iter().enumerate()));
+    let (sender, receiver) = mpsc::channel();
+    let workers: Vec<thread::JoinHandle<()>> = (0..jobs.max(1))
+        .map(|_| {
+            let queue = queue.clone();
+            let sender = sender.clone();
+            thread::spawn(move || loop {
+                let next = queue.lock().map(|mut queue| queue.next()).unwrap_or(None);
+                match next {
+                    Some((i, path)) => {
+                        if sender.send((i, checksums(&path))).is_err() {
+                            break;
+                        }
+                    },
+                    None => break,
+                }
+            })
+        })
+        .collect();
+    drop(sender);
+
+    let mut results: Vec<Option<io::Result<(String, String)>>> = paths.iter().map(|_| None).collect();
+    for (i, result) in receiver {
+        results[i] = Some(result);
+    }
+    for worker in workers {
+        let _ = worker.join();
+    }
+    results.into_iter()
+        .map(|result| result.unwrap_or_else(|| Err(io::Error::other("Checksum thread failed"))))
+        .collect()
+}
+
+pub fn md5sum(path: &path::Path, complete: bool) -> io::Result<String> {
+    let (head, full) = hash_content(&mut fs::File::open(path)?, complete)?;
+    Ok(if complete { full } else { head })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_hash_content() {
+        let content: Vec<u8> = (0..HEAD_SIZE + 1000).map(|i| (i % 251) as u8).collect();
+        let head = format!("{:X}", md5::compute(&content[0..HEAD_SIZE]));
+        let full = format!("{:X}", md5::compute(&content));
+        assert_eq!(hash_content(&mut io::Cursor::new(&content), true).unwrap(), (head.clone(), full));
+        assert_eq!(hash_content(&mut io::Cursor::new(&content), false).unwrap(), (head.clone(), head));
+
+        let short = format!("{:X}", md5::compute(b"short"));
+        assert_eq!(hash_content(&mut io::Cursor::new(b"short"), true).unwrap(), (short.clone(), short));
+    }
+}
@@ -2,6 +2,47 @@ use std::path::Path;
 use crate::schema::archives;
 use super::*;
 
+#[derive(Eq, PartialEq, Hash, Clone, Copy, Debug)]
+pub enum SourceKind {
+    Zip,
+    Fb2Zip,
+    Fb2,
+}
+impl SourceKind {
+    pub fn detect(path: &Path) -> Option<Self> {
+        let name = path.file_name()?.to_string_lossy().to_lowercase();
+        if name.ends_with(".fb2.zip") {
+            Some(SourceKind::Fb2Zip)
+        } else if name.ends_with(".zip") {
+            Some(SourceKind::Zip)
+        } else if name.ends_with(".fb2") {
+            Some(SourceKind::Fb2)
+        } else {
+            None
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SourceKind::Zip => "zip",
+            SourceKind::Fb2Zip => "fb2.zip",
+            SourceKind::Fb2 => "fb2",
+        }
+    }
+
+    pub fn parse(kind: &str) -> Self {
+        match kind {
+            "fb2.zip" => SourceKind::Fb2Zip,
+            "fb2" => SourceKind::Fb2,
+            _ => SourceKind::Zip,
+        }
+    }
+
+    pub fn is_zipped(&self) -> bool {
+        *self != SourceKind::Fb2
+    }
+}
+
 #[derive(Insertable)]
 #[table_name="archives"]
 #[derive(Eq, PartialEq, Hash, Clone, Debug)]
@@ -10,9 +51,13 @@ pub struct Archive {
     pub arch_home: String,
     pub arch_size: i64,
     pub arch_uuid: String,
+    pub arch_kind: String,
+    pub arch_head: String,
+    pub root_id: Id,
 }
 impl Archive {
-    pub fn new(archive: &Path, uuid: String) -> Self {
+    /// The head is the MD5 of the first megabyte, the uuid is the MD5 of the whole archive
+    pub fn new(archive: &Path, head: String, uuid: String, kind: SourceKind) -> Self {
         use std::fs;
         Self {
             arch_name: archive.file_name()
@@ -23,6 +68,9 @@ impl Archive {
                 .to_string_lossy().to_string(),
             arch_size: fs::metadata(archive).map(|meta| meta.len()).unwrap_or_default() as i64,
             arch_uuid: uuid,
+            arch_kind: String::from(kind.as_str()),
+            arch_head: head,
+            root_id: 0,
         }
     }
 }
@@ -36,6 +84,10 @@ pub struct ArchiveRecord {
     pub arch_size: i64,
     pub arch_uuid: String,
     pub arch_done: bool,
+    pub arch_kind: String,
+    pub arch_loaded: String,
+    pub arch_head: String,
+    pub root_id: Id,
 }
 impl Record {
     pub fn find_uniq(conn: &SqliteConnection, uid: &String) -> Option<Id> {
@@ -45,8 +97,217 @@ impl Record {
         use crate::diesel::QueryDsl;
         archives.filter(arch_uuid.eq(uid)).select(id).first(conn).ok()
     }
+
+    pub fn load_all(conn: &SqliteConnection) -> QueryResult<Vec<Self>> {
+        use crate::schema::archives::dsl::*;
+        use crate::diesel::RunQueryDsl;
+        use crate::diesel::QueryDsl;
+        archives.order(arch_name).load(conn)
+    }
+
+    pub fn set_done(conn: &SqliteConnection, archive: Id) -> QueryResult<usize> {
+        use crate::schema::archives::dsl::*;
+        use crate::diesel::ExpressionMethods;
+        use crate::diesel::RunQueryDsl;
+        use crate::diesel::QueryDsl;
+        diesel::update(archives.find(archive)).set(arch_done.eq(true)).execute(conn)
+    }
+
+    /// Removes the archive with its books and every row of the books in one transaction,
+    /// returns the number of the removed books. The shelves, the annotations and the downloads
+    /// of the removed canonical book move to the remaining version of it, the authors, the titles,
+    /// the genres, the series and the tags left without the books are removed too.
+    pub fn remove(conn: &SqliteConnection, archive: Id) -> QueryResult<usize> {
+        use crate::diesel::Connection;
+        use crate::diesel::RunQueryDsl;
+        use crate::diesel::connection::SimpleConnection;
+
+        let removed = format!("SELECT id FROM books WHERE arch_id = {}", archive);
+        conn.transaction(|| {
+            conn.batch_execute(&format!(
+                r#"
+                DROP TABLE IF EXISTS temp.moved_canons;
+                CREATE TEMP TABLE moved_canons AS
+                    SELECT canon_id AS old_id, min(book_id) AS new_id
+                    FROM documents
+                    WHERE canon_id IN ({removed}) AND book_id NOT IN ({removed})
+                    GROUP BY canon_id;
+                UPDATE documents SET canon_id = (SELECT new_id FROM moved_canons WHERE old_id = documents.canon_id)
+                    WHERE canon_id IN (SELECT old_id FROM moved_canons);
+                "#,
+                removed = removed
+            ))?;
+            for table in ["shelves", "reviews", "tag_links", "downloads"].iter() {
+                diesel::sql_query(format!(
+                    r#"
+                    UPDATE OR IGNORE {table} SET book_id = (SELECT new_id FROM moved_canons WHERE old_id = {table}.book_id)
+                    WHERE book_id IN (SELECT old_id FROM moved_canons)
+                    "#,
+                    table = table
+                )).execute(conn)?;
+            }
+            for table in BOOK_TABLES.iter() {
+                diesel::sql_query(format!("DELETE FROM {} WHERE book_id IN ({})", table, removed)).execute(conn)?;
+            }
+            conn.batch_execute("DROP TABLE temp.moved_canons;")?;
+            let count = diesel::sql_query(format!("DELETE FROM books WHERE arch_id = {}", archive)).execute(conn)?;
+            diesel::sql_query(format!("DELETE FROM archives WHERE id = {}", archive)).execute(conn)?;
+            Self::remove_orphans(conn)?;
+            Ok(count)
+        })
+    }
+
+    /// Removes the rows not linked to any book, returns the number of the removed rows
+    pub fn remove_orphans(conn: &SqliteConnection) -> QueryResult<usize> {
+        use crate::diesel::RunQueryDsl;
+        let queries = [
+            "DELETE FROM authors WHERE id NOT IN (SELECT author_id FROM author_links)",
+            "DELETE FROM author_aliases WHERE author_id NOT IN (SELECT id FROM authors)",
+            "DELETE FROM titles WHERE id NOT IN (SELECT title_id FROM title_links)",
+            "DELETE FROM genres WHERE id NOT IN (SELECT genre_id FROM genre_links)",
+            "DELETE FROM series WHERE id NOT IN (SELECT series_id FROM series_links)",
+            "DELETE FROM tags WHERE id NOT IN (SELECT tag_id FROM tag_links)",
+        ];
+        let mut count = 0;
+        for query in queries.iter() {
+            count += diesel::sql_query(*query).execute(conn)?;
+        }
+        Ok(count)
+    }
+
+    /// Frees the content hash of the replaced archive, so the new version with the same content can be saved
+    pub fn retire(conn: &SqliteConnection, archive: Id) -> QueryResult<usize> {
+        use crate::schema::archives::dsl::*;
+        use crate::diesel::ExpressionMethods;
+        use crate::diesel::RunQueryDsl;
+        use crate::diesel::QueryDsl;
+        diesel::update(archives.find(archive))
+            .set((arch_uuid.eq(format!("REPLACED:{}", archive)), arch_done.eq(false)))
+            .execute(conn)
+    }
+
+    /// Sets the directory of the archive moved on disk, relative to the root unless the root is 0
+    pub fn relocate(conn: &SqliteConnection, archive: Id, root: Id, home: &str) -> QueryResult<usize> {
+        use crate::schema::archives::dsl::*;
+        use crate::diesel::ExpressionMethods;
+        use crate::diesel::RunQueryDsl;
+        use crate::diesel::QueryDsl;
+        diesel::update(archives.find(archive)).set((root_id.eq(root), arch_home.eq(home))).execute(conn)
+    }
+
+    /// Replaces the first megabyte hash of the archive loaded before the full hashes with the full one,
+    /// the archive is recognized by the name, the size and the head
+    pub fn upgrade(conn: &SqliteConnection, value: &Archive) -> QueryResult<usize> {
+        use crate::schema::archives::dsl::*;
+        use crate::diesel::ExpressionMethods;
+        use crate::diesel::RunQueryDsl;
+        use crate::diesel::QueryDsl;
+        diesel::update(archives
+            .filter(arch_uuid.eq(format!("{}{}", PREFIX_HASH, value.arch_head)))
+            .filter(arch_name.eq(&value.arch_name))
+            .filter(arch_size.eq(value.arch_size)))
+            .set(arch_uuid.eq(&value.arch_uuid))
+            .execute(conn)
+    }
+
+    /// The archive of the same name, size and head loaded before with any hash,
+    /// the archive hashed by the first megabyte only is found by it
+    pub fn find_by_head(conn: &SqliteConnection, value: &Archive) -> QueryResult<Option<Id>> {
+        use crate::schema::archives::dsl::*;
+        use crate::diesel::ExpressionMethods;
+        use crate::diesel::OptionalExtension;
+        use crate::diesel::TextExpressionMethods;
+        use crate::diesel::RunQueryDsl;
+        use crate::diesel::QueryDsl;
+        archives
+            .filter(arch_name.eq(&value.arch_name))
+            .filter(arch_size.eq(value.arch_size))
+            .filter(arch_head.eq(&value.arch_head))
+            .filter(arch_uuid.not_like("REPLACED:%"))
+            .select(id)
+            .order(id)
+            .first(conn)
+            .optional()
+    }
+
+    /// The archives loaded before the full hashes, their uuid is the hash of the first megabyte
+    pub fn load_prefixed(conn: &SqliteConnection) -> QueryResult<Vec<Self>> {
+        use crate::schema::archives::dsl::*;
+        use crate::diesel::TextExpressionMethods;
+        use crate::diesel::RunQueryDsl;
+        use crate::diesel::QueryDsl;
+        archives.filter(arch_uuid.like(format!("{}%", PREFIX_HASH))).order(id).load(conn)
+    }
+
+    pub fn count_prefixed(conn: &SqliteConnection) -> QueryResult<i64> {
+        use crate::schema::archives::dsl::*;
+        use crate::diesel::TextExpressionMethods;
+        use crate::diesel::RunQueryDsl;
+        use crate::diesel::QueryDsl;
+        archives.filter(arch_uuid.like(format!("{}%", PREFIX_HASH))).count().get_result(conn)
+    }
+
+    pub fn set_checksums(conn: &SqliteConnection, archive: Id, head: &str, uuid: &str) -> QueryResult<usize> {
+        use crate::schema::archives::dsl::*;
+        use crate::diesel::ExpressionMethods;
+        use crate::diesel::RunQueryDsl;
+        use crate::diesel::QueryDsl;
+        diesel::update(archives.find(archive)).set((arch_head.eq(head), arch_uuid.eq(uuid))).execute(conn)
+    }
+
+    /// Completes the archive imported from INPX before it was found with the checksums and the directory
+    /// of the loaded one, the archive is recognized by the name. Returns the id of the completed archive.
+    pub fn complete(conn: &SqliteConnection, value: &Archive) -> QueryResult<Option<Id>> {
+        use crate::schema::archives::dsl::*;
+        use crate::diesel::ExpressionMethods;
+        use crate::diesel::OptionalExtension;
+        use crate::diesel::RunQueryDsl;
+        use crate::diesel::QueryDsl;
+        let pending: Option<Id> = archives
+            .filter(arch_uuid.eq(format!("{}{}", PREFIX_INPX, value.arch_name)))
+            .select(id)
+            .first(conn)
+            .optional()?;
+        match pending {
+            Some(archive) => {
+                let updated = diesel::update(archives.find(archive))
+                    .set((
+                        arch_home.eq(&value.arch_home),
+                        root_id.eq(value.root_id),
+                        arch_size.eq(value.arch_size),
+                        arch_uuid.eq(&value.arch_uuid),
+                        arch_kind.eq(&value.arch_kind),
+                        arch_head.eq(&value.arch_head),
+                    ))
+                    .execute(conn)?;
+                // The same content may be loaded already as the other archive
+                Ok(Some(archive).filter(|_| updated > 0))
+            },
+            None => Ok(None),
+        }
+    }
+
+    pub fn find_by_name(conn: &SqliteConnection, name: &str) -> QueryResult<Vec<Self>> {
+        use crate::schema::archives::dsl::*;
+        use crate::diesel::ExpressionMethods;
+        use crate::diesel::RunQueryDsl;
+        use crate::diesel::QueryDsl;
+        archives.filter(arch_name.eq(name)).order(id).load(conn)
+    }
 }
 
+/// The uuid prefix of the archives loaded before the full hashes
+pub const PREFIX_HASH: &str = "PREFIX:";
+
+/// The uuid prefix of the archives imported from INPX without the archive file
+pub const PREFIX_INPX: &str = "INPX:";
+
+/// The tables referencing the books by the book_id column
+const BOOK_TABLES: &[&str] = &[
+    "author_links", "title_links", "genre_links", "series_links", "documents", "publications", "isbns",
+    "originals", "book_digests", "shelves", "reviews", "tag_links", "downloads",
+];
+
 type Base = Archive;
 type Record = ArchiveRecord;
 impl Load<Record> for Record {
@@ -63,10 +324,8 @@ impl Find<Base> for Record {
         use crate::diesel::ExpressionMethods;
         use crate::diesel::RunQueryDsl;
         use crate::diesel::QueryDsl;
+        // The same content loaded from the other path is the same archive
         archives
-            .filter(arch_name.eq(&value.arch_name))
-            .filter(arch_home.eq(&value.arch_home))
-            .filter(arch_size.eq(&value.arch_size))
             .filter(arch_uuid.eq(&value.arch_uuid))
             .select(id)
             .first(conn)
@@ -74,7 +333,10 @@ impl Find<Base> for Record {
 }
 impl Save<Base> for Record {
     fn save(conn: &SqliteConnection, value: &Base) -> QueryResult<usize> {
-        use crate::diesel::RunQueryDsl;       
-        diesel::insert_into(archives::table).values(value).execute(conn)
+        use crate::diesel::ExpressionMethods;
+        use crate::diesel::RunQueryDsl;
+        diesel::insert_into(archives::table)
+            .values((value, archives::arch_loaded.eq(timestamp())))
+            .execute(conn)
     }
 }
@@ -1,5 +1,6 @@
 use std::convert::From;
 use crate::schema::authors;
+use crate::normalize::{clean, fold, sort_key};
 use super::*;
 
 #[derive(Insertable)]
@@ -12,25 +13,50 @@ pub struct Author {
     pub nickname: String,
     pub uuid: String,
 }
+impl Author {
+    pub fn get_display_name(&self) -> String {
+        let name = vec![&self.first_name, &self.middle_name, &self.last_name]
+            .into_iter()
+            .filter(|name| !name.is_empty())
+            .cloned()
+            .collect::<Vec<String>>()
+            .join(" ");
+        if name.is_empty() {
+            self.nickname.clone()
+        } else {
+            name
+        }
+    }
+
+    /// The folded full name used to match the variants of the same author
+    pub fn get_match_key(&self) -> String {
+        fold(&format!("{} {} {} {}", self.last_name, self.first_name, self.middle_name, self.nickname))
+    }
+
+    /// The folded name without the middle name, the authors with and without middle name share it
+    pub fn get_short_key(&self) -> String {
+        fold(&format!("{} {} {}", self.last_name, self.first_name, self.nickname))
+    }
+}
 impl From<&fb2parser::Author> for Author{
     fn from(src: &fb2parser::Author) -> Self {
         Self {
-            first_name: src.get_first_name().unwrap_or_default(),
-            middle_name: src.get_middle_name().unwrap_or_default(),
-            last_name: src.get_last_name().unwrap_or_default(),
-            nickname: src.get_nickname().unwrap_or_default(),
-            uuid: src.get_id().unwrap_or_default()
+            first_name: clean(&src.get_first_name().unwrap_or_default()),
+            middle_name: clean(&src.get_middle_name().unwrap_or_default()),
+            last_name: clean(&src.get_last_name().unwrap_or_default()),
+            nickname: clean(&src.get_nickname().unwrap_or_default()),
+            uuid: clean(&src.get_id().unwrap_or_default())
         }
     }
 }
 impl From<&fb2parser::Translator> for Author{
     fn from(src: &fb2parser::Translator) -> Self {
         Self {
-            first_name: src.get_first_name().unwrap_or_default(),
-            middle_name: src.get_middle_name().unwrap_or_default(),
-            last_name: src.get_last_name().unwrap_or_default(),
-            nickname: src.get_nickname().unwrap_or_default(),
-            uuid: src.get_id().unwrap_or_default()
+            first_name: clean(&src.get_first_name().unwrap_or_default()),
+            middle_name: clean(&src.get_middle_name().unwrap_or_default()),
+            last_name: clean(&src.get_last_name().unwrap_or_default()),
+            nickname: clean(&src.get_nickname().unwrap_or_default()),
+            uuid: clean(&src.get_id().unwrap_or_default())
         }
     }
 }
@@ -45,15 +71,110 @@ pub struct AuthorRecord {
     pub nickname: String,
     pub uuid: String,
 }
+impl AuthorRecord {
+    pub fn get_author(&self) -> Author {
+        Author {
+            first_name: self.first_name.clone(),
+            middle_name: self.middle_name.clone(),
+            last_name: self.last_name.clone(),
+            nickname: self.nickname.clone(),
+            uuid: self.uuid.clone(),
+        }
+    }
+
+    pub fn load_all(conn: &SqliteConnection) -> QueryResult<Vec<Self>> {
+        use crate::schema::authors::dsl::*;
+        use crate::diesel::RunQueryDsl;
+        use crate::diesel::QueryDsl;
+        authors
+            .select((id, first_name, middle_name, last_name, nickname, uuid))
+            .order((last_name, first_name, middle_name, id))
+            .load(conn)
+    }
+
+    /// Finds the author with the same FB2 author id or the same folded name, so the variants
+    /// of the name written in the other case or with 'е' instead of 'ё' are not added again
+    pub fn find_variant(conn: &SqliteConnection, value: &Author) -> Option<Id> {
+        use crate::schema::authors::dsl::*;
+        use crate::diesel::ExpressionMethods;
+        use crate::diesel::RunQueryDsl;
+        use crate::diesel::QueryDsl;
+        if !value.uuid.is_empty() {
+            let found = authors
+                .filter(uuid.eq(&value.uuid))
+                .select(id)
+                .order(id)
+                .first(conn);
+            if let Ok(found) = found {
+                return Some(found);
+            }
+        }
+        let candidates: Vec<Self> = authors
+            .filter(last_name_key.eq(sort_key(&value.last_name)))
+            .filter(first_name_key.eq(sort_key(&value.first_name)))
+            .filter(middle_name_key.eq(sort_key(&value.middle_name)))
+            .select((id, first_name, middle_name, last_name, nickname, uuid))
+            .order(id)
+            .load(conn)
+            .ok()?;
+        let key = value.get_match_key();
+        candidates.iter()
+            .find(|record| record.get_author().get_match_key() == key)
+            .map(|record| record.id)
+    }
+
+    /// The authors loaded before the navigation keys were calculated by the loader, the names
+    /// of the whitespaces only have the empty keys as well
+    pub fn has_stale_keys(conn: &SqliteConnection) -> QueryResult<bool> {
+        use crate::schema::authors::dsl::*;
+        use crate::diesel::BoolExpressionMethods;
+        use crate::diesel::ExpressionMethods;
+        use crate::diesel::RunQueryDsl;
+        use crate::diesel::QueryDsl;
+        let names: Vec<(String, String, String, String, String, String)> = authors
+            .filter(first_name_key.eq("").and(first_name.ne(""))
+                .or(middle_name_key.eq("").and(middle_name.ne("")))
+                .or(last_name_key.eq("").and(last_name.ne(""))))
+            .select((first_name, first_name_key, middle_name, middle_name_key, last_name, last_name_key))
+            .limit(STALE_CHECK_LIMIT)
+            .load(conn)?;
+        let is_stale = |name: &str, key: &str| key.is_empty() && !sort_key(name).is_empty();
+        Ok(names.iter().any(|(first, first_key, middle, middle_key, last, last_key)| {
+            is_stale(first, first_key) || is_stale(middle, middle_key) || is_stale(last, last_key)
+        }))
+    }
+
+    /// Recalculates the navigation sort keys of the all authors
+    pub fn reindex(conn: &SqliteConnection) -> QueryResult<usize> {
+        use crate::schema::authors::dsl::*;
+        use crate::diesel::ExpressionMethods;
+        use crate::diesel::RunQueryDsl;
+        use crate::diesel::QueryDsl;
+        let mut count = 0;
+        for record in Self::load_all(conn)? {
+            count += diesel::update(authors.find(record.id))
+                .set((
+                    first_name_key.eq(sort_key(&record.first_name)),
+                    middle_name_key.eq(sort_key(&record.middle_name)),
+                    last_name_key.eq(sort_key(&record.last_name)),
+                ))
+                .execute(conn)?;
+        }
+        Ok(count)
+    }
+}
 
 type Base = Author;
 type Record = AuthorRecord;
 impl Load<Record> for Record {
     fn load(conn: &SqliteConnection, id: Id) -> QueryResult<Self> {
-        use crate::schema::authors::dsl::authors;
+        use crate::schema::authors::dsl::{authors, first_name, middle_name, last_name, nickname, uuid};
         use crate::diesel::RunQueryDsl;
         use crate::diesel::QueryDsl;
-        authors.find(id).first(conn)
+        authors
+            .find(id)
+            .select((crate::schema::authors::id, first_name, middle_name, last_name, nickname, uuid))
+            .first(conn)
     }
 }
 impl ForBook<Record> for Record {
@@ -88,7 +209,16 @@ impl Find<Base> for Record {
 }
 impl Save<Base> for Record {
     fn save(conn: &SqliteConnection, value: &Base) -> QueryResult<usize> {
-        use crate::diesel::RunQueryDsl;       
-        diesel::insert_into(authors::table).values(value).execute(conn)
+        use crate::schema::authors::dsl::*;
+        use crate::diesel::ExpressionMethods;
+        use crate::diesel::RunQueryDsl;
+        diesel::insert_into(authors)
+            .values((
+                value,
+                first_name_key.eq(sort_key(&value.first_name)),
+                middle_name_key.eq(sort_key(&value.middle_name)),
+                last_name_key.eq(sort_key(&value.last_name)),
+            ))
+            .execute(conn)
     }
 }
@@ -0,0 +1,187 @@
+use std::collections::HashMap;
+use crate::schema::author_aliases;
+use super::*;
+
+#[derive(Insertable)]
+#[table_name="author_aliases"]
+#[derive(Eq, PartialEq, Hash, Clone, Debug)]
+pub struct AuthorAlias {
+    pub first_name: String,
+    pub middle_name: String,
+    pub last_name: String,
+    pub nickname: String,
+    pub uuid: String,
+    pub author_id: Id,
+}
+impl AuthorAlias {
+    pub fn new(alias: &Author, author_id: Id) -> Self {
+        Self {
+            first_name: alias.first_name.clone(),
+            middle_name: alias.middle_name.clone(),
+            last_name: alias.last_name.clone(),
+            nickname: alias.nickname.clone(),
+            uuid: alias.uuid.clone(),
+            author_id,
+        }
+    }
+}
+
+#[derive(Insertable, Queryable, Debug, Clone)]
+#[table_name="author_aliases"]
+pub struct AuthorAliasRecord {
+    pub id: Id,
+    pub first_name: String,
+    pub middle_name: String,
+    pub last_name: String,
+    pub nickname: String,
+    pub uuid: String,
+    pub author_id: Id,
+}
+impl AuthorAliasRecord {
+    /// Returns the author the name variant was merged into
+    pub fn find_author(conn: &SqliteConnection, value: &Author) -> Option<Id> {
+        use crate::schema::author_aliases::dsl::*;
+        use crate::diesel::ExpressionMethods;
+        use crate::diesel::RunQueryDsl;
+        use crate::diesel::QueryDsl;
+        author_aliases
+            .filter(first_name.eq(&value.first_name))
+            .filter(middle_name.eq(&value.middle_name))
+            .filter(last_name.eq(&value.last_name))
+            .filter(nickname.eq(&value.nickname))
+            .filter(uuid.eq(&value.uuid))
+            .select(author_id)
+            .first(conn)
+            .ok()
+    }
+
+    /// Merges the alias author into the author: the books of the alias are relinked,
+    /// the name of the alias is remembered for the next loads and the alias record is removed
+    pub fn merge(conn: &SqliteConnection, author: Id, alias: Id) -> QueryResult<()> {
+        use crate::diesel::Connection;
+        use crate::diesel::ExpressionMethods;
+        use crate::diesel::RunQueryDsl;
+        use crate::diesel::QueryDsl;
+        use crate::schema::{authors, author_links};
+
+        if author == alias {
+            return Ok(());
+        }
+        let variant = AuthorRecord::load(conn, alias)?.get_author();
+        conn.transaction(|| {
+            Self::save(conn, &AuthorAlias::new(&variant, author))?;
+            diesel::update(author_aliases::table.filter(author_aliases::author_id.eq(alias)))
+                .set(author_aliases::author_id.eq(author))
+                .execute(conn)?;
+            diesel::sql_query(format!(
+                "UPDATE OR IGNORE author_links SET author_id = {author} WHERE author_id = {alias}",
+                author = author,
+                alias = alias
+            )).execute(conn)?;
+            diesel::delete(author_links::table.filter(author_links::author_id.eq(alias))).execute(conn)?;
+            diesel::delete(authors::table.find(alias)).execute(conn)?;
+            Ok(())
+        })
+    }
+
+    /// Groups the authors which look like the same person: the same folded name,
+    /// the same name with and without the middle name or the same FB2 author id.
+    /// The first author of the each group is the one with the most complete name.
+    pub fn find_duplicates(conn: &SqliteConnection) -> QueryResult<Vec<Vec<AuthorRecord>>> {
+        let records = AuthorRecord::load_all(conn)?;
+        let mut parent: Vec<usize> = (0..records.len()).collect();
+        fn root(parent: &mut [usize], mut i: usize) -> usize {
+            while parent[i] != i {
+                parent[i] = parent[parent[i]];
+                i = parent[i];
+            }
+            i
+        }
+        fn join(parent: &mut [usize], a: usize, b: usize) {
+            let (a, b) = (root(parent, a), root(parent, b));
+            if a != b {
+                parent[b] = a;
+            }
+        }
+
+        let mut by_key: HashMap<String, usize> = HashMap::new();
+        let mut by_uuid: HashMap<String, usize> = HashMap::new();
+        let mut by_short_key: HashMap<String, Vec<usize>> = HashMap::new();
+        for (i, record) in records.iter().enumerate() {
+            let author = record.get_author();
+            match by_key.get(&author.get_match_key()) {
+                Some(&j) => join(&mut parent, j, i),
+                None => { by_key.insert(author.get_match_key(), i); }
+            }
+            if !author.uuid.is_empty() {
+                match by_uuid.get(&author.uuid) {
+                    Some(&j) => join(&mut parent, j, i),
+                    None => { by_uuid.insert(author.uuid.clone(), i); }
+                }
+            }
+            by_short_key.entry(author.get_short_key()).or_default().push(i);
+        }
+
+        // The author without the middle name is joined only if the middle name of the others is unambiguous
+        for group in by_short_key.values() {
+            let mut middles: Vec<String> = group.iter()
+                .map(|&i| crate::normalize::fold(&records[i].middle_name))
+                .filter(|middle| !middle.is_empty())
+                .collect();
+            middles.sort();
+            middles.dedup();
+            if middles.len() <= 1 {
+                for &i in group.iter().skip(1) {
+                    join(&mut parent, group[0], i);
+                }
+            }
+        }
+
+        let mut groups: HashMap<usize, Vec<AuthorRecord>> = HashMap::new();
+        for (i, record) in records.iter().enumerate() {
+            let r = root(&mut parent, i);
+            groups.entry(r).or_default().push(record.clone());
+        }
+        let mut result: Vec<Vec<AuthorRecord>> = groups.into_values()
+            .filter(|group| group.len() > 1)
+            .collect();
+        for group in result.iter_mut() {
+            group.sort_by_key(|record| (std::cmp::Reverse(record.get_author().get_display_name().chars().count()), record.id));
+        }
+        result.sort_by(|a, b| (&a[0].last_name, &a[0].first_name).cmp(&(&b[0].last_name, &b[0].first_name)));
+        Ok(result)
+    }
+}
+
+type Base = AuthorAlias;
+type Record = AuthorAliasRecord;
+impl Load<Record> for Record {
+    fn load(conn: &SqliteConnection, id: Id) -> QueryResult<Self> {
+        use crate::schema::author_aliases::dsl::author_aliases;
+        use crate::diesel::RunQueryDsl;
+        use crate::diesel::QueryDsl;
+        author_aliases.find(id).first(conn)
+    }
+}
+impl Find<Base> for Record {
+    fn find(conn: &SqliteConnection, value: &Base) -> QueryResult<Id> {
+        use crate::schema::author_aliases::dsl::*;
+        use crate::diesel::ExpressionMethods;
+        use crate::diesel::RunQueryDsl;
+        use crate::diesel::QueryDsl;
+        author_aliases
+            .filter(first_name.eq(&value.first_name))
+            .filter(middle_name.eq(&value.middle_name))
+            .filter(last_name.eq(&value.last_name))
+            .filter(nickname.eq(&value.nickname))
+            .filter(uuid.eq(&value.uuid))
+            .select(id)
+            .first(conn)
+    }
+}
+impl Save<Base> for Record {
+    fn save(conn: &SqliteConnection, value: &Base) -> QueryResult<usize> {
+        use crate::diesel::RunQueryDsl;
+        diesel::insert_into(author_aliases::table).values(value).execute(conn)
+    }
+}
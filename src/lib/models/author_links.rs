@@ -1,16 +1,35 @@
 use crate::schema::author_links;
 use super::*;
 
+#[derive(Eq, PartialEq, Hash, Clone, Copy, Debug)]
+pub enum AuthorRole {
+    Author,
+    Translator,
+}
+impl AuthorRole {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuthorRole::Author => "author",
+            AuthorRole::Translator => "translator",
+        }
+    }
+}
+
 #[derive(Insertable)]
 #[table_name="author_links"]
 #[derive(Eq, PartialEq, Hash, Clone, Debug)]
 pub struct AuthorLink{
     pub book_id: Id,
     pub author_id: Id,
+    pub role: String,
 }
 impl AuthorLink{
     pub fn new(book_id: Id, author_id: Id) -> Self {
-        Self { book_id, author_id }
+        Self::with_role(book_id, author_id, AuthorRole::Author)
+    }
+
+    pub fn with_role(book_id: Id, author_id: Id, role: AuthorRole) -> Self {
+        Self { book_id, author_id, role: String::from(role.as_str()) }
     }
 }
 
@@ -20,6 +39,7 @@ pub struct AuthorLinkRecord {
     pub id: Id,
     pub book_id: Id,
     pub author_id: Id,
+    pub role: String,
 }
 
 type Base = AuthorLink;
@@ -43,6 +63,7 @@ impl Find<Base> for Record {
         author_links
             .filter(book_id.eq(&value.book_id))
             .filter(author_id.eq(&value.author_id))
+            .filter(role.eq(&value.role))
             .select(id).first(conn)
     }
 }
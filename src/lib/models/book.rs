@@ -1,3 +1,6 @@
+use std::io;
+use std::fs;
+use std::path::Path;
 use crate::zip::read::ZipFile;
 use crate::schema::books;
 use super::*;
@@ -24,6 +27,32 @@ impl Book {
             book_offset: book.data_start() as i64,
         }
     }
+
+    pub fn from_file(archive_id: Id, book: &Path) -> io::Result<Self> {
+        use std::io::Read;
+        let mut file = fs::File::open(book)?;
+        let mut buffer = [0u8; 64*1024];
+        let mut hasher = crc32fast::Hasher::new();
+        let mut size = 0;
+        loop {
+            let readed = file.read(&mut buffer)?;
+            if 0 == readed {
+                break;
+            }
+            hasher.update(&buffer[0..readed]);
+            size += readed as i64;
+        }
+        Ok(Self {
+            arch_id: archive_id,
+            book_file: book.file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_default(),
+            book_zip_size: size,
+            book_size: size,
+            book_crc32: hasher.finalize() as i64,
+            book_offset: 0,
+        })
+    }
 }
 
 #[derive(Insertable, Queryable, Debug, Clone)]
@@ -36,6 +65,9 @@ pub struct BookRecord {
     pub book_size: i64,
     pub book_crc32: i64,
     pub book_offset: i64,
+    pub book_loaded: String,
+    pub book_lang: String,
+    pub book_deleted: bool,
 }
 impl BookRecord {
     pub fn find_uniq(conn: &SqliteConnection, aid: Id, book: &str, crc: i64) -> Option<Id> {
@@ -50,6 +82,80 @@ impl BookRecord {
             .select(id)
             .first(conn).ok()
     }
+
+    pub fn load_for_archive(conn: &SqliteConnection, archive: Id) -> QueryResult<Vec<Self>> {
+        use crate::schema::books::dsl::*;
+        use crate::diesel::ExpressionMethods;
+        use crate::diesel::RunQueryDsl;
+        use crate::diesel::QueryDsl;
+        books.filter(arch_id.eq(archive)).order(id).load(conn)
+    }
+
+    /// The book of the archive with the file name regardless of the CRC
+    pub fn find_by_file(conn: &SqliteConnection, aid: Id, book: &str) -> QueryResult<Option<Self>> {
+        use crate::schema::books::dsl::*;
+        use crate::diesel::ExpressionMethods;
+        use crate::diesel::OptionalExtension;
+        use crate::diesel::RunQueryDsl;
+        use crate::diesel::QueryDsl;
+        books
+            .filter(arch_id.eq(aid))
+            .filter(book_file.eq(book))
+            .order(id)
+            .first(conn)
+            .optional()
+    }
+
+    /// The book of any archive with the same content, found by the size and the CRC
+    pub fn find_by_content(conn: &SqliteConnection, size: i64, crc: i64) -> QueryResult<Option<Id>> {
+        use crate::schema::books::dsl::*;
+        use crate::diesel::ExpressionMethods;
+        use crate::diesel::OptionalExtension;
+        use crate::diesel::RunQueryDsl;
+        use crate::diesel::QueryDsl;
+        books
+            .filter(book_crc32.eq(crc))
+            .filter(book_size.eq(size))
+            .select(id)
+            .order(id)
+            .first(conn)
+            .optional()
+    }
+
+    pub fn set_lang(conn: &SqliteConnection, book: Id, lang: &str) -> QueryResult<usize> {
+        use crate::schema::books::dsl::*;
+        use crate::diesel::ExpressionMethods;
+        use crate::diesel::RunQueryDsl;
+        use crate::diesel::QueryDsl;
+        diesel::update(books.find(book)).set(book_lang.eq(lang)).execute(conn)
+    }
+
+    pub fn set_deleted(conn: &SqliteConnection, book: Id, deleted: bool) -> QueryResult<usize> {
+        use crate::schema::books::dsl::*;
+        use crate::diesel::ExpressionMethods;
+        use crate::diesel::RunQueryDsl;
+        use crate::diesel::QueryDsl;
+        diesel::update(books.find(book)).set(book_deleted.eq(deleted)).execute(conn)
+    }
+
+    /// Attaches the book to the entry of the archive keeping the id of the book, the books imported
+    /// from INPX without the archive get the size and the CRC of the entry as well
+    pub fn move_to(conn: &SqliteConnection, book: Id, value: &Book) -> QueryResult<usize> {
+        use crate::schema::books::dsl::*;
+        use crate::diesel::ExpressionMethods;
+        use crate::diesel::RunQueryDsl;
+        use crate::diesel::QueryDsl;
+        diesel::update(books.find(book))
+            .set((
+                arch_id.eq(value.arch_id),
+                book_file.eq(&value.book_file),
+                book_zip_size.eq(value.book_zip_size),
+                book_size.eq(value.book_size),
+                book_crc32.eq(value.book_crc32),
+                book_offset.eq(value.book_offset),
+            ))
+            .execute(conn)
+    }
 }
 type Base = Book;
 type Record = BookRecord;
@@ -80,8 +186,11 @@ impl Find<Base> for Record {
 }
 impl Save<Base> for Record {
     fn save(conn: &SqliteConnection, value: &Base) -> QueryResult<usize> {
-        use crate::diesel::RunQueryDsl;       
-        diesel::insert_into(books::table).values(value).execute(conn)
+        use crate::diesel::ExpressionMethods;
+        use crate::diesel::RunQueryDsl;
+        diesel::insert_into(books::table)
+            .values((value, books::book_loaded.eq(timestamp())))
+            .execute(conn)
     }
 }
 
@@ -0,0 +1,52 @@
+use crate::schema::book_digests;
+use super::*;
+
+/// The KOReader document hash of the file served for the book
+#[derive(Insertable)]
+#[table_name="book_digests"]
+#[derive(Eq, PartialEq, Hash, Clone, Debug)]
+pub struct BookDigest {
+    pub book_id: Id,
+    pub document: String,
+}
+impl BookDigest {
+    pub fn new(book_id: Id, document: String) -> Self {
+        Self { book_id, document }
+    }
+}
+
+#[derive(Insertable, Queryable, Debug, Clone)]
+#[table_name="book_digests"]
+pub struct BookDigestRecord {
+    pub id: Id,
+    pub book_id: Id,
+    pub document: String,
+}
+type Base = BookDigest;
+type Record = BookDigestRecord;
+impl Load<Record> for Record {
+    fn load(conn: &SqliteConnection, id: Id) -> QueryResult<Self> {
+        use crate::schema::book_digests::dsl::book_digests;
+        use crate::diesel::RunQueryDsl;
+        use crate::diesel::QueryDsl;
+        book_digests.find(id).first(conn)
+    }
+}
+impl Find<Base> for Record {
+    fn find(conn: &SqliteConnection, value: &Base) -> QueryResult<Id> {
+        use crate::schema::book_digests::dsl::*;
+        use crate::diesel::ExpressionMethods;
+        use crate::diesel::RunQueryDsl;
+        use crate::diesel::QueryDsl;
+        book_digests
+            .filter(document.eq(&value.document))
+            .select(id)
+            .first(conn)
+    }
+}
+impl Save<Base> for Record {
+    fn save(conn: &SqliteConnection, value: &Base) -> QueryResult<usize> {
+        use crate::diesel::RunQueryDsl;
+        diesel::insert_into(book_digests::table).values(value).execute(conn)
+    }
+}
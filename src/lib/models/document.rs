@@ -0,0 +1,236 @@
+use diesel::sql_types::{Integer, Text};
+use crate::schema::documents;
+use crate::normalize::make_match_key;
+use super::*;
+
+#[derive(Insertable)]
+#[table_name="documents"]
+#[derive(Eq, PartialEq, Hash, Clone, Debug)]
+pub struct Document {
+    pub book_id: Id,
+    pub canon_id: Id,
+    pub doc_id: String,
+    pub doc_version: String,
+    pub doc_date: String,
+    pub match_key: String,
+    pub program_used: String,
+    pub src_url: String,
+    pub creator: String,
+}
+impl Document {
+    pub fn new(book_id: Id, title: &str, authors: &Vec<Author>) -> Self {
+        let names = authors.iter()
+            .map(|author| format!("{} {} {}", author.last_name, author.first_name, author.middle_name))
+            .collect();
+        Self {
+            book_id: book_id,
+            canon_id: book_id,
+            doc_id: String::new(),
+            doc_version: String::new(),
+            doc_date: String::new(),
+            match_key: make_match_key(title, &names).unwrap_or_default(),
+            program_used: String::new(),
+            src_url: String::new(),
+            creator: String::new(),
+        }
+    }
+
+    pub fn from_fb2(book_id: Id, fb2: &fb2parser::FictionBook) -> Self {
+        let authors = fb2.get_authors().iter().map(Author::from).collect();
+        let mut document = Self::new(book_id, &fb2.get_title(), &authors);
+        if let Some(ref info) = fb2.description.document_info {
+            document.doc_id = info.id.as_ref().map(|id| id.text.trim().to_string()).unwrap_or_default();
+            document.doc_version = info.version.as_ref().map(|version| version.text.trim().to_string()).unwrap_or_default();
+            document.doc_date = info.date.as_ref()
+                .map(|date| date.value.clone().unwrap_or(date.text.clone()).trim().to_string())
+                .unwrap_or_default();
+            document.program_used = info.program_used.as_ref().map(|program| program.text.trim().to_string()).unwrap_or_default();
+            document.src_url = info.src_url.iter()
+                .map(|url| url.text.trim().to_string())
+                .collect::<Vec<String>>()
+                .join(" ");
+            document.creator = info.authors.iter()
+                .map(|author| Author::from(author).get_display_name())
+                .collect::<Vec<String>>()
+                .join(", ");
+        }
+        document
+    }
+}
+
+#[derive(Insertable, Queryable, Debug, Clone)]
+#[table_name="documents"]
+pub struct DocumentRecord {
+    pub id: Id,
+    pub book_id: Id,
+    pub canon_id: Id,
+    pub doc_id: String,
+    pub doc_version: String,
+    pub doc_date: String,
+    pub match_key: String,
+    pub program_used: String,
+    pub src_url: String,
+    pub creator: String,
+}
+impl DocumentRecord {
+    /// Looks for the canonical book among the books indexed before the document's book.
+    /// The same FB2 document id, then the same content (crc32 and size), then the same title and authors are checked.
+    pub fn find_canon(conn: &SqliteConnection, value: &Document) -> Option<Id> {
+        use crate::schema::documents::dsl::*;
+        use crate::diesel::ExpressionMethods;
+        use crate::diesel::RunQueryDsl;
+        use crate::diesel::QueryDsl;
+
+        if !value.doc_id.is_empty() {
+            let found = documents
+                .filter(doc_id.eq(&value.doc_id))
+                .filter(book_id.lt(&value.book_id))
+                .order(book_id)
+                .select(canon_id)
+                .first(conn)
+                .ok();
+            if found.is_some() {
+                return found;
+            }
+        }
+
+        #[derive(QueryableByName)]
+        struct Canon {
+            #[sql_type = "Integer"] canon_id: Id,
+        }
+        let query = format!(
+            r#"
+            SELECT documents.canon_id AS canon_id
+            FROM books AS book
+            JOIN books ON (books.book_crc32 = book.book_crc32 AND books.book_size = book.book_size)
+            JOIN documents ON (documents.book_id = books.id)
+            WHERE book.id = {book} AND book.book_crc32 <> 0 AND books.id < {book}
+            ORDER BY books.id
+            LIMIT 1
+            "#,
+            book = value.book_id
+        );
+        let found: Option<Canon> = diesel::sql_query(&query).get_result(conn).ok();
+        if found.is_some() {
+            return found.map(|canon| canon.canon_id);
+        }
+
+        if !value.match_key.is_empty() {
+            return documents
+                .filter(match_key.eq(&value.match_key))
+                .filter(book_id.lt(&value.book_id))
+                .order(book_id)
+                .select(canon_id)
+                .first(conn)
+                .ok();
+        }
+        None
+    }
+
+    pub fn update_canon(conn: &SqliteConnection, value: &Document) -> QueryResult<usize> {
+        use crate::schema::documents::dsl::*;
+        use crate::diesel::ExpressionMethods;
+        use crate::diesel::RunQueryDsl;
+        use crate::diesel::QueryDsl;
+        diesel::update(documents.filter(book_id.eq(&value.book_id)))
+            .set((canon_id.eq(&value.canon_id), match_key.eq(&value.match_key)))
+            .execute(conn)
+    }
+
+    /// Recalculates match keys and canonical books of the all indexed books in one transaction,
+    /// the books without the document and the match key are skipped
+    pub fn rebuild(conn: &SqliteConnection) -> QueryResult<usize> {
+        use crate::diesel::Connection;
+        use crate::diesel::RunQueryDsl;
+        #[derive(QueryableByName)]
+        struct BookKey {
+            #[sql_type = "Integer"] book_id: Id,
+            #[sql_type = "Text"] book_title: String,
+            #[sql_type = "Text"] authors: String,
+        }
+        let query = r#"
+            SELECT
+                books.id AS book_id,
+                ifnull((SELECT book_title FROM titles_view WHERE titles_view.book_id = books.id), '') AS book_title,
+                ifnull((SELECT group_concat(last_name || ' ' || first_name || ' ' || middle_name, ':')
+                        FROM authors_view WHERE authors_view.book_id = books.id), '') AS authors
+            FROM books
+            ORDER BY books.id
+            "#;
+
+        conn.transaction(|| {
+            let keys: Vec<BookKey> = RunQueryDsl::load(diesel::sql_query(query), conn)?;
+            let mut changed = 0;
+            for key in keys {
+                let names = key.authors.split(':').map(String::from).collect();
+                let book_key = make_match_key(&key.book_title, &names);
+                let mut document = match Self::load_for_book(conn, key.book_id) {
+                    Ok(record) => Document {
+                        book_id: record.book_id,
+                        canon_id: record.book_id,
+                        doc_id: record.doc_id,
+                        doc_version: record.doc_version,
+                        doc_date: record.doc_date,
+                        match_key: book_key.unwrap_or_default(),
+                        program_used: record.program_used,
+                        src_url: record.src_url,
+                        creator: record.creator,
+                    },
+                    Err(diesel::result::Error::NotFound) => match book_key {
+                        Some(book_key) => {
+                            let mut document = Document::new(key.book_id, "", &Vec::new());
+                            document.match_key = book_key;
+                            Self::save(conn, &document)?;
+                            document
+                        },
+                        None => continue,
+                    },
+                    Err(e) => return Err(e),
+                };
+                document.canon_id = Self::find_canon(conn, &document).unwrap_or(document.book_id);
+                if document.canon_id != document.book_id {
+                    changed += 1;
+                }
+                Self::update_canon(conn, &document)?;
+            }
+            Ok(changed)
+        })
+    }
+
+    pub fn load_for_book(conn: &SqliteConnection, book: Id) -> QueryResult<Self> {
+        use crate::schema::documents::dsl::*;
+        use crate::diesel::ExpressionMethods;
+        use crate::diesel::RunQueryDsl;
+        use crate::diesel::QueryDsl;
+        documents.filter(book_id.eq(&book)).first(conn)
+    }
+}
+
+type Base = Document;
+type Record = DocumentRecord;
+impl Load<Record> for Record {
+    fn load(conn: &SqliteConnection, id: Id) -> QueryResult<Self> {
+        use crate::schema::documents::dsl::documents;
+        use crate::diesel::RunQueryDsl;
+        use crate::diesel::QueryDsl;
+        documents.find(id).first(conn)
+    }
+}
+impl Find<Base> for Record {
+    fn find(conn: &SqliteConnection, value: &Base) -> QueryResult<Id> {
+        use crate::schema::documents::dsl::*;
+        use crate::diesel::ExpressionMethods;
+        use crate::diesel::RunQueryDsl;
+        use crate::diesel::QueryDsl;
+        documents
+            .filter(book_id.eq(&value.book_id))
+            .select(id)
+            .first(conn)
+    }
+}
+impl Save<Base> for Record {
+    fn save(conn: &SqliteConnection, value: &Base) -> QueryResult<usize> {
+        use crate::diesel::RunQueryDsl;
+        diesel::insert_into(documents::table).values(value).execute(conn)
+    }
+}
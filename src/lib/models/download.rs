@@ -0,0 +1,77 @@
+use crate::schema::downloads;
+use super::*;
+
+/// The format of the served file
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DownloadFormat {
+    Fb2,
+    Zip,
+}
+impl DownloadFormat {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DownloadFormat::Fb2 => "fb2",
+            DownloadFormat::Zip => "zip",
+        }
+    }
+}
+
+/// The file served by the download link, the user is empty for the anonymous download
+#[derive(Insertable)]
+#[table_name="downloads"]
+#[derive(Eq, PartialEq, Hash, Clone, Debug)]
+pub struct Download {
+    pub book_id: Id,
+    pub user_id: Option<Id>,
+    pub download_format: String,
+}
+impl Download {
+    pub fn new(book_id: Id, user_id: Option<Id>, format: DownloadFormat) -> Self {
+        Self {
+            book_id: book_id,
+            user_id: user_id,
+            download_format: String::from(format.as_str()),
+        }
+    }
+}
+
+#[derive(Insertable, Queryable, Debug, Clone)]
+#[table_name="downloads"]
+pub struct DownloadRecord {
+    pub id: Id,
+    pub book_id: Id,
+    pub user_id: Option<Id>,
+    pub download_format: String,
+    pub download_time: String,
+}
+impl DownloadRecord {
+    /// Keeps the downloads of the removed user as the anonymous ones
+    pub fn forget_user(conn: &SqliteConnection, user: Id) -> QueryResult<usize> {
+        use crate::diesel::ExpressionMethods;
+        use crate::diesel::RunQueryDsl;
+        use crate::diesel::QueryDsl;
+        diesel::update(downloads::table.filter(downloads::user_id.eq(user)))
+            .set(downloads::user_id.eq(None::<Id>))
+            .execute(conn)
+    }
+}
+
+type Base = Download;
+type Record = DownloadRecord;
+impl Load<Record> for Record {
+    fn load(conn: &SqliteConnection, id: Id) -> QueryResult<Self> {
+        use crate::schema::downloads::dsl::downloads;
+        use crate::diesel::RunQueryDsl;
+        use crate::diesel::QueryDsl;
+        downloads.find(id).first(conn)
+    }
+}
+impl Save<Base> for Record {
+    fn save(conn: &SqliteConnection, value: &Base) -> QueryResult<usize> {
+        use crate::diesel::ExpressionMethods;
+        use crate::diesel::RunQueryDsl;
+        diesel::insert_into(downloads::table)
+            .values((value, downloads::download_time.eq(timestamp())))
+            .execute(conn)
+    }
+}
@@ -0,0 +1,129 @@
+use crate::schema::isbns;
+use super::*;
+
+#[derive(Insertable)]
+#[table_name="isbns"]
+#[derive(Eq, PartialEq, Hash, Clone, Debug)]
+pub struct Isbn {
+    pub book_id: Id,
+    pub isbn: String,
+    pub isbn_key: String,
+}
+impl Isbn {
+    /// The valid ISBNs of the publish-info text, it often holds several of them
+    /// like "5-17-038620-6, 5-9713-3386-3" or "5-17-038620-6 5-9713-3386-3"
+    pub fn parse(book_id: Id, text: &str) -> Vec<Self> {
+        let mut result = Vec::new();
+        for part in text.split(|c| c == ',' || c == ';' || c == '/') {
+            if let Some(key) = Self::make_key(part) {
+                result.push(Self { book_id: book_id, isbn: part.trim().to_string(), isbn_key: key });
+                continue;
+            }
+            // The space separated numbers, the words are accumulated until they make the valid ISBN
+            let mut words: Vec<&str> = Vec::new();
+            for word in part.split_whitespace() {
+                words.push(word);
+                let joined = words.join(" ");
+                if let Some(key) = Self::make_key(&joined) {
+                    result.push(Self { book_id: book_id, isbn: joined, isbn_key: key });
+                    words.clear();
+                } else if Self::get_symbols(&joined).len() >= 13 {
+                    words.clear();
+                }
+            }
+        }
+        result
+    }
+
+    /// Leaves only digits and the check symbol 'X', so "5-17-038620-6" and "5170386206" are the same ISBN.
+    /// Only the ISBN-10 and the ISBN-13 with the correct check digit have the key.
+    pub fn make_key(isbn: &str) -> Option<String> {
+        let key = Self::get_symbols(isbn);
+        let digits: Vec<u32> = key.chars().map(|c| c.to_digit(10).unwrap_or(10)).collect();
+        let valid = match digits.len() {
+            10 => {
+                !digits[..9].contains(&10)
+                    && digits.iter().enumerate().map(|(i, d)| (10 - i as u32) * d).sum::<u32>() % 11 == 0
+            },
+            13 => {
+                !digits.contains(&10)
+                    && digits.iter().enumerate().map(|(i, d)| if i % 2 == 0 { *d } else { 3 * d }).sum::<u32>() % 10 == 0
+            },
+            _ => false,
+        };
+        if valid { Some(key) } else { None }
+    }
+
+    fn get_symbols(isbn: &str) -> String {
+        isbn.chars()
+            .filter(|c| c.is_ascii_digit() || *c == 'x' || *c == 'X')
+            .map(|c| c.to_ascii_uppercase())
+            .collect()
+    }
+}
+
+#[derive(Insertable, Queryable, Debug, Clone)]
+#[table_name="isbns"]
+pub struct IsbnRecord {
+    pub id: Id,
+    pub book_id: Id,
+    pub isbn: String,
+    pub isbn_key: String,
+}
+
+type Base = Isbn;
+type Record = IsbnRecord;
+impl Load<Record> for Record {
+    fn load(conn: &SqliteConnection, id: Id) -> QueryResult<Self> {
+        use crate::schema::isbns::dsl::isbns;
+        use crate::diesel::RunQueryDsl;
+        use crate::diesel::QueryDsl;
+        isbns.find(id).first(conn)
+    }
+}
+impl Find<Base> for Record {
+    fn find(conn: &SqliteConnection, value: &Base) -> QueryResult<Id> {
+        use crate::schema::isbns::dsl::*;
+        use crate::diesel::ExpressionMethods;
+        use crate::diesel::RunQueryDsl;
+        use crate::diesel::QueryDsl;
+        isbns
+            .filter(book_id.eq(&value.book_id))
+            .filter(isbn_key.eq(&value.isbn_key))
+            .select(id)
+            .first(conn)
+    }
+}
+impl Save<Base> for Record {
+    fn save(conn: &SqliteConnection, value: &Base) -> QueryResult<usize> {
+        use crate::diesel::RunQueryDsl;
+        diesel::insert_into(isbns::table).values(value).execute(conn)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_make_key() {
+        assert_eq!(Isbn::make_key("5-17-038620-6"), Some(String::from("5170386206")));
+        assert_eq!(Isbn::make_key("ISBN 978-5-17-038620-8"), Some(String::from("9785170386208")));
+        assert_eq!(Isbn::make_key("0-8044-2957-x"), Some(String::from("080442957X")));
+        assert_eq!(Isbn::make_key("5-17-038620-7"), None);
+        assert_eq!(Isbn::make_key("978-5-17-038620-5"), None);
+        assert_eq!(Isbn::make_key("5-17-03862"), None);
+        assert_eq!(Isbn::make_key("X-17-038620-6"), None);
+    }
+
+    #[test]
+    fn test_parse() {
+        let keys = |text: &str| Isbn::parse(1, text).into_iter().map(|isbn| isbn.isbn_key).collect::<Vec<String>>();
+        assert_eq!(keys("5-17-038620-6, 978-5-17-038620-8"), vec!["5170386206", "9785170386208"]);
+        assert_eq!(keys("5-17-038620-6 978-5-17-038620-8"), vec!["5170386206", "9785170386208"]);
+        assert_eq!(keys("978 5 17 038620 8"), vec!["9785170386208"]);
+        assert_eq!(keys("5-17-038620-7; 0-8044-2957-X"), vec!["080442957X"]);
+        assert_eq!(Isbn::parse(1, " 5-17-038620-6 ,")[0].isbn, "5-17-038620-6");
+        assert!(keys("нет").is_empty());
+    }
+}
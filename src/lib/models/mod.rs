@@ -3,6 +3,36 @@ pub type Id = i32;
 pub type QueryResult<T> = std::result::Result<T, diesel::result::Error>;
 pub use diesel::sqlite::SqliteConnection;
 
+/// The load time stored in the arch_loaded and book_loaded columns, comparable with SQLite datetime()
+pub fn timestamp() -> String {
+    chrono::Utc::now().format("%Y-%m-%d %H:%M:%S").to_string()
+}
+
+/// The rows with the empty keys checked for the stale ones, the stale table has them among the first rows
+const STALE_CHECK_LIMIT: i64 = 1000;
+
+/// Recalculates the navigation keys of the tables which have them not calculated by the loader,
+/// the migrations changing the format of the keys leave them empty. Returns the reindexed tables.
+pub fn reindex_stale_keys(conn: &SqliteConnection) -> QueryResult<Vec<&'static str>> {
+    use crate::diesel::Connection;
+    conn.transaction(|| {
+        let mut stale = Vec::new();
+        if AuthorRecord::has_stale_keys(conn)? {
+            AuthorRecord::reindex(conn)?;
+            stale.push("authors");
+        }
+        if TitleRecord::has_stale_keys(conn)? {
+            TitleRecord::reindex(conn)?;
+            stale.push("titles");
+        }
+        if SeriesRecord::has_stale_keys(conn)? {
+            SeriesRecord::reindex(conn)?;
+            stale.push("series");
+        }
+        Ok(stale)
+    })
+}
+
 pub trait Find<T> {
     fn find(conn: &SqliteConnection, value: &T) -> QueryResult<Id>;
 }
@@ -20,15 +50,45 @@ pub trait ForBook<T> {
 }
 
 pub mod archive;
-pub use archive::{Archive, ArchiveRecord};
+pub use archive::{Archive, ArchiveRecord, SourceKind, PREFIX_HASH, PREFIX_INPX};
 pub mod book;
 pub use book::{Book, BookRecord};
 pub mod genre;
 pub use genre::{Genre, GenreRecord, GenreView};
 pub mod author;
 pub use author::{Author, AuthorRecord};
+pub mod author_alias;
+pub use author_alias::{AuthorAlias, AuthorAliasRecord};
 pub mod title;
 pub use title::{Title, TitleRecord, TitleView};
+pub mod document;
+pub use document::{Document, DocumentRecord};
+pub mod publication;
+pub use publication::{Publication, PublicationRecord};
+pub mod isbn;
+pub use isbn::{Isbn, IsbnRecord};
+pub mod original;
+pub use original::{Original, OriginalRecord};
+pub mod series;
+pub use series::{Series, SeriesRecord};
+pub mod user;
+pub use user::{User, UserRecord};
+pub mod session;
+pub use session::{Session, SessionRecord};
+pub mod shelf;
+pub use shelf::{ShelfKind, ShelfItem, ShelfRecord};
+pub mod book_digest;
+pub use book_digest::{BookDigest, BookDigestRecord};
+pub mod reading_progress;
+pub use reading_progress::{ReadingProgress, ReadingProgressRecord};
+pub mod review;
+pub use review::{Review, ReviewRecord};
+pub mod tag;
+pub use tag::{Tag, TagRecord};
+pub mod root;
+pub use root::{Root, RootRecord, Roots};
+pub mod download;
+pub use download::{Download, DownloadFormat, DownloadRecord};
 
 pub mod title_links;
 pub use title_links::*;
@@ -36,4 +96,8 @@ pub mod author_links;
 pub use author_links::*;
 pub mod genre_links;
 pub use genre_links::*;
+pub mod series_links;
+pub use series_links::*;
+pub mod tag_links;
+pub use tag_links::*;
 
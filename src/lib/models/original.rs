@@ -0,0 +1,75 @@
+use crate::schema::originals;
+use super::*;
+
+#[derive(Insertable)]
+#[table_name="originals"]
+#[derive(Eq, PartialEq, Hash, Clone, Debug)]
+pub struct Original {
+    pub book_id: Id,
+    pub src_title: String,
+    pub src_authors: String,
+    pub src_lang: String,
+}
+impl Original {
+    pub fn from(book_id: Id, fb2: &fb2parser::FictionBook) -> Option<Self> {
+        let title_info = &fb2.description.title_info;
+        let src_lang = title_info.src_lang.as_ref().map(|lang| lang.text.trim().to_string()).unwrap_or_default();
+        match fb2.description.src_title_info {
+            Some(ref src) => Some(Self {
+                book_id: book_id,
+                src_title: src.book_title.as_ref().map(|title| title.text.trim().to_string()).unwrap_or_default(),
+                src_authors: src.authors.iter()
+                    .map(|author| Author::from(author).get_display_name())
+                    .collect::<Vec<String>>()
+                    .join(", "),
+                src_lang: src.lang.as_ref().map(|lang| lang.text.trim().to_string()).unwrap_or(src_lang),
+            }),
+            None if !src_lang.is_empty() => Some(Self {
+                book_id: book_id,
+                src_title: String::new(),
+                src_authors: String::new(),
+                src_lang: src_lang,
+            }),
+            None => None,
+        }
+    }
+}
+
+#[derive(Insertable, Queryable, Debug, Clone)]
+#[table_name="originals"]
+pub struct OriginalRecord {
+    pub id: Id,
+    pub book_id: Id,
+    pub src_title: String,
+    pub src_authors: String,
+    pub src_lang: String,
+}
+
+type Base = Original;
+type Record = OriginalRecord;
+impl Load<Record> for Record {
+    fn load(conn: &SqliteConnection, id: Id) -> QueryResult<Self> {
+        use crate::schema::originals::dsl::originals;
+        use crate::diesel::RunQueryDsl;
+        use crate::diesel::QueryDsl;
+        originals.find(id).first(conn)
+    }
+}
+impl Find<Base> for Record {
+    fn find(conn: &SqliteConnection, value: &Base) -> QueryResult<Id> {
+        use crate::schema::originals::dsl::*;
+        use crate::diesel::ExpressionMethods;
+        use crate::diesel::RunQueryDsl;
+        use crate::diesel::QueryDsl;
+        originals
+            .filter(book_id.eq(&value.book_id))
+            .select(id)
+            .first(conn)
+    }
+}
+impl Save<Base> for Record {
+    fn save(conn: &SqliteConnection, value: &Base) -> QueryResult<usize> {
+        use crate::diesel::RunQueryDsl;
+        diesel::insert_into(originals::table).values(value).execute(conn)
+    }
+}
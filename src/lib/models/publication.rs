@@ -0,0 +1,64 @@
+use crate::schema::publications;
+use super::*;
+
+#[derive(Insertable)]
+#[table_name="publications"]
+#[derive(Eq, PartialEq, Hash, Clone, Debug)]
+pub struct Publication {
+    pub book_id: Id,
+    pub book_name: String,
+    pub publisher: String,
+    pub city: String,
+    pub year: String,
+}
+impl Publication {
+    pub fn from(book_id: Id, src: &fb2parser::PublishInfo) -> Self {
+        Self {
+            book_id: book_id,
+            book_name: src.book_name.as_ref().map(|v| v.text.trim().to_string()).unwrap_or_default(),
+            publisher: src.publisher.as_ref().map(|v| v.text.trim().to_string()).unwrap_or_default(),
+            city: src.city.as_ref().map(|v| v.text.trim().to_string()).unwrap_or_default(),
+            year: src.year.as_ref().map(|v| v.text.trim().to_string()).unwrap_or_default(),
+        }
+    }
+}
+
+#[derive(Insertable, Queryable, Debug, Clone)]
+#[table_name="publications"]
+pub struct PublicationRecord {
+    pub id: Id,
+    pub book_id: Id,
+    pub book_name: String,
+    pub publisher: String,
+    pub city: String,
+    pub year: String,
+}
+
+type Base = Publication;
+type Record = PublicationRecord;
+impl Load<Record> for Record {
+    fn load(conn: &SqliteConnection, id: Id) -> QueryResult<Self> {
+        use crate::schema::publications::dsl::publications;
+        use crate::diesel::RunQueryDsl;
+        use crate::diesel::QueryDsl;
+        publications.find(id).first(conn)
+    }
+}
+impl Find<Base> for Record {
+    fn find(conn: &SqliteConnection, value: &Base) -> QueryResult<Id> {
+        use crate::schema::publications::dsl::*;
+        use crate::diesel::ExpressionMethods;
+        use crate::diesel::RunQueryDsl;
+        use crate::diesel::QueryDsl;
+        publications
+            .filter(book_id.eq(&value.book_id))
+            .select(id)
+            .first(conn)
+    }
+}
+impl Save<Base> for Record {
+    fn save(conn: &SqliteConnection, value: &Base) -> QueryResult<usize> {
+        use crate::diesel::RunQueryDsl;
+        diesel::insert_into(publications::table).values(value).execute(conn)
+    }
+}
@@ -0,0 +1,68 @@
+use crate::schema::reading_progress;
+use super::*;
+
+/// The reading position reported by the KOReader progress sync
+#[derive(Insertable)]
+#[table_name="reading_progress"]
+#[derive(PartialEq, Clone, Debug)]
+pub struct ReadingProgress {
+    pub user_id: Id,
+    pub document: String,
+    pub progress: String,
+    pub percentage: f64,
+    pub device: String,
+    pub device_id: String,
+    pub progress_time: i64,
+}
+
+#[derive(Insertable, Queryable, Debug, Clone)]
+#[table_name="reading_progress"]
+pub struct ReadingProgressRecord {
+    pub id: Id,
+    pub user_id: Id,
+    pub document: String,
+    pub progress: String,
+    pub percentage: f64,
+    pub device: String,
+    pub device_id: String,
+    pub progress_time: i64,
+}
+impl ReadingProgressRecord {
+    pub fn find_for_user(conn: &SqliteConnection, user: Id, value: &str) -> QueryResult<Self> {
+        use crate::diesel::ExpressionMethods;
+        use crate::diesel::RunQueryDsl;
+        use crate::diesel::QueryDsl;
+        reading_progress::table
+            .filter(reading_progress::user_id.eq(user))
+            .filter(reading_progress::document.eq(value))
+            .first(conn)
+    }
+}
+
+type Base = ReadingProgress;
+type Record = ReadingProgressRecord;
+impl Load<Record> for Record {
+    fn load(conn: &SqliteConnection, id: Id) -> QueryResult<Self> {
+        use crate::diesel::RunQueryDsl;
+        use crate::diesel::QueryDsl;
+        reading_progress::table.find(id).first(conn)
+    }
+}
+impl Find<Base> for Record {
+    fn find(conn: &SqliteConnection, value: &Base) -> QueryResult<Id> {
+        use crate::diesel::ExpressionMethods;
+        use crate::diesel::RunQueryDsl;
+        use crate::diesel::QueryDsl;
+        reading_progress::table
+            .filter(reading_progress::user_id.eq(&value.user_id))
+            .filter(reading_progress::document.eq(&value.document))
+            .select(reading_progress::id)
+            .first(conn)
+    }
+}
+impl Save<Base> for Record {
+    fn save(conn: &SqliteConnection, value: &Base) -> QueryResult<usize> {
+        use crate::diesel::RunQueryDsl;
+        diesel::insert_into(reading_progress::table).values(value).execute(conn)
+    }
+}
@@ -0,0 +1,94 @@
+use crate::schema::reviews;
+use super::*;
+
+/// The star rating and the short review of the user, the book is the canonical one of the versions
+#[derive(Insertable)]
+#[table_name="reviews"]
+#[derive(Eq, PartialEq, Hash, Clone, Debug)]
+pub struct Review {
+    pub user_id: Id,
+    pub book_id: Id,
+    pub rating: i32,
+    pub review: String,
+}
+impl Review {
+    pub const MAX_RATING: i32 = 5;
+    pub const MAX_REVIEW_SIZE: usize = 4000;
+
+    pub fn new(user_id: Id, book_id: Id, rating: i32, review: &str) -> Self {
+        Self {
+            user_id: user_id,
+            book_id: book_id,
+            rating: rating.max(0).min(Self::MAX_RATING),
+            review: review.trim().chars().take(Self::MAX_REVIEW_SIZE).collect(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        0 == self.rating && self.review.is_empty()
+    }
+}
+
+#[derive(Insertable, Queryable, Debug, Clone)]
+#[table_name="reviews"]
+pub struct ReviewRecord {
+    pub id: Id,
+    pub user_id: Id,
+    pub book_id: Id,
+    pub rating: i32,
+    pub review: String,
+    pub review_added: String,
+}
+impl ReviewRecord {
+    /// Replaces the review of the user, the empty review is removed
+    pub fn put(conn: &SqliteConnection, value: &Review) -> QueryResult<usize> {
+        if value.is_empty() {
+            Self::remove(conn, value.user_id, value.book_id)
+        } else {
+            Self::save(conn, value)
+        }
+    }
+
+    pub fn remove(conn: &SqliteConnection, user: Id, book: Id) -> QueryResult<usize> {
+        use crate::diesel::ExpressionMethods;
+        use crate::diesel::RunQueryDsl;
+        use crate::diesel::QueryDsl;
+        diesel::delete(reviews::table
+            .filter(reviews::user_id.eq(user))
+            .filter(reviews::book_id.eq(book)))
+            .execute(conn)
+    }
+}
+
+type Base = Review;
+type Record = ReviewRecord;
+impl Load<Record> for Record {
+    fn load(conn: &SqliteConnection, id: Id) -> QueryResult<Self> {
+        use crate::schema::reviews::dsl::reviews;
+        use crate::diesel::RunQueryDsl;
+        use crate::diesel::QueryDsl;
+        reviews.find(id).first(conn)
+    }
+}
+impl Find<Base> for Record {
+    fn find(conn: &SqliteConnection, value: &Base) -> QueryResult<Id> {
+        use crate::schema::reviews::dsl::*;
+        use crate::diesel::ExpressionMethods;
+        use crate::diesel::RunQueryDsl;
+        use crate::diesel::QueryDsl;
+        reviews
+            .filter(user_id.eq(&value.user_id))
+            .filter(book_id.eq(&value.book_id))
+            .select(id)
+            .first(conn)
+    }
+}
+impl Save<Base> for Record {
+    fn save(conn: &SqliteConnection, value: &Base) -> QueryResult<usize> {
+        use crate::diesel::ExpressionMethods;
+        use crate::diesel::RunQueryDsl;
+        diesel::insert_into(reviews::table)
+            .values((value, reviews::review_added.eq(timestamp())))
+            .execute(conn)
+    }
+}
@@ -0,0 +1,172 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use crate::schema::roots;
+use super::*;
+
+/// The named directory of the library, the archives inside it keep the directory relative to it,
+/// so the library may be moved or mounted at the other path
+#[derive(Insertable)]
+#[table_name="roots"]
+#[derive(Eq, PartialEq, Hash, Clone, Debug)]
+pub struct Root {
+    pub root_name: String,
+    pub root_path: String,
+}
+impl Root {
+    pub fn new(name: &str, path: &str) -> Self {
+        Self {
+            root_name: String::from(name),
+            root_path: String::from(path),
+        }
+    }
+}
+
+#[derive(Insertable, Queryable, Debug, Clone)]
+#[table_name="roots"]
+pub struct RootRecord {
+    pub id: Id,
+    pub root_name: String,
+    pub root_path: String,
+}
+impl Record {
+    pub fn load_all(conn: &SqliteConnection) -> QueryResult<Vec<Self>> {
+        use crate::schema::roots::dsl::*;
+        use crate::diesel::RunQueryDsl;
+        use crate::diesel::QueryDsl;
+        roots.order(root_name).load(conn)
+    }
+
+    /// Sets the path of the root, adds the new root. The archives stored with the absolute directory
+    /// inside the root become relative to it. Returns the id of the root and the number of such archives.
+    pub fn rebase(conn: &SqliteConnection, value: &Root) -> QueryResult<(Id, usize)> {
+        use crate::schema::roots::dsl::*;
+        use crate::diesel::Connection;
+        use crate::diesel::ExpressionMethods;
+        use crate::diesel::RunQueryDsl;
+        use crate::diesel::QueryDsl;
+        use diesel::sql_types::{Integer, Text};
+
+        conn.transaction(|| {
+            let root = match Self::find(conn, value) {
+                Ok(root) => {
+                    diesel::update(roots.find(root)).set(root_path.eq(&value.root_path)).execute(conn)?;
+                    root
+                },
+                Err(diesel::result::Error::NotFound) => {
+                    Self::save(conn, value)?;
+                    Self::find(conn, value)?
+                },
+                Err(e) => return Err(e),
+            };
+            let length = value.root_path.chars().count() as i32;
+            let mut adopted = diesel::sql_query(
+                "UPDATE archives SET root_id = ?, arch_home = '' WHERE root_id = 0 AND arch_home = ?"
+            )
+                .bind::<Integer, _>(root)
+                .bind::<Text, _>(&value.root_path)
+                .execute(conn)?;
+            adopted += diesel::sql_query(r#"
+                UPDATE archives SET root_id = ?, arch_home = substr(arch_home, ? + 2)
+                WHERE root_id = 0 AND substr(arch_home, 1, ? + 1) = ? || '/'
+                "#)
+                .bind::<Integer, _>(root)
+                .bind::<Integer, _>(length)
+                .bind::<Integer, _>(length)
+                .bind::<Text, _>(&value.root_path)
+                .execute(conn)?;
+            Ok((root, adopted))
+        })
+    }
+}
+
+/// The paths of the roots by the id, resolves the directories of the archives
+#[derive(Debug, Clone, Default)]
+pub struct Roots {
+    paths: HashMap<Id, PathBuf>,
+}
+impl Roots {
+    pub fn new(roots: &[RootRecord]) -> Self {
+        Self {
+            paths: roots.iter().map(|root| (root.id, PathBuf::from(&root.root_path))).collect(),
+        }
+    }
+
+    pub fn load(conn: &SqliteConnection) -> QueryResult<Self> {
+        Ok(Self::new(&RootRecord::load_all(conn)?))
+    }
+
+    pub fn get_paths(&self) -> Vec<PathBuf> {
+        self.paths.values().cloned().collect()
+    }
+
+    /// The absolute directory of the archive, the home of the archive outside the roots is absolute already
+    pub fn get_home(&self, root: Id, home: &str) -> PathBuf {
+        match self.paths.get(&root) {
+            Some(path) if home.is_empty() => path.clone(),
+            Some(path) => path.join(home),
+            None => PathBuf::from(home),
+        }
+    }
+
+    /// The root of the absolute directory and the directory relative to it, the deepest root wins,
+    /// the directory outside the roots stays absolute with the root 0
+    pub fn split(&self, home: &Path) -> (Id, String) {
+        self.paths.iter()
+            .filter_map(|(root, path)| home.strip_prefix(path).ok().map(|relative| (path.components().count(), *root, relative)))
+            .max_by_key(|(depth, _, _)| *depth)
+            .map(|(_, root, relative)| (root, relative.to_string_lossy().to_string()))
+            .unwrap_or((0, home.to_string_lossy().to_string()))
+    }
+}
+
+type Base = Root;
+type Record = RootRecord;
+impl Load<Record> for Record {
+    fn load(conn: &SqliteConnection, id: Id) -> QueryResult<Self> {
+        use crate::schema::roots::dsl::roots;
+        use crate::diesel::RunQueryDsl;
+        use crate::diesel::QueryDsl;
+        roots.find(id).first(conn)
+    }
+}
+impl Find<Base> for Record {
+    fn find(conn: &SqliteConnection, value: &Base) -> QueryResult<Id> {
+        use crate::schema::roots::dsl::*;
+        use crate::diesel::ExpressionMethods;
+        use crate::diesel::RunQueryDsl;
+        use crate::diesel::QueryDsl;
+        roots
+            .filter(root_name.eq(&value.root_name))
+            .select(id)
+            .first(conn)
+    }
+}
+impl Save<Base> for Record {
+    fn save(conn: &SqliteConnection, value: &Base) -> QueryResult<usize> {
+        use crate::diesel::RunQueryDsl;
+        diesel::insert_into(roots::table)
+            .values(value)
+            .execute(conn)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_roots() {
+        let roots = Roots::new(&[
+            RootRecord { id: 1, root_name: String::from("books"), root_path: String::from("/mnt/books") },
+            RootRecord { id: 2, root_name: String::from("librusec"), root_path: String::from("/mnt/books/lib.rus.ec") },
+        ]);
+        assert_eq!(roots.split(Path::new("/mnt/books/lib.rus.ec/fb2")), (2, String::from("fb2")));
+        assert_eq!(roots.split(Path::new("/mnt/books/lib.rus.ec")), (2, String::new()));
+        assert_eq!(roots.split(Path::new("/mnt/books/flibusta")), (1, String::from("flibusta")));
+        assert_eq!(roots.split(Path::new("/mnt/bookshelf")), (0, String::from("/mnt/bookshelf")));
+
+        assert_eq!(roots.get_home(2, "fb2"), PathBuf::from("/mnt/books/lib.rus.ec/fb2"));
+        assert_eq!(roots.get_home(2, ""), PathBuf::from("/mnt/books/lib.rus.ec"));
+        assert_eq!(roots.get_home(0, "/mnt/bookshelf"), PathBuf::from("/mnt/bookshelf"));
+    }
+}
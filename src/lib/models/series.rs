@@ -0,0 +1,90 @@
+use std::convert::From;
+use crate::schema::series;
+use crate::normalize::{clean, sort_key};
+use super::*;
+
+#[derive(Insertable)]
+#[table_name="series"]
+#[derive(Eq, PartialEq, Hash, Clone, Debug)]
+pub struct Series {
+    pub series_name: String,
+}
+impl From<&fb2parser::Sequence> for Series {
+    fn from(src: &fb2parser::Sequence) -> Self {
+        Self {
+            series_name: clean(&src.name),
+        }
+    }
+}
+
+#[derive(Insertable, Queryable, Debug, Clone)]
+#[table_name="series"]
+pub struct SeriesRecord {
+    pub id: Id,
+    pub series_name: String,
+}
+impl SeriesRecord {
+    /// The series loaded before the navigation keys were calculated by the loader
+    pub fn has_stale_keys(conn: &SqliteConnection) -> QueryResult<bool> {
+        use crate::schema::series::dsl::*;
+        use crate::diesel::ExpressionMethods;
+        use crate::diesel::RunQueryDsl;
+        use crate::diesel::QueryDsl;
+        let names: Vec<String> = series
+            .filter(series_name_key.eq(""))
+            .filter(series_name.ne(""))
+            .select(series_name)
+            .limit(STALE_CHECK_LIMIT)
+            .load(conn)?;
+        Ok(names.iter().any(|name| !sort_key(name).is_empty()))
+    }
+
+    /// Recalculates the navigation sort keys of the all series
+    pub fn reindex(conn: &SqliteConnection) -> QueryResult<usize> {
+        use crate::schema::series::dsl::*;
+        use crate::diesel::ExpressionMethods;
+        use crate::diesel::RunQueryDsl;
+        use crate::diesel::QueryDsl;
+        let records: Vec<Self> = series.select((id, series_name)).load(conn)?;
+        let mut count = 0;
+        for record in records {
+            count += diesel::update(series.find(record.id))
+                .set(series_name_key.eq(sort_key(&record.series_name)))
+                .execute(conn)?;
+        }
+        Ok(count)
+    }
+}
+
+type Base = Series;
+type Record = SeriesRecord;
+impl Load<Record> for Record {
+    fn load(conn: &SqliteConnection, id: Id) -> QueryResult<Self> {
+        use crate::schema::series::dsl::{series, series_name};
+        use crate::diesel::RunQueryDsl;
+        use crate::diesel::QueryDsl;
+        series.find(id).select((crate::schema::series::id, series_name)).first(conn)
+    }
+}
+impl Find<Base> for Record {
+    fn find(conn: &SqliteConnection, value: &Base) -> QueryResult<Id> {
+        use crate::schema::series::dsl::*;
+        use crate::diesel::ExpressionMethods;
+        use crate::diesel::RunQueryDsl;
+        use crate::diesel::QueryDsl;
+        series
+            .filter(series_name.eq(&value.series_name))
+            .select(id)
+            .first(conn)
+    }
+}
+impl Save<Base> for Record {
+    fn save(conn: &SqliteConnection, value: &Base) -> QueryResult<usize> {
+        use crate::schema::series::dsl::*;
+        use crate::diesel::ExpressionMethods;
+        use crate::diesel::RunQueryDsl;
+        diesel::insert_into(series)
+            .values((value, series_name_key.eq(sort_key(&value.series_name))))
+            .execute(conn)
+    }
+}
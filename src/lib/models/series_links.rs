@@ -0,0 +1,61 @@
+use crate::schema::series_links;
+use super::*;
+
+#[derive(Insertable)]
+#[table_name="series_links"]
+#[derive(Eq, PartialEq, Hash, Clone, Debug)]
+pub struct SeriesLink{
+    pub book_id: Id,
+    pub series_id: Id,
+    pub serno: i32,
+}
+impl SeriesLink{
+    pub fn new(book_id: Id, series_id: Id, serno: i32) -> Self {
+        Self { book_id, series_id, serno }
+    }
+
+    /// The number of the book in the series, the unnumbered books get 0
+    pub fn parse_serno(number: &str) -> i32 {
+        number.trim().parse().unwrap_or_default()
+    }
+}
+
+#[derive(Insertable, Queryable)]
+#[table_name="series_links"]
+pub struct SeriesLinkRecord {
+    pub id: Id,
+    pub book_id: Id,
+    pub series_id: Id,
+    pub serno: i32,
+}
+
+type Base = SeriesLink;
+type Record = SeriesLinkRecord;
+
+impl Load<Record> for Record {
+    fn load(conn: &SqliteConnection, id: Id) -> QueryResult<Self> {
+        use crate::schema::series_links::dsl::series_links;
+        use crate::diesel::RunQueryDsl;
+        use crate::diesel::QueryDsl;
+        series_links.find(id).first(conn)
+    }
+}
+
+impl Find<Base> for Record {
+    fn find(conn: &SqliteConnection, value: &Base) -> QueryResult<Id> {
+        use crate::schema::series_links::dsl::*;
+        use crate::diesel::ExpressionMethods;
+        use crate::diesel::RunQueryDsl;
+        use crate::diesel::QueryDsl;
+        series_links
+            .filter(book_id.eq(&value.book_id))
+            .filter(series_id.eq(&value.series_id))
+            .select(id).first(conn)
+    }
+}
+impl Save<Base> for Record {
+    fn save(conn: &SqliteConnection, value: &Base) -> QueryResult<usize> {
+        use crate::diesel::RunQueryDsl;
+        diesel::insert_into(series_links::table).values(value).execute(conn)
+    }
+}
@@ -0,0 +1,88 @@
+use crate::schema::sessions;
+use crate::auth::make_token;
+use super::*;
+
+#[derive(Insertable)]
+#[table_name="sessions"]
+#[derive(Eq, PartialEq, Hash, Clone, Debug)]
+pub struct Session {
+    pub user_id: Id,
+    pub token: String,
+}
+impl Session {
+    /// The sessions older than this are not accepted
+    pub const MAX_AGE_DAYS: i64 = 30;
+
+    pub fn new(user_id: Id) -> Self {
+        Self {
+            user_id: user_id,
+            token: make_token(),
+        }
+    }
+}
+
+#[derive(Insertable, Queryable, Debug, Clone)]
+#[table_name="sessions"]
+pub struct SessionRecord {
+    pub id: Id,
+    pub user_id: Id,
+    pub token: String,
+    pub session_created: String,
+}
+impl SessionRecord {
+    /// Returns the owner of the not expired session
+    pub fn find_user(conn: &SqliteConnection, value: &str) -> QueryResult<UserRecord> {
+        use crate::schema::users;
+        use crate::diesel::ExpressionMethods;
+        use crate::diesel::RunQueryDsl;
+        use crate::diesel::QueryDsl;
+        let since = (chrono::Utc::now() - chrono::Duration::days(Session::MAX_AGE_DAYS))
+            .format("%Y-%m-%d %H:%M:%S")
+            .to_string();
+        sessions::table
+            .inner_join(users::table)
+            .filter(sessions::token.eq(value))
+            .filter(sessions::session_created.ge(since))
+            .select(users::all_columns)
+            .first(conn)
+    }
+
+    pub fn remove(conn: &SqliteConnection, value: &str) -> QueryResult<usize> {
+        use crate::diesel::ExpressionMethods;
+        use crate::diesel::RunQueryDsl;
+        use crate::diesel::QueryDsl;
+        diesel::delete(sessions::table.filter(sessions::token.eq(value))).execute(conn)
+    }
+}
+
+type Base = Session;
+type Record = SessionRecord;
+impl Load<Record> for Record {
+    fn load(conn: &SqliteConnection, id: Id) -> QueryResult<Self> {
+        use crate::schema::sessions::dsl::sessions;
+        use crate::diesel::RunQueryDsl;
+        use crate::diesel::QueryDsl;
+        sessions.find(id).first(conn)
+    }
+}
+impl Find<Base> for Record {
+    fn find(conn: &SqliteConnection, value: &Base) -> QueryResult<Id> {
+        use crate::schema::sessions::dsl::*;
+        use crate::diesel::ExpressionMethods;
+        use crate::diesel::RunQueryDsl;
+        use crate::diesel::QueryDsl;
+        sessions
+            .filter(token.eq(&value.token))
+            .select(id)
+            .first(conn)
+    }
+}
+impl Save<Base> for Record {
+    fn save(conn: &SqliteConnection, value: &Base) -> QueryResult<usize> {
+        use crate::diesel::ExpressionMethods;
+        use crate::diesel::RunQueryDsl;
+        diesel::insert_into(sessions::table)
+            .values((value, sessions::session_created.eq(timestamp())))
+            .execute(conn)
+    }
+}
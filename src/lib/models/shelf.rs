@@ -0,0 +1,123 @@
+use crate::schema::shelves;
+use super::*;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ShelfKind {
+    Want,
+    Reading,
+    Read,
+}
+impl ShelfKind {
+    pub const ALL: [ShelfKind; 3] = [ShelfKind::Want, ShelfKind::Reading, ShelfKind::Read];
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "want" => Some(ShelfKind::Want),
+            "reading" => Some(ShelfKind::Reading),
+            "read" => Some(ShelfKind::Read),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ShelfKind::Want => "want",
+            ShelfKind::Reading => "reading",
+            ShelfKind::Read => "read",
+        }
+    }
+
+    pub fn get_label(&self) -> &'static str {
+        match self {
+            ShelfKind::Want => "Хочу прочитать",
+            ShelfKind::Reading => "Читаю",
+            ShelfKind::Read => "Прочитано",
+        }
+    }
+}
+
+/// The book on the shelf of the user, the book is the canonical one of the versions
+#[derive(Insertable)]
+#[table_name="shelves"]
+#[derive(Eq, PartialEq, Hash, Clone, Debug)]
+pub struct ShelfItem {
+    pub user_id: Id,
+    pub book_id: Id,
+    pub shelf: String,
+}
+impl ShelfItem {
+    pub fn new(user_id: Id, book_id: Id, shelf: ShelfKind) -> Self {
+        Self {
+            user_id: user_id,
+            book_id: book_id,
+            shelf: String::from(shelf.as_str()),
+        }
+    }
+}
+
+#[derive(Insertable, Queryable, Debug, Clone)]
+#[table_name="shelves"]
+pub struct ShelfRecord {
+    pub id: Id,
+    pub user_id: Id,
+    pub book_id: Id,
+    pub shelf: String,
+    pub shelf_added: String,
+}
+impl ShelfRecord {
+    /// Puts the book on the shelf, the book is moved from the other shelf of the user
+    pub fn put(conn: &SqliteConnection, user: Id, book: Id, shelf: ShelfKind) -> QueryResult<usize> {
+        Self::save(conn, &ShelfItem::new(user, book, shelf))
+    }
+
+    pub fn remove(conn: &SqliteConnection, user: Id, book: Id) -> QueryResult<usize> {
+        use crate::diesel::ExpressionMethods;
+        use crate::diesel::RunQueryDsl;
+        use crate::diesel::QueryDsl;
+        diesel::delete(shelves::table
+            .filter(shelves::user_id.eq(user))
+            .filter(shelves::book_id.eq(book)))
+            .execute(conn)
+    }
+
+    pub fn load_for_user(conn: &SqliteConnection, user: Id) -> QueryResult<Vec<Self>> {
+        use crate::schema::shelves::dsl::*;
+        use crate::diesel::ExpressionMethods;
+        use crate::diesel::RunQueryDsl;
+        use crate::diesel::QueryDsl;
+        shelves.filter(user_id.eq(user)).load(conn)
+    }
+}
+
+type Base = ShelfItem;
+type Record = ShelfRecord;
+impl Load<Record> for Record {
+    fn load(conn: &SqliteConnection, id: Id) -> QueryResult<Self> {
+        use crate::schema::shelves::dsl::shelves;
+        use crate::diesel::RunQueryDsl;
+        use crate::diesel::QueryDsl;
+        shelves.find(id).first(conn)
+    }
+}
+impl Find<Base> for Record {
+    fn find(conn: &SqliteConnection, value: &Base) -> QueryResult<Id> {
+        use crate::schema::shelves::dsl::*;
+        use crate::diesel::ExpressionMethods;
+        use crate::diesel::RunQueryDsl;
+        use crate::diesel::QueryDsl;
+        shelves
+            .filter(user_id.eq(&value.user_id))
+            .filter(book_id.eq(&value.book_id))
+            .select(id)
+            .first(conn)
+    }
+}
+impl Save<Base> for Record {
+    fn save(conn: &SqliteConnection, value: &Base) -> QueryResult<usize> {
+        use crate::diesel::ExpressionMethods;
+        use crate::diesel::RunQueryDsl;
+        diesel::insert_into(shelves::table)
+            .values((value, shelves::shelf_added.eq(timestamp())))
+            .execute(conn)
+    }
+}
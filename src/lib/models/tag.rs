@@ -0,0 +1,64 @@
+use crate::schema::tags;
+use crate::normalize::{clean, sort_key};
+use super::*;
+
+/// The free-form tag, the listings of the tags use ':' as the separator
+#[derive(Insertable)]
+#[table_name="tags"]
+#[derive(Eq, PartialEq, Hash, Clone, Debug)]
+pub struct Tag {
+    pub tag_name: String,
+}
+impl Tag {
+    pub const MAX_SIZE: usize = 64;
+
+    pub fn new(name: &str) -> Self {
+        Self {
+            tag_name: clean(&name.replace(':', " ")).chars().take(Self::MAX_SIZE).collect(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tag_name.is_empty()
+    }
+}
+
+#[derive(Insertable, Queryable, Debug, Clone)]
+#[table_name="tags"]
+pub struct TagRecord {
+    pub id: Id,
+    pub tag_name: String,
+}
+
+type Base = Tag;
+type Record = TagRecord;
+impl Load<Record> for Record {
+    fn load(conn: &SqliteConnection, id: Id) -> QueryResult<Self> {
+        use crate::schema::tags::dsl::{tags, tag_name};
+        use crate::diesel::RunQueryDsl;
+        use crate::diesel::QueryDsl;
+        tags.find(id).select((crate::schema::tags::id, tag_name)).first(conn)
+    }
+}
+impl Find<Base> for Record {
+    fn find(conn: &SqliteConnection, value: &Base) -> QueryResult<Id> {
+        use crate::schema::tags::dsl::*;
+        use crate::diesel::ExpressionMethods;
+        use crate::diesel::RunQueryDsl;
+        use crate::diesel::QueryDsl;
+        tags
+            .filter(tag_name.eq(&value.tag_name))
+            .select(id)
+            .first(conn)
+    }
+}
+impl Save<Base> for Record {
+    fn save(conn: &SqliteConnection, value: &Base) -> QueryResult<usize> {
+        use crate::schema::tags::dsl::*;
+        use crate::diesel::ExpressionMethods;
+        use crate::diesel::RunQueryDsl;
+        diesel::insert_into(tags)
+            .values((value, tag_name_key.eq(sort_key(&value.tag_name))))
+            .execute(conn)
+    }
+}
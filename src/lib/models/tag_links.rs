@@ -0,0 +1,84 @@
+use crate::schema::tag_links;
+use super::*;
+
+/// The tag put by the user on the canonical book
+#[derive(Insertable)]
+#[table_name="tag_links"]
+#[derive(Eq, PartialEq, Hash, Clone, Debug)]
+pub struct TagLink{
+    pub user_id: Id,
+    pub book_id: Id,
+    pub tag_id: Id,
+}
+impl TagLink{
+    pub fn new(user_id: Id, book_id: Id, tag_id: Id) -> Self {
+        Self { user_id, book_id, tag_id }
+    }
+}
+
+#[derive(Insertable, Queryable)]
+#[table_name="tag_links"]
+pub struct TagLinkRecord {
+    pub id: Id,
+    pub user_id: Id,
+    pub book_id: Id,
+    pub tag_id: Id,
+}
+impl TagLinkRecord {
+    /// Tags the book, the new tag is added to the tags
+    pub fn put(conn: &SqliteConnection, user: Id, book: Id, tag: &Tag) -> QueryResult<usize> {
+        if tag.is_empty() {
+            return Ok(0);
+        }
+        TagRecord::save(conn, tag)?;
+        let tag_id = TagRecord::find(conn, tag)?;
+        Self::save(conn, &TagLink::new(user, book, tag_id))
+    }
+
+    pub fn remove(conn: &SqliteConnection, user: Id, book: Id, tag: &Tag) -> QueryResult<usize> {
+        use crate::diesel::ExpressionMethods;
+        use crate::diesel::RunQueryDsl;
+        use crate::diesel::QueryDsl;
+        match TagRecord::find(conn, tag) {
+            Ok(tag_id) => diesel::delete(tag_links::table
+                    .filter(tag_links::user_id.eq(user))
+                    .filter(tag_links::book_id.eq(book))
+                    .filter(tag_links::tag_id.eq(tag_id)))
+                .execute(conn),
+            Err(diesel::result::Error::NotFound) => Ok(0),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+type Base = TagLink;
+type Record = TagLinkRecord;
+
+impl Load<Record> for Record {
+    fn load(conn: &SqliteConnection, id: Id) -> QueryResult<Self> {
+        use crate::schema::tag_links::dsl::tag_links;
+        use crate::diesel::RunQueryDsl;
+        use crate::diesel::QueryDsl;
+        tag_links.find(id).first(conn)
+    }
+}
+
+impl Find<Base> for Record {
+    fn find(conn: &SqliteConnection, value: &Base) -> QueryResult<Id> {
+        use crate::schema::tag_links::dsl::*;
+        use crate::diesel::ExpressionMethods;
+        use crate::diesel::RunQueryDsl;
+        use crate::diesel::QueryDsl;
+        tag_links
+            .filter(user_id.eq(&value.user_id))
+            .filter(book_id.eq(&value.book_id))
+            .filter(tag_id.eq(&value.tag_id))
+            .select(id).first(conn)
+    }
+}
+impl Save<Base> for Record {
+    fn save(conn: &SqliteConnection, value: &Base) -> QueryResult<usize> {
+        use crate::diesel::RunQueryDsl;
+        diesel::insert_into(tag_links::table).values(value).execute(conn)
+    }
+}
@@ -1,5 +1,6 @@
 use std::convert::From;
 use crate::schema::titles;
+use crate::normalize::{clean, sort_key};
 use super::*;
 
 #[derive(Insertable)]
@@ -11,7 +12,7 @@ pub struct Title {
 impl From<&fb2parser::BookTitle> for Title{
     fn from(src: &fb2parser::BookTitle) -> Self {
         Self {
-            book_title: src.text.clone()
+            book_title: clean(&src.text)
         }
     }
 }
@@ -22,15 +23,47 @@ pub struct TitleRecord {
     pub id: Id,
     pub book_title: String,
 }
+impl TitleRecord {
+    /// The titles loaded before the navigation keys were calculated by the loader
+    pub fn has_stale_keys(conn: &SqliteConnection) -> QueryResult<bool> {
+        use crate::schema::titles::dsl::*;
+        use crate::diesel::ExpressionMethods;
+        use crate::diesel::RunQueryDsl;
+        use crate::diesel::QueryDsl;
+        let names: Vec<String> = titles
+            .filter(book_title_key.eq(""))
+            .filter(book_title.ne(""))
+            .select(book_title)
+            .limit(STALE_CHECK_LIMIT)
+            .load(conn)?;
+        Ok(names.iter().any(|name| !sort_key(name).is_empty()))
+    }
+
+    /// Recalculates the navigation sort keys of the all titles
+    pub fn reindex(conn: &SqliteConnection) -> QueryResult<usize> {
+        use crate::schema::titles::dsl::*;
+        use crate::diesel::ExpressionMethods;
+        use crate::diesel::RunQueryDsl;
+        use crate::diesel::QueryDsl;
+        let records: Vec<Self> = titles.select((id, book_title)).load(conn)?;
+        let mut count = 0;
+        for record in records {
+            count += diesel::update(titles.find(record.id))
+                .set(book_title_key.eq(sort_key(&record.book_title)))
+                .execute(conn)?;
+        }
+        Ok(count)
+    }
+}
 
 type Base = Title;
 type Record = TitleRecord;
 impl Load<Record> for Record {
     fn load(conn: &SqliteConnection, id: Id) -> QueryResult<Self> {
-        use crate::schema::titles::dsl::titles;
+        use crate::schema::titles::dsl::{titles, book_title};
         use crate::diesel::RunQueryDsl;
         use crate::diesel::QueryDsl;
-        titles.find(id).first(conn)
+        titles.find(id).select((crate::schema::titles::id, book_title)).first(conn)
     }
 }
 impl Find<Base> for Record {
@@ -47,8 +80,12 @@ impl Find<Base> for Record {
 }
 impl Save<Base> for Record {
     fn save(conn: &SqliteConnection, value: &Base) -> QueryResult<usize> {
-        use crate::diesel::RunQueryDsl;       
-        diesel::insert_into(titles::table).values(value).execute(conn)
+        use crate::schema::titles::dsl::*;
+        use crate::diesel::ExpressionMethods;
+        use crate::diesel::RunQueryDsl;
+        diesel::insert_into(titles)
+            .values((value, book_title_key.eq(sort_key(&value.book_title))))
+            .execute(conn)
     }
 }
 
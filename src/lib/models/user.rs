@@ -0,0 +1,125 @@
+use crate::schema::users;
+use crate::auth::{hash_password, make_sync_key, verify_password};
+use super::*;
+
+#[derive(Insertable)]
+#[table_name="users"]
+#[derive(Eq, PartialEq, Hash, Clone, Debug)]
+pub struct User {
+    pub user_name: String,
+    pub password_hash: String,
+    pub sync_key: String,
+}
+impl User {
+    pub fn new(name: &str, password: &str) -> Self {
+        Self {
+            user_name: String::from(name.trim()),
+            password_hash: hash_password(password),
+            sync_key: hash_password(&make_sync_key(password)),
+        }
+    }
+}
+
+#[derive(Insertable, Queryable, Debug, Clone)]
+#[table_name="users"]
+pub struct UserRecord {
+    pub id: Id,
+    pub user_name: String,
+    pub password_hash: String,
+    pub user_created: String,
+    pub sync_key: String,
+}
+impl UserRecord {
+    pub fn find_by_name(conn: &SqliteConnection, name: &str) -> QueryResult<Self> {
+        use crate::schema::users::dsl::*;
+        use crate::diesel::ExpressionMethods;
+        use crate::diesel::RunQueryDsl;
+        use crate::diesel::QueryDsl;
+        users.filter(user_name.eq(name)).first(conn)
+    }
+
+    /// The names of the users added before the progress sync, KOReader can't authenticate them
+    /// until the password is set again
+    pub fn load_without_sync_key(conn: &SqliteConnection) -> QueryResult<Vec<String>> {
+        use crate::schema::users::dsl::*;
+        use crate::diesel::ExpressionMethods;
+        use crate::diesel::RunQueryDsl;
+        use crate::diesel::QueryDsl;
+        users.filter(sync_key.eq("")).select(user_name).order(user_name).load(conn)
+    }
+
+    /// Returns the user only if the password matches the stored hash
+    pub fn authenticate(conn: &SqliteConnection, name: &str, password: &str) -> Option<Self> {
+        Self::find_by_name(conn, name)
+            .ok()
+            .filter(|user| verify_password(password, &user.password_hash))
+    }
+
+    /// Returns the user only if the KOReader key matches the stored hash
+    pub fn authenticate_sync(conn: &SqliteConnection, name: &str, key: &str) -> Option<Self> {
+        Self::find_by_name(conn, name)
+            .ok()
+            .filter(|user| verify_password(key, &user.sync_key))
+    }
+
+    pub fn set_password(conn: &SqliteConnection, user: Id, password: &str) -> QueryResult<usize> {
+        use crate::schema::users::dsl::*;
+        use crate::diesel::ExpressionMethods;
+        use crate::diesel::RunQueryDsl;
+        use crate::diesel::QueryDsl;
+        diesel::update(users.find(user))
+            .set((password_hash.eq(hash_password(password)), sync_key.eq(hash_password(&make_sync_key(password)))))
+            .execute(conn)
+    }
+
+    /// Removes the user with the sessions, the shelves, the reading progress and the annotations,
+    /// the downloads of the user become anonymous
+    pub fn remove(conn: &SqliteConnection, user: Id) -> QueryResult<usize> {
+        use crate::diesel::Connection;
+        use crate::diesel::ExpressionMethods;
+        use crate::diesel::RunQueryDsl;
+        use crate::diesel::QueryDsl;
+        use crate::schema::{reading_progress, reviews, sessions, shelves, tag_links};
+        conn.transaction(|| {
+            super::DownloadRecord::forget_user(conn, user)?;
+            diesel::delete(tag_links::table.filter(tag_links::user_id.eq(user))).execute(conn)?;
+            diesel::delete(reviews::table.filter(reviews::user_id.eq(user))).execute(conn)?;
+            diesel::delete(reading_progress::table.filter(reading_progress::user_id.eq(user))).execute(conn)?;
+            diesel::delete(shelves::table.filter(shelves::user_id.eq(user))).execute(conn)?;
+            diesel::delete(sessions::table.filter(sessions::user_id.eq(user))).execute(conn)?;
+            diesel::delete(users::table.find(user)).execute(conn)
+        })
+    }
+}
+
+type Base = User;
+type Record = UserRecord;
+impl Load<Record> for Record {
+    fn load(conn: &SqliteConnection, id: Id) -> QueryResult<Self> {
+        use crate::schema::users::dsl::users;
+        use crate::diesel::RunQueryDsl;
+        use crate::diesel::QueryDsl;
+        users.find(id).first(conn)
+    }
+}
+impl Find<Base> for Record {
+    fn find(conn: &SqliteConnection, value: &Base) -> QueryResult<Id> {
+        use crate::schema::users::dsl::*;
+        use crate::diesel::ExpressionMethods;
+        use crate::diesel::RunQueryDsl;
+        use crate::diesel::QueryDsl;
+        users
+            .filter(user_name.eq(&value.user_name))
+            .select(id)
+            .first(conn)
+    }
+}
+impl Save<Base> for Record {
+    fn save(conn: &SqliteConnection, value: &Base) -> QueryResult<usize> {
+        use crate::diesel::ExpressionMethods;
+        use crate::diesel::RunQueryDsl;
+        diesel::insert_into(users::table)
+            .values((value, users::user_created.eq(timestamp())))
+            .execute(conn)
+    }
+}
@@ -0,0 +1,161 @@
+/// Trims the value and collapses the inner whitespaces
+pub fn clean(value: &str) -> String {
+    value
+        .split_whitespace()
+        .collect::<Vec<&str>>()
+        .join(" ")
+}
+
+/// Folds the value for matching: cleans it, lowercases and replaces 'ё' by 'е'
+pub fn fold(value: &str) -> String {
+    clean(value)
+        .to_lowercase()
+        .replace('ё', "е")
+}
+
+/// Lowercase letters of the Russian and Ukrainian alphabets in the collation order
+const ALPHABET: &str = "абвгґдеёєжзиіїйклмнопрстуфхцчшщъыьэюя";
+/// The letters of the ALPHABET are stored in the sort key starting from this code point,
+/// it is the uppercase Cyrillic block which never appears in the folded value
+const ALPHABET_BASE: u32 = 0x0400;
+
+/// Builds the sort key stored in the database: the folded value with the Cyrillic letters
+/// renumbered by the ALPHABET, so the binary order of the keys is the alphabet order
+/// and a prefix of the value is the prefix of the key
+pub fn sort_key(value: &str) -> String {
+    fold(value)
+        .chars()
+        .map(|c| match ALPHABET.chars().position(|letter| letter == c) {
+            Some(pos) => std::char::from_u32(ALPHABET_BASE + pos as u32).unwrap_or(c),
+            None => c,
+        })
+        .collect()
+}
+
+/// Restores the folded value from the sort key
+pub fn from_sort_key(key: &str) -> String {
+    let letters: Vec<char> = ALPHABET.chars().collect();
+    key.chars()
+        .map(|c| match (c as u32).checked_sub(ALPHABET_BASE) {
+            Some(pos) if (pos as usize) < letters.len() => letters[pos as usize],
+            _ => c,
+        })
+        .collect()
+}
+
+/// Compares the values in the alphabet order
+pub fn collate(a: &str, b: &str) -> std::cmp::Ordering {
+    sort_key(a).cmp(&sort_key(b))
+}
+
+/// Uppercases the first letter of the folded prefix to show it on the navigation button
+pub fn capitalize(value: &str) -> String {
+    let mut chars = value.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+/// Builds the key to match the same book loaded from the different sources,
+/// the books without the title have no key since they would match each other
+pub fn make_match_key(title: &str, authors: &Vec<String>) -> Option<String> {
+    let title = fold(title);
+    if title.is_empty() {
+        return None;
+    }
+    let mut names: Vec<String> = authors.iter()
+        .map(|author| fold(author))
+        .filter(|author| !author.is_empty())
+        .collect();
+    names.sort();
+    names.dedup();
+    Some(format!("{}|{}", title, names.join(";")))
+}
+
+/// The Latin spelling of the Cyrillic letters of the ALPHABET
+const TRANSLIT: &[(char, &str)] = &[
+    ('а', "a"), ('б', "b"), ('в', "v"), ('г', "g"), ('ґ', "g"), ('д', "d"), ('е', "e"), ('є', "ye"),
+    ('ж', "zh"), ('з', "z"), ('и', "i"), ('і', "i"), ('ї', "yi"), ('й', "y"), ('к', "k"), ('л', "l"),
+    ('м', "m"), ('н', "n"), ('о', "o"), ('п', "p"), ('р', "r"), ('с', "s"), ('т', "t"), ('у', "u"),
+    ('ф', "f"), ('х', "kh"), ('ц', "ts"), ('ч', "ch"), ('ш', "sh"), ('щ', "shch"), ('ъ', ""), ('ы', "y"),
+    ('ь', ""), ('э', "e"), ('ю', "yu"), ('я', "ya"),
+];
+/// The longest slug, it is cut at the word boundary
+const SLUG_LENGTH: usize = 64;
+
+/// Builds the readable decoration of the id-based URL: the folded value transliterated
+/// to Latin, the words are joined by the dashes, may be empty
+pub fn slugify(value: &str) -> String {
+    let mut latin = String::new();
+    for c in fold(value).chars() {
+        match TRANSLIT.iter().find(|(letter, _)| *letter == c) {
+            Some((_, spelling)) => latin.push_str(spelling),
+            None if c.is_ascii_alphanumeric() => latin.push(c),
+            None => latin.push(' '),
+        }
+    }
+    let mut slug = String::new();
+    for word in latin.split_whitespace() {
+        if !slug.is_empty() && slug.len() + 1 + word.len() > SLUG_LENGTH {
+            break;
+        }
+        if !slug.is_empty() {
+            slug.push('-');
+        }
+        slug.push_str(word);
+    }
+    slug.truncate(SLUG_LENGTH);
+    slug
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_clean() {
+        assert_eq!(clean(" Константин  "), "Константин");
+        assert_eq!(clean("Анна\u{a0} Мария"), "Анна Мария");
+        assert_eq!(clean(""), "");
+    }
+
+    #[test]
+    fn test_fold() {
+        assert_eq!(fold("  Калбазов   Константин "), "калбазов константин");
+        assert_eq!(fold("Ёлкин"), "елкин");
+        assert_eq!(fold("Пётр\tПетров"), "петр петров");
+    }
+
+    #[test]
+    fn test_collate() {
+        let mut letters = vec!["я", "Ї", "і", "и", "Є", "ж", "ґ", "Г", "z", "A"];
+        letters.sort_by(|a, b| collate(a, b));
+        assert_eq!(letters, vec!["A", "z", "Г", "ґ", "Є", "ж", "и", "і", "Ї", "я"]);
+        assert_eq!(collate("Ёж", "еж"), std::cmp::Ordering::Equal);
+        assert_eq!(from_sort_key(&sort_key("Її Ґанок")), "її ґанок");
+        assert!(sort_key("Калбазов").starts_with(&sort_key("КАЛ")));
+        assert_eq!(capitalize("ка"), "Ка");
+    }
+
+    #[test]
+    fn test_slugify() {
+        assert_eq!(slugify("Толстой Лев Николаевич"), "tolstoy-lev-nikolaevich");
+        assert_eq!(slugify("Война и мир / Том 1?"), "voyna-i-mir-tom-1");
+        assert_eq!(slugify("Щука, Її подвір'я"), "shchuka-yiyi-podvir-ya");
+        assert_eq!(slugify("-"), "");
+        assert!(slugify(&"слово ".repeat(40)).len() <= SLUG_LENGTH);
+    }
+
+    #[test]
+    fn test_make_match_key() {
+        let first = make_match_key("Игра под названием Жизнь ",
+            &vec![String::from("Медведева Ирина Борисовна"), String::from("Медведев Александр  Николаевич")]);
+        let second = make_match_key("игра под названием жизнь",
+            &vec![String::from("Медведев Александр Николаевич"), String::from("медведева ирина борисовна")]);
+        assert_eq!(first, second);
+        assert!(first.is_some());
+        assert_eq!(make_match_key("", &Vec::new()), None);
+        assert_eq!(make_match_key(" ", &vec![String::from("Медведев Александр Николаевич")]), None);
+    }
+}
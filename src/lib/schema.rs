@@ -6,6 +6,22 @@ table! {
         arch_size -> BigInt,
         arch_uuid -> Text,
         arch_done -> Bool,
+        arch_kind -> Text,
+        arch_loaded -> Text,
+        arch_head -> Text,
+        root_id -> Integer,
+    }
+}
+
+table! {
+    author_aliases (id) {
+        id -> Integer,
+        first_name -> Text,
+        middle_name -> Text,
+        last_name -> Text,
+        nickname -> Text,
+        uuid -> Text,
+        author_id -> Integer,
     }
 }
 
@@ -14,6 +30,7 @@ table! {
         id -> Integer,
         book_id -> Integer,
         author_id -> Integer,
+        role -> Text,
     }
 }
 
@@ -25,6 +42,17 @@ table! {
         last_name -> Text,
         nickname -> Text,
         uuid -> Text,
+        first_name_key -> Text,
+        middle_name_key -> Text,
+        last_name_key -> Text,
+    }
+}
+
+table! {
+    book_digests (id) {
+        id -> Integer,
+        book_id -> Integer,
+        document -> Text,
     }
 }
 
@@ -37,6 +65,34 @@ table! {
         book_size -> BigInt,
         book_crc32 -> BigInt,
         book_offset -> BigInt,
+        book_loaded -> Text,
+        book_lang -> Text,
+        book_deleted -> Bool,
+    }
+}
+
+table! {
+    documents (id) {
+        id -> Integer,
+        book_id -> Integer,
+        canon_id -> Integer,
+        doc_id -> Text,
+        doc_version -> Text,
+        doc_date -> Text,
+        match_key -> Text,
+        program_used -> Text,
+        src_url -> Text,
+        creator -> Text,
+    }
+}
+
+table! {
+    downloads (id) {
+        id -> Integer,
+        book_id -> Integer,
+        user_id -> Nullable<Integer>,
+        download_format -> Text,
+        download_time -> Text,
     }
 }
 
@@ -79,6 +135,121 @@ table! {
     }
 }
 
+table! {
+    isbns (id) {
+        id -> Integer,
+        book_id -> Integer,
+        isbn -> Text,
+        isbn_key -> Text,
+    }
+}
+
+table! {
+    originals (id) {
+        id -> Integer,
+        book_id -> Integer,
+        src_title -> Text,
+        src_authors -> Text,
+        src_lang -> Text,
+    }
+}
+
+table! {
+    publications (id) {
+        id -> Integer,
+        book_id -> Integer,
+        book_name -> Text,
+        publisher -> Text,
+        city -> Text,
+        year -> Text,
+    }
+}
+
+table! {
+    reading_progress (id) {
+        id -> Integer,
+        user_id -> Integer,
+        document -> Text,
+        progress -> Text,
+        percentage -> Double,
+        device -> Text,
+        device_id -> Text,
+        progress_time -> BigInt,
+    }
+}
+
+table! {
+    reviews (id) {
+        id -> Integer,
+        user_id -> Integer,
+        book_id -> Integer,
+        rating -> Integer,
+        review -> Text,
+        review_added -> Text,
+    }
+}
+
+table! {
+    roots (id) {
+        id -> Integer,
+        root_name -> Text,
+        root_path -> Text,
+    }
+}
+
+table! {
+    series (id) {
+        id -> Integer,
+        series_name -> Text,
+        series_name_key -> Text,
+    }
+}
+
+table! {
+    series_links (id) {
+        id -> Integer,
+        book_id -> Integer,
+        series_id -> Integer,
+        serno -> Integer,
+    }
+}
+
+table! {
+    sessions (id) {
+        id -> Integer,
+        user_id -> Integer,
+        token -> Text,
+        session_created -> Text,
+    }
+}
+
+table! {
+    shelves (id) {
+        id -> Integer,
+        user_id -> Integer,
+        book_id -> Integer,
+        shelf -> Text,
+        shelf_added -> Text,
+    }
+}
+
+table! {
+    tag_links (id) {
+        id -> Integer,
+        user_id -> Integer,
+        book_id -> Integer,
+        tag_id -> Integer,
+    }
+}
+
+table! {
+    tags (id) {
+        id -> Integer,
+        tag_name -> Text,
+        tag_name_key -> Text,
+    }
+}
+
 table! {
     title_links (id) {
         id -> Integer,
@@ -91,29 +262,75 @@ table! {
     titles (id) {
         id -> Integer,
         book_title -> Text,
+        book_title_key -> Text,
+    }
+}
+
+table! {
+    users (id) {
+        id -> Integer,
+        user_name -> Text,
+        password_hash -> Text,
+        user_created -> Text,
+        sync_key -> Text,
     }
 }
 
+joinable!(author_aliases -> authors (author_id));
 joinable!(author_links -> authors (author_id));
 joinable!(author_links -> books (book_id));
+joinable!(book_digests -> books (book_id));
 joinable!(books -> archives (arch_id));
+joinable!(downloads -> books (book_id));
+joinable!(downloads -> users (user_id));
 joinable!(genre_links -> books (book_id));
 joinable!(genre_links -> genres (genre_id));
 joinable!(genre_names -> genre_groups (group_id));
 joinable!(genre_synonyms -> genre_names (synonym_id));
+joinable!(isbns -> books (book_id));
+joinable!(originals -> books (book_id));
+joinable!(publications -> books (book_id));
+joinable!(reading_progress -> users (user_id));
+joinable!(reviews -> books (book_id));
+joinable!(reviews -> users (user_id));
+joinable!(series_links -> books (book_id));
+joinable!(series_links -> series (series_id));
+joinable!(sessions -> users (user_id));
+joinable!(shelves -> books (book_id));
+joinable!(shelves -> users (user_id));
+joinable!(tag_links -> books (book_id));
+joinable!(tag_links -> tags (tag_id));
+joinable!(tag_links -> users (user_id));
 joinable!(title_links -> books (book_id));
 joinable!(title_links -> titles (title_id));
 
 allow_tables_to_appear_in_same_query!(
     archives,
+    author_aliases,
     author_links,
     authors,
+    book_digests,
     books,
+    documents,
+    downloads,
     genre_groups,
     genre_links,
     genre_names,
     genre_synonyms,
     genres,
+    isbns,
+    originals,
+    publications,
+    reading_progress,
+    reviews,
+    roots,
+    series,
+    series_links,
+    sessions,
+    shelves,
+    tag_links,
+    tags,
     title_links,
     titles,
+    users,
 );
@@ -0,0 +1,226 @@
+use std::fmt;
+use std::io;
+use actix_web::{HttpResponse, ResponseError};
+use actix_web::error::BlockingError;
+use actix_web::http::StatusCode;
+use serde::Serialize;
+
+/// The failure of the request handler, the message of the internal error is only logged
+#[derive(Debug, Clone, PartialEq)]
+pub enum ServiceError {
+    NotFound(String),
+    BadRequest(String),
+    Forbidden(String),
+    Internal(String),
+}
+
+/// The body of the error response and the context of the error page
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ErrorBody {
+    pub status: u16,
+    pub error: String,
+    pub message: String,
+}
+
+impl ServiceError {
+    /// The message shown to the user
+    pub fn get_message(&self) -> String {
+        match self {
+            ServiceError::NotFound(message) => message.clone(),
+            ServiceError::BadRequest(message) => message.clone(),
+            ServiceError::Forbidden(message) => message.clone(),
+            ServiceError::Internal(_) => ErrorBody::get_default_message(StatusCode::INTERNAL_SERVER_ERROR),
+        }
+    }
+
+    pub fn get_body(&self) -> ErrorBody {
+        ErrorBody::new(self.status_code(), &self.get_message())
+    }
+}
+
+impl ErrorBody {
+    pub fn new(status: StatusCode, message: &str) -> Self {
+        Self {
+            status: status.as_u16(),
+            error: String::from(status.canonical_reason().unwrap_or("")),
+            message: String::from(message),
+        }
+    }
+
+    /// The body of the error raised outside of the handlers, e.g. by the router
+    pub fn from_status(status: StatusCode) -> Self {
+        Self::new(status, &Self::get_default_message(status))
+    }
+
+    fn get_default_message(status: StatusCode) -> String {
+        match status {
+            StatusCode::NOT_FOUND => String::from("Страница не найдена"),
+            StatusCode::BAD_REQUEST => String::from("Неверный запрос"),
+            StatusCode::FORBIDDEN => String::from("Доступ запрещен"),
+            _ => String::from("Внутренняя ошибка сервера"),
+        }
+    }
+}
+
+impl fmt::Display for ServiceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ServiceError::NotFound(message) => write!(f, "Not found: {}", message),
+            ServiceError::BadRequest(message) => write!(f, "Bad request: {}", message),
+            ServiceError::Forbidden(message) => write!(f, "Forbidden: {}", message),
+            ServiceError::Internal(message) => write!(f, "Internal error: {}", message),
+        }
+    }
+}
+
+impl ResponseError for ServiceError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            ServiceError::NotFound(_) => StatusCode::NOT_FOUND,
+            ServiceError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            ServiceError::Forbidden(_) => StatusCode::FORBIDDEN,
+            ServiceError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    /// The JSON body, the pages replace it with the rendered error template
+    fn error_response(&self) -> HttpResponse {
+        if let ServiceError::Internal(_) = self {
+            log::error!("{}", self);
+        }
+        HttpResponse::build(self.status_code()).json(self.get_body())
+    }
+}
+
+impl From<diesel::result::Error> for ServiceError {
+    fn from(error: diesel::result::Error) -> Self {
+        match error {
+            diesel::result::Error::NotFound => ServiceError::NotFound(ErrorBody::get_default_message(StatusCode::NOT_FOUND)),
+            _ => ServiceError::Internal(error.to_string()),
+        }
+    }
+}
+
+impl From<diesel::r2d2::PoolError> for ServiceError {
+    fn from(error: diesel::r2d2::PoolError) -> Self {
+        ServiceError::Internal(format!("couldn't get db connection from pool: {}", error))
+    }
+}
+
+impl From<handlebars::RenderError> for ServiceError {
+    fn from(error: handlebars::RenderError) -> Self {
+        ServiceError::Internal(format!("couldn't render template: {}", error))
+    }
+}
+
+/// The files of the known books are expected to be there, the missing one is the fault of the server
+impl From<io::Error> for ServiceError {
+    fn from(error: io::Error) -> Self {
+        ServiceError::Internal(error.to_string())
+    }
+}
+
+impl<E: Into<ServiceError> + fmt::Debug> From<BlockingError<E>> for ServiceError {
+    fn from(error: BlockingError<E>) -> Self {
+        match error {
+            BlockingError::Error(error) => error.into(),
+            BlockingError::Canceled => ServiceError::Internal(String::from("the blocking operation is canceled")),
+        }
+    }
+}
+
+/// Names the missing resource of the handler, the database only knows that the row is missing
+pub trait OrNotFound<T> {
+    fn or_not_found(self, message: &str) -> Result<T, ServiceError>;
+}
+impl<T, E: Into<ServiceError>> OrNotFound<T> for Result<T, E> {
+    fn or_not_found(self, message: &str) -> Result<T, ServiceError> {
+        self.map_err(|error| match error.into() {
+            ServiceError::NotFound(_) => ServiceError::NotFound(String::from(message)),
+            error => error,
+        })
+    }
+}
+
+/// The API clients get the JSON errors instead of the error pages
+pub fn is_api_path(path: &str) -> bool {
+    ["/api/", "/syncs/", "/users/"].iter().any(|prefix| path.starts_with(prefix))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use actix_web::body::{Body, ResponseBody};
+
+    fn get_json(error: &ServiceError) -> serde_json::Value {
+        let mut response = error.error_response();
+        match response.take_body() {
+            ResponseBody::Body(Body::Bytes(bytes)) => serde_json::from_slice(&bytes).unwrap(),
+            _ => panic!("unexpected body"),
+        }
+    }
+
+    #[test]
+    fn test_not_found() {
+        let error = ServiceError::from(diesel::result::Error::NotFound);
+        assert_eq!(error.status_code(), StatusCode::NOT_FOUND);
+        assert_eq!(get_json(&error), serde_json::json!({"status": 404, "error": "Not Found", "message": "Страница не найдена"}));
+
+    }
+
+    #[test]
+    fn test_or_not_found() {
+        let result: Result<(), _> = Err(diesel::result::Error::NotFound);
+        assert_eq!(result.or_not_found("Автор не найден"), Err(ServiceError::NotFound(String::from("Автор не найден"))));
+        let result: Result<(), _> = Err(BlockingError::Error(diesel::result::Error::NotFound));
+        assert_eq!(result.or_not_found("Серия не найдена"), Err(ServiceError::NotFound(String::from("Серия не найдена"))));
+        let result: Result<(), _> = Err(diesel::result::Error::RollbackTransaction);
+        assert_eq!(result.or_not_found("Автор не найден").unwrap_err().status_code(), StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(Ok::<_, ServiceError>(1).or_not_found("Автор не найден"), Ok(1));
+    }
+
+    #[test]
+    fn test_bad_request() {
+        let error = ServiceError::BadRequest(String::from("invalid digit found in string"));
+        assert_eq!(error.status_code(), StatusCode::BAD_REQUEST);
+        assert_eq!(get_json(&error)["message"], "invalid digit found in string");
+    }
+
+    #[test]
+    fn test_forbidden() {
+        let error = ServiceError::Forbidden(String::from("Доступ только для администраторов"));
+        assert_eq!(error.status_code(), StatusCode::FORBIDDEN);
+        assert_eq!(get_json(&error)["error"], "Forbidden");
+    }
+
+    #[test]
+    fn test_internal() {
+        let error = ServiceError::from(diesel::result::Error::RollbackTransaction);
+        assert_eq!(error.status_code(), StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(get_json(&error)["message"], "Внутренняя ошибка сервера");
+
+        let error = ServiceError::from(io::Error::new(io::ErrorKind::PermissionDenied, "/tmp"));
+        assert_eq!(error.status_code(), StatusCode::INTERNAL_SERVER_ERROR);
+        let error = ServiceError::from(io::Error::new(io::ErrorKind::NotFound, "a.zip"));
+        assert_eq!(error.status_code(), StatusCode::INTERNAL_SERVER_ERROR);
+
+        let error = ServiceError::from(BlockingError::<diesel::result::Error>::Canceled);
+        assert_eq!(error.status_code(), StatusCode::INTERNAL_SERVER_ERROR);
+        let error = ServiceError::from(BlockingError::Error(diesel::result::Error::NotFound));
+        assert_eq!(error.status_code(), StatusCode::NOT_FOUND);
+    }
+
+    #[test]
+    fn test_from_status() {
+        assert_eq!(ErrorBody::from_status(StatusCode::NOT_FOUND).message, "Страница не найдена");
+        assert_eq!(ErrorBody::from_status(StatusCode::BAD_GATEWAY).error, "Bad Gateway");
+    }
+
+    #[test]
+    fn test_is_api_path() {
+        assert!(is_api_path("/api/new"));
+        assert!(is_api_path("/syncs/progress"));
+        assert!(!is_api_path("/download/a.zip/1.fb2"));
+        assert!(!is_api_path("/apikey/"));
+    }
+}
@@ -0,0 +1,288 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use crate::loader::{checksums, md5sum};
+use crate::models::{ArchiveRecord, Book, BookRecord, Id, QueryResult, Roots, SourceKind, SqliteConnection};
+
+/// The difference between the database and the files of the library
+#[derive(Debug, Clone, PartialEq)]
+pub enum Problem {
+    MissingArchive { archive: Id, path: PathBuf },
+    MovedArchive { archive: Id, path: PathBuf, found: PathBuf },
+    ArchiveSize { archive: Id, path: PathBuf, expected: i64, actual: i64 },
+    ArchiveHash { archive: Id, path: PathBuf, expected: String, actual: String },
+    UnreadableArchive { archive: Id, path: PathBuf, error: String },
+    MissingBook { book: Id, path: PathBuf, file: String },
+    ChangedBook { book: Id, path: PathBuf, file: String, field: &'static str, expected: i64, actual: i64 },
+    ShiftedBook { book: Id, path: PathBuf, file: String, field: &'static str, expected: i64, actual: i64 },
+    CorruptedBook { book: Id, path: PathBuf, file: String, error: String },
+}
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Problem::MissingArchive { archive, path } =>
+                write!(f, "Archive {} is not found: {}", archive, path.to_string_lossy()),
+            Problem::MovedArchive { archive, path, found } =>
+                write!(f, "Archive {} is moved: {} -> {}", archive, path.to_string_lossy(), found.to_string_lossy()),
+            Problem::ArchiveSize { archive, path, expected, actual } =>
+                write!(f, "Archive {} size {} doesn't match recorded size {}: {}", archive, actual, expected, path.to_string_lossy()),
+            Problem::ArchiveHash { archive, path, expected, actual } =>
+                write!(f, "Archive {} hash {} doesn't match recorded hash {}: {}", archive, actual, expected, path.to_string_lossy()),
+            Problem::UnreadableArchive { archive, path, error } =>
+                write!(f, "Archive {} can't be read: {}: {}", archive, path.to_string_lossy(), error),
+            Problem::MissingBook { book, path, file } =>
+                write!(f, "Book {} is not found: {} in {}", book, file, path.to_string_lossy()),
+            Problem::ChangedBook { book, path, file, field, expected, actual } =>
+                write!(f, "Book {} {} {} doesn't match recorded {}: {} in {}", book, field, actual, expected, file, path.to_string_lossy()),
+            Problem::ShiftedBook { book, path, file, field, expected, actual } =>
+                write!(f, "Book {} {} {} doesn't match recorded {}: {} in {}", book, field, actual, expected, file, path.to_string_lossy()),
+            Problem::CorruptedBook { book, path, file, error } =>
+                write!(f, "Book {} is corrupted: {} in {}: {}", book, file, path.to_string_lossy(), error),
+        }
+    }
+}
+
+/// The differing field of the book entry with the recorded and the actual values
+pub type Difference = (&'static str, i64, i64);
+
+/// The recorded and the actual values of the book entry, the changed content and the moved data
+/// are reported separately, since only the latter can be fixed
+pub fn compare_book(record: &BookRecord, entry: &Book) -> (Vec<Difference>, Vec<Difference>) {
+    let mut changed = Vec::new();
+    if record.book_size != entry.book_size {
+        changed.push(("size", record.book_size, entry.book_size));
+    }
+    // The books imported from INPX without the archive have no CRC
+    if record.book_crc32 != 0 && record.book_crc32 != entry.book_crc32 {
+        changed.push(("crc32", record.book_crc32, entry.book_crc32));
+    }
+    let mut shifted = Vec::new();
+    if record.book_offset != entry.book_offset {
+        shifted.push(("offset", record.book_offset, entry.book_offset));
+    }
+    if record.book_zip_size != entry.book_zip_size {
+        shifted.push(("compressed size", record.book_zip_size, entry.book_zip_size));
+    }
+    (changed, shifted)
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct VerifyReport {
+    pub archives: usize,
+    pub books: usize,
+    pub problems: Vec<Problem>,
+    pub fixed: usize,
+}
+impl VerifyReport {
+    fn add(&mut self, problem: Problem, fixed: bool) {
+        self.problems.push(problem);
+        if fixed {
+            self.fixed += 1;
+        }
+    }
+}
+
+/// Checks the archives and the books recorded in the database against the files of the library
+pub struct Verifier {
+    search: Vec<PathBuf>,
+    full: bool,
+    fix: bool,
+    found: Option<HashMap<String, Vec<PathBuf>>>,
+    roots: Roots,
+}
+impl Verifier {
+    /// The search directories and the library roots are searched for the archives not found at the recorded path, the full check hashes
+    /// the whole archives and reads every book, the fix updates the paths of the moved archives and the offsets of the shifted books
+    pub fn new(search: Vec<PathBuf>, full: bool, fix: bool) -> Self {
+        Self {
+            search,
+            full,
+            fix,
+            found: None,
+            roots: Roots::default(),
+        }
+    }
+
+    pub fn verify(&mut self, conn: &SqliteConnection) -> QueryResult<VerifyReport> {
+        let mut report = VerifyReport::default();
+        self.roots = Roots::load(conn)?;
+        for archive in ArchiveRecord::load_all(conn)? {
+            report.archives += 1;
+            self.verify_archive(conn, &archive, &mut report)?;
+        }
+        Ok(report)
+    }
+
+    fn verify_archive(&mut self, conn: &SqliteConnection, archive: &ArchiveRecord, report: &mut VerifyReport) -> QueryResult<()> {
+        let mut path = self.roots.get_home(archive.root_id, &archive.arch_home).join(&archive.arch_name);
+        if !path.is_file() {
+            match self.find_moved(archive) {
+                Some(found) => {
+                    let (root, home) = self.roots.split(found.parent().unwrap_or(Path::new("")));
+                    let fixed = self.fix && ArchiveRecord::relocate(conn, archive.id, root, &home)? > 0;
+                    report.add(Problem::MovedArchive { archive: archive.id, path, found: found.clone() }, fixed);
+                    path = found;
+                },
+                None => {
+                    report.add(Problem::MissingArchive { archive: archive.id, path }, false);
+                    return Ok(());
+                }
+            }
+        }
+
+        let size = fs::metadata(&path).map(|meta| meta.len() as i64).unwrap_or_default();
+        if size != archive.arch_size {
+            report.add(Problem::ArchiveSize { archive: archive.id, path: path.clone(), expected: archive.arch_size, actual: size }, false);
+        }
+        if !archive.arch_head.is_empty() {
+            let checksums = if self.full && is_content_hash(&archive.arch_uuid) {
+                checksums(&path)
+            } else {
+                md5sum(&path, false).map(|head| (head, archive.arch_uuid.clone()))
+            };
+            let (head, uuid) = match checksums {
+                Ok(checksums) => checksums,
+                Err(error) => {
+                    report.add(Problem::UnreadableArchive { archive: archive.id, path, error: error.to_string() }, false);
+                    return Ok(());
+                }
+            };
+            if head != archive.arch_head {
+                report.add(Problem::ArchiveHash { archive: archive.id, path: path.clone(), expected: archive.arch_head.clone(), actual: head }, false);
+            } else if uuid != archive.arch_uuid {
+                report.add(Problem::ArchiveHash { archive: archive.id, path: path.clone(), expected: archive.arch_uuid.clone(), actual: uuid }, false);
+            }
+        }
+
+        let kind = SourceKind::parse(&archive.arch_kind);
+        let entries = match read_entries(archive.id, &path, kind) {
+            Ok(entries) => entries,
+            Err(error) => {
+                report.add(Problem::UnreadableArchive { archive: archive.id, path, error: error.to_string() }, false);
+                return Ok(());
+            }
+        };
+        let books = BookRecord::load_for_archive(conn, archive.id)?;
+        report.books += books.len();
+        for book in books.iter() {
+            let file = book.book_file.clone();
+            let entry = match entries.get(&book.book_file) {
+                Some(entry) => entry,
+                None => {
+                    report.add(Problem::MissingBook { book: book.id, path: path.clone(), file }, false);
+                    continue;
+                }
+            };
+            let (changed, shifted) = compare_book(book, entry);
+            for (field, expected, actual) in changed.iter().cloned() {
+                report.add(Problem::ChangedBook { book: book.id, path: path.clone(), file: file.clone(), field, expected, actual }, false);
+            }
+            let fixed = self.fix && !shifted.is_empty() && changed.is_empty() && BookRecord::move_to(conn, book.id, entry)? > 0;
+            for (field, expected, actual) in shifted.iter().cloned() {
+                report.add(Problem::ShiftedBook { book: book.id, path: path.clone(), file: file.clone(), field, expected, actual }, fixed);
+            }
+            if self.full && kind.is_zipped() && changed.is_empty() {
+                if let Err(error) = read_book(&path, &book.book_file) {
+                    report.add(Problem::CorruptedBook { book: book.id, path: path.clone(), file, error: error.to_string() }, false);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The file with the name, the size and the hash of the archive in the search directories and the roots
+    fn find_moved(&mut self, archive: &ArchiveRecord) -> Option<PathBuf> {
+        if self.found.is_none() {
+            let mut found = HashMap::new();
+            for dir in self.search.iter().cloned().chain(self.roots.get_paths()) {
+                scan_root(&dir, &mut found);
+            }
+            self.found = Some(found);
+        }
+        let candidates = self.found.as_ref()?.get(&archive.arch_name)?;
+        candidates.iter()
+            .filter(|candidate| 0 == archive.arch_size
+                || fs::metadata(candidate).map(|meta| meta.len() as i64 == archive.arch_size).unwrap_or(false))
+            .find(|candidate| archive.arch_head.is_empty() || md5sum(candidate, false).ok().as_ref() == Some(&archive.arch_head))
+            .cloned()
+    }
+}
+
+/// The INPX imports without the archive, the replaced archives and the archives loaded before the full hashes
+/// have no full content hash
+fn is_content_hash(uuid: &str) -> bool {
+    !uuid.contains(':')
+}
+
+fn scan_root(dir: &Path, found: &mut HashMap<String, Vec<PathBuf>>) {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return,
+    };
+    for entry in entries.filter_map(|entry| entry.ok()) {
+        let path = entry.path();
+        if path.is_dir() {
+            scan_root(&path, found);
+        } else if let Some(name) = path.file_name().map(|name| name.to_string_lossy().to_string()) {
+            let path = path.canonicalize().unwrap_or(path);
+            found.entry(name).or_default().push(path);
+        }
+    }
+}
+
+fn read_entries(archive: Id, path: &Path, kind: SourceKind) -> io::Result<HashMap<String, Book>> {
+    let mut entries = HashMap::new();
+    if kind.is_zipped() {
+        let mut zip = zip::ZipArchive::new(fs::File::open(path)?)?;
+        for i in 0..zip.len() {
+            let file = zip.by_index(i)?;
+            entries.insert(String::from(file.name()), Book::new(archive, &file));
+        }
+    } else {
+        let book = Book::from_file(archive, path)?;
+        entries.insert(book.book_file.clone(), book);
+    }
+    Ok(entries)
+}
+
+/// Reading the entry to the end checks its CRC
+fn read_book(path: &Path, name: &str) -> io::Result<u64> {
+    let mut zip = zip::ZipArchive::new(fs::File::open(path)?)?;
+    let mut file = zip.by_name(name)?;
+    io::copy(&mut file, &mut io::sink())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_compare_book() {
+        let record = BookRecord {
+            id: 1,
+            arch_id: 1,
+            book_file: String::from("1.fb2"),
+            book_zip_size: 100,
+            book_size: 300,
+            book_crc32: 0x1234,
+            book_offset: 40,
+            book_loaded: String::new(),
+            book_lang: String::from("ru"),
+            book_deleted: false,
+        };
+        let entry = Book {
+            arch_id: 1,
+            book_file: String::from("1.fb2"),
+            book_zip_size: 100,
+            book_size: 300,
+            book_crc32: 0x1234,
+            book_offset: 40,
+        };
+        assert_eq!(compare_book(&record, &entry), (Vec::new(), Vec::new()));
+        assert_eq!(compare_book(&record, &Book { book_offset: 80, ..entry.clone() }), (Vec::new(), vec![("offset", 40, 80)]));
+        assert_eq!(compare_book(&record, &Book { book_crc32: 0x4321, ..entry.clone() }).0, vec![("crc32", 0x1234, 0x4321)]);
+        assert_eq!(compare_book(&BookRecord { book_crc32: 0, ..record.clone() }, &entry), (Vec::new(), Vec::new()));
+    }
+}
@@ -0,0 +1,195 @@
+use std::panic::{self, AssertUnwindSafe};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use actix::prelude::*;
+use serde::Serialize;
+
+use crate::database::Manager;
+use crate::loader::Loader;
+use crate::models::{timestamp, Id};
+
+/// The finished jobs shown by the admin pages
+pub const MAX_REPORTS: usize = 20;
+
+/// The change of the library made by the worker one after another
+#[derive(Message, Debug, Clone)]
+#[rtype(result = "()")]
+pub enum LibraryJob {
+    Load(PathBuf),
+    Remove(Id, String),
+}
+impl LibraryJob {
+    pub fn get_name(&self) -> String {
+        match self {
+            LibraryJob::Load(path) => format!("Загрузка {}", path.to_string_lossy()),
+            LibraryJob::Remove(_, name) => format!("Удаление {}", name),
+        }
+    }
+}
+
+/// The books processed by the running job
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LoadProgress {
+    pub name: String,
+    pub started: String,
+    pub done: usize,
+    pub total: usize,
+    pub percent: usize,
+}
+
+/// The result of the finished job, the errors are limited by loader::MAX_ERRORS
+#[derive(Debug, Clone, PartialEq, Default, Serialize)]
+pub struct LoadReport {
+    pub name: String,
+    pub started: String,
+    pub finished: String,
+    pub total: usize,
+    pub added: usize,
+    pub skipped: usize,
+    pub removed: usize,
+    pub failed: usize,
+    pub errors: Vec<String>,
+}
+
+/// The state of the worker shared with the request handlers
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct LoadStatus {
+    pub current: Option<LoadProgress>,
+    pub queue: Vec<String>,
+    pub reports: Vec<LoadReport>,
+}
+impl LoadStatus {
+    pub fn enqueue(&mut self, job: &LibraryJob) {
+        self.queue.push(job.get_name());
+    }
+
+    pub fn is_busy(&self) -> bool {
+        self.current.is_some() || !self.queue.is_empty()
+    }
+
+    fn start(&mut self, name: &str) {
+        if let Some(i) = self.queue.iter().position(|queued| queued == name) {
+            self.queue.remove(i);
+        }
+        self.current = Some(LoadProgress {
+            name: String::from(name),
+            started: timestamp(),
+            done: 0,
+            total: 0,
+            percent: 0,
+        });
+    }
+
+    fn set_progress(&mut self, done: usize, total: usize) {
+        if let Some(ref mut current) = self.current {
+            current.done = done;
+            current.total = total;
+            current.percent = (100 * done).checked_div(total).unwrap_or(0);
+        }
+    }
+
+    fn finish(&mut self, mut report: LoadReport) {
+        if let Some(current) = self.current.take() {
+            report.name = current.name;
+            report.started = current.started;
+        }
+        report.finished = timestamp();
+        self.reports.insert(0, report);
+        self.reports.truncate(MAX_REPORTS);
+    }
+}
+
+pub type SharedStatus = Arc<Mutex<LoadStatus>>;
+
+/// Loads and removes the archives in the background thread of the service
+pub struct LibraryWorker {
+    database: String,
+    status: SharedStatus,
+}
+impl LibraryWorker {
+    pub fn new(database: &str, status: SharedStatus) -> Self {
+        Self {
+            database: String::from(database),
+            status,
+        }
+    }
+
+    /// Starts the single worker thread, so the writes to the database never overlap
+    pub fn start(database: &str, status: SharedStatus) -> Addr<Self> {
+        let database = String::from(database);
+        SyncArbiter::start(1, move || Self::new(&database, status.clone()))
+    }
+
+    fn run(&self, job: &LibraryJob) -> Result<LoadReport, String> {
+        let manager = Manager::open(&self.database)?;
+        match job {
+            LibraryJob::Load(path) => {
+                let mut loader = Loader::new(manager);
+                let status = self.status.clone();
+                loader.set_progress(move |done, total| status.lock().unwrap().set_progress(done, total));
+                loader.set_archive_transactions(true);
+                loader.load(path);
+                Ok(LoadReport {
+                    total: loader.total_counter,
+                    added: loader.manager.books.added as usize,
+                    skipped: loader.skip_counter,
+                    failed: loader.error_counter,
+                    errors: loader.errors.clone(),
+                    ..Default::default()
+                })
+            },
+            LibraryJob::Remove(archive, _) => {
+                let mut manager = manager;
+                let removed = manager.remove_archive(*archive).map_err(|e| e.to_string())?;
+                Ok(LoadReport { removed, ..Default::default() })
+            },
+        }
+    }
+}
+
+impl Actor for LibraryWorker {
+    type Context = SyncContext<Self>;
+}
+
+impl Handler<LibraryJob> for LibraryWorker {
+    type Result = ();
+
+    /// The failed job is reported and rolled back, the worker keeps running
+    fn handle(&mut self, job: LibraryJob, _: &mut Self::Context) {
+        self.status.lock().unwrap().start(&job.get_name());
+        let report = match panic::catch_unwind(AssertUnwindSafe(|| self.run(&job))) {
+            Ok(Ok(report)) => report,
+            Ok(Err(message)) => LoadReport { failed: 1, errors: vec![message], ..Default::default() },
+            Err(cause) => {
+                let message = cause.downcast_ref::<String>().cloned()
+                    .or_else(|| cause.downcast_ref::<&str>().map(|message| String::from(*message)))
+                    .unwrap_or(String::from("The job is aborted"));
+                LoadReport { failed: 1, errors: vec![message], ..Default::default() }
+            },
+        };
+        self.status.lock().unwrap().finish(report);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_load_status() {
+        let job = LibraryJob::Remove(1, String::from("a.zip"));
+        let mut status = LoadStatus::default();
+        status.enqueue(&job);
+        assert!(status.is_busy());
+
+        status.start(&job.get_name());
+        assert!(status.queue.is_empty());
+        status.set_progress(1, 4);
+        assert_eq!(status.current.as_ref().map(|current| current.percent), Some(25));
+
+        status.finish(LoadReport { removed: 4, ..Default::default() });
+        assert!(!status.is_busy());
+        assert_eq!(status.reports[0].name, "Удаление a.zip");
+        assert_eq!(status.reports[0].removed, 4);
+    }
+}
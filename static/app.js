@@ -0,0 +1,19 @@
+// fb2c web UI: the menu of the narrow screens and the confirmation of the removals
+document.addEventListener('DOMContentLoaded', function () {
+    var toggle = document.querySelector('.menu-toggle');
+    var menu = document.getElementById('site-menu');
+    if (toggle && menu) {
+        toggle.addEventListener('click', function () {
+            var open = menu.classList.toggle('open');
+            toggle.setAttribute('aria-expanded', open ? 'true' : 'false');
+        });
+    }
+
+    document.querySelectorAll('[data-confirm]').forEach(function (button) {
+        button.addEventListener('click', function (event) {
+            if (!window.confirm(button.getAttribute('data-confirm'))) {
+                event.preventDefault();
+            }
+        });
+    });
+});